@@ -0,0 +1,255 @@
+//! Local receiver for ARK `callback_url` notifications.
+//!
+//! When a public callback URL is configured (a tunnel or reverse proxy that
+//! forwards to `listen_addr`), video tasks are created with `callback_url` set
+//! and ARK POSTs the task status body to us on every status change.
+//!
+//! A callback is only treated as a wake-up: the waiting task re-queries
+//! `GET /contents/generations/tasks/{id}` before trusting any result, so a
+//! forged or replayed POST can at most cause one extra status request.
+//!
+//! Protocol: a minimal HTTP/1.1 server — one request per connection, JSON body
+//! with `Content-Length`, always answered with `Connection: close`.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use super::types::VideoTaskStatus;
+
+/// Default local address for the receiver; the tunnel/proxy forwards here.
+pub const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:8788";
+
+/// Upper bound on request headers we are willing to buffer.
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+/// Upper bound on a callback body (ARK bodies are a few hundred bytes).
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Callbacks for ARK task IDs nobody has registered yet (e.g. arriving before
+/// `create_video_task` returned). Oldest entries are discarded past this.
+const MAX_EARLY_CALLBACKS: usize = 256;
+
+// ---------------------------------------------------------------------------
+// Registry — routes callbacks to the task waiting on that ARK task ID
+// ---------------------------------------------------------------------------
+
+#[derive(Default)]
+struct Registry {
+    waiting: HashMap<String, mpsc::UnboundedSender<VideoTaskStatus>>,
+    early: Vec<VideoTaskStatus>,
+}
+
+/// Cloneable handle shared by the HTTP receiver and the video tasks.
+#[derive(Clone)]
+pub struct ArkCallbacks {
+    public_url: String,
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl ArkCallbacks {
+    /// URL sent to ARK as `callback_url`.
+    pub fn public_url(&self) -> &str {
+        &self.public_url
+    }
+
+    /// Start receiving callbacks for `ark_task_id`. Any callback that arrived
+    /// before registration is delivered immediately.
+    pub fn register(&self, ark_task_id: &str) -> mpsc::UnboundedReceiver<VideoTaskStatus> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut reg = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        let early = std::mem::take(&mut reg.early);
+        for status in early {
+            if status.id.as_deref() == Some(ark_task_id) {
+                let _ = tx.send(status);
+            } else {
+                reg.early.push(status);
+            }
+        }
+        reg.waiting.insert(ark_task_id.to_string(), tx);
+        rx
+    }
+
+    /// Stop routing callbacks for `ark_task_id`.
+    pub fn unregister(&self, ark_task_id: &str) {
+        let mut reg = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        reg.waiting.remove(ark_task_id);
+    }
+
+    fn dispatch(&self, status: VideoTaskStatus) {
+        let Some(id) = status.id.clone() else {
+            warn!("ARK callback without task id ignored");
+            return;
+        };
+        let mut reg = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        match reg.waiting.get(&id) {
+            Some(tx) => {
+                let _ = tx.send(status);
+            }
+            None => {
+                if reg.early.len() >= MAX_EARLY_CALLBACKS {
+                    reg.early.remove(0);
+                }
+                reg.early.push(status);
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// HTTP receiver
+// ---------------------------------------------------------------------------
+
+/// The bound listener. Binding happens synchronously in [`bind`] so a port
+/// conflict is reported at startup instead of silently dropping callbacks.
+pub struct CallbackServer {
+    listener: std::net::TcpListener,
+    callbacks: ArkCallbacks,
+}
+
+/// Bind the receiver on `listen_addr` (e.g. "127.0.0.1:8788"). ARK will be
+/// told to POST to `public_url`, which must forward to this address.
+pub fn bind(listen_addr: &str, public_url: &str) -> Result<(ArkCallbacks, CallbackServer)> {
+    let listen_addr: SocketAddr = listen_addr
+        .parse()
+        .with_context(|| format!("invalid callback listen address \"{listen_addr}\""))?;
+    let listener = std::net::TcpListener::bind(listen_addr)
+        .with_context(|| format!("failed to bind ARK callback receiver on {listen_addr}"))?;
+    listener.set_nonblocking(true)?;
+    let callbacks = ArkCallbacks {
+        public_url: public_url.to_string(),
+        registry: Arc::new(Mutex::new(Registry::default())),
+    };
+    let server = CallbackServer {
+        listener,
+        callbacks: callbacks.clone(),
+    };
+    Ok((callbacks, server))
+}
+
+impl CallbackServer {
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept callbacks until the process exits.
+    pub async fn run(self) -> Result<()> {
+        let listener = TcpListener::from_std(self.listener)?;
+        info!(addr = %listener.local_addr()?, "ARK callback receiver listening");
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("ARK callback accept error: {e}");
+                    continue;
+                }
+            };
+            let callbacks = self.callbacks.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, &callbacks).await {
+                    debug!(%peer, "ARK callback connection ended: {e:#}");
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, callbacks: &ArkCallbacks) -> Result<()> {
+    let request = match read_request(&mut stream).await {
+        Ok(r) => r,
+        Err(e) => {
+            write_response(&mut stream, 400, "Bad Request").await?;
+            return Err(e);
+        }
+    };
+    if request.method != "POST" {
+        write_response(&mut stream, 405, "Method Not Allowed").await?;
+        return Ok(());
+    }
+    match serde_json::from_slice::<VideoTaskStatus>(&request.body) {
+        Ok(status) => {
+            info!(
+                path = %request.path,
+                ark_task_id = status.id.as_deref().unwrap_or("?"),
+                status = status.status.as_deref().unwrap_or("?"),
+                "ARK callback received"
+            );
+            callbacks.dispatch(status);
+            write_response(&mut stream, 200, "OK").await
+        }
+        Err(e) => {
+            write_response(&mut stream, 400, "Bad Request").await?;
+            bail!("invalid callback body: {e}")
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Minimal HTTP/1.1 framing
+// ---------------------------------------------------------------------------
+
+pub(crate) struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+/// Read one request: request line, headers, and a `Content-Length` body.
+pub(crate) async fn read_request(stream: &mut TcpStream) -> Result<HttpRequest> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            bail!("request headers too large");
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("connection closed before end of headers");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = std::str::from_utf8(&buf[..header_end]).context("non-UTF-8 request head")?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or("/").to_string();
+
+    let mut content_length = 0usize;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().context("invalid Content-Length")?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        bail!("request body too large ({content_length} bytes)");
+    }
+
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("connection closed before end of body");
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(HttpRequest { method, path, body })
+}
+
+pub(crate) async fn write_response(stream: &mut TcpStream, code: u16, reason: &str) -> Result<()> {
+    let resp = format!("HTTP/1.1 {code} {reason}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    stream.write_all(resp.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
@@ -1,10 +1,12 @@
+pub mod callback;
 #[allow(dead_code)]
 pub mod types;
 
 use anyhow::{bail, Context, Result};
 use reqwest::Client;
 use types::{
-    ImageGenRequest, ImageGenResponse, VideoCreateResponse, VideoGenRequest, VideoTaskStatus,
+    ImageGenRequest, ImageGenResponse, VideoCreateResponse, VideoGenRequest, VideoTaskList,
+    VideoTaskStatus,
 };
 
 pub struct ArkClient {
@@ -96,4 +98,30 @@ impl ArkClient {
             .await
             .context("failed to parse video task status response")
     }
+
+    /// GET /contents/generations/tasks?filter.task_ids=… — batch status query.
+    pub async fn list_video_tasks(&self, task_ids: &[String]) -> Result<VideoTaskList> {
+        let url = format!("{}/contents/generations/tasks", self.base_url);
+        let page_size = task_ids.len().to_string();
+        let mut query: Vec<(&str, &str)> = vec![("page_num", "1"), ("page_size", &page_size)];
+        query.extend(task_ids.iter().map(|id| ("filter.task_ids", id.as_str())));
+        let resp = self
+            .http
+            .get(&url)
+            .bearer_auth(&self.api_key)
+            .query(&query)
+            .send()
+            .await
+            .context("video task list request failed")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("ARK video list API error {status}: {body}");
+        }
+
+        resp.json::<VideoTaskList>()
+            .await
+            .context("failed to parse video task list response")
+    }
 }
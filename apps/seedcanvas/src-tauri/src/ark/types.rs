@@ -11,6 +11,9 @@ pub struct ImageGenRequest {
     pub prompt: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub size: Option<String>,
+    /// Reference images as URLs or `data:` URLs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub image: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub n: Option<u32>,
     pub response_format: String, // always "b64_json"
@@ -22,6 +25,8 @@ pub struct ImageGenRequest {
 #[derive(Debug, Deserialize)]
 pub struct ImageGenResponse {
     pub data: Vec<ImageGenItem>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,14 +55,31 @@ pub struct VideoGenRequest {
     /// Always false — we never want watermarks on generated videos.
     #[serde(default)]
     pub watermark: bool,
+    /// ARK POSTs the task status body here on every status change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// Ask ARK to also return the final frame as `content.last_frame_url`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_last_frame: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct VideoContentItem {
     #[serde(rename = "type")]
-    pub content_type: String,
+    pub content_type: String, // "text" | "image_url"
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<VideoImageUrl>,
+    /// "first_frame" | "last_frame" | "reference_image"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VideoImageUrl {
+    /// Image URL or `data:image/<format>;base64,<content>`.
+    pub url: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -71,6 +93,35 @@ pub struct VideoTaskStatus {
     pub status: Option<String>, // queued | running | succeeded | failed | expired | cancelled
     pub content: Option<VideoTaskContent>,
     pub error: Option<VideoTaskError>,
+    /// Seed the model actually used.
+    pub seed: Option<i64>,
+    /// Present once the task has succeeded.
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// Billing usage ARK reports for a finished generation.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Usage {
+    pub generated_images: Option<i64>,
+    pub output_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub total_tokens: Option<i64>,
+}
+
+impl Usage {
+    /// Tokens billed for the generation, whichever field ARK filled in.
+    pub fn billed_tokens(&self) -> Option<i64> {
+        self.total_tokens.or(self.completion_tokens).or(self.output_tokens)
+    }
+}
+
+/// GET {baseURL}/contents/generations/tasks?filter.task_ids=… → page of statuses
+#[derive(Debug, Deserialize)]
+pub struct VideoTaskList {
+    #[serde(default)]
+    pub items: Vec<VideoTaskStatus>,
+    pub total: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
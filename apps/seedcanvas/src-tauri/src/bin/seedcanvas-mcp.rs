@@ -6,6 +6,7 @@
 
 use anyhow::{Context, Result};
 use rmcp::{transport::stdio, ServiceExt};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::mpsc;
@@ -13,8 +14,14 @@ use tracing::info;
 
 // Import from the library crate
 use seedcanvas_lib::ark::ArkClient;
-use seedcanvas_lib::db::Db;
+use seedcanvas_lib::datadir;
+use seedcanvas_lib::db::{Db, SharedDb, TaskStatus, TaskType};
+use seedcanvas_lib::library::AssetLibrary;
 use seedcanvas_lib::mcp::{CanvasIpcRequest, SeedCanvasMcp};
+use seedcanvas_lib::tasks::events::{for_each_event, TaskEvent, TaskEventPayload};
+use seedcanvas_lib::pricing::PriceTable;
+use seedcanvas_lib::storage::StorageOptions;
+use seedcanvas_lib::tasks::poller::PollPolicy;
 use seedcanvas_lib::tasks::{TaskQueue, UserDefaults};
 
 // ---------------------------------------------------------------------------
@@ -33,12 +40,35 @@ struct Settings {
     default_image_model: Option<String>,
     #[serde(default)]
     default_video_model: Option<String>,
+    /// Public URL ARK should POST video callbacks to — a tunnel or reverse proxy
+    /// forwarding to `video_callback_listen_addr`. Unset means polling only.
+    #[serde(default)]
+    video_callback_url: Option<String>,
+    #[serde(default = "default_callback_listen_addr")]
+    video_callback_listen_addr: String,
+    /// Per-model video polling overrides, keyed by model ID.
+    #[serde(default)]
+    video_poll_policies: HashMap<String, PollPolicy>,
+    #[serde(default)]
+    asset_storage: StorageOptions,
+    #[serde(default)]
+    pricing: PriceTable,
+    #[serde(default = "default_trash_retention_days")]
+    trash_retention_days: u32,
 }
 
 fn default_base_url() -> String {
     "https://ark.cn-beijing.volces.com/api/v3".to_string()
 }
 
+fn default_trash_retention_days() -> u32 {
+    seedcanvas_lib::trash::DEFAULT_RETENTION_DAYS
+}
+
+fn default_callback_listen_addr() -> String {
+    seedcanvas_lib::ark::callback::DEFAULT_LISTEN_ADDR.to_string()
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -46,6 +76,12 @@ impl Default for Settings {
             base_url: default_base_url(),
             default_image_model: None,
             default_video_model: None,
+            video_callback_url: None,
+            video_callback_listen_addr: default_callback_listen_addr(),
+            video_poll_policies: HashMap::new(),
+            asset_storage: StorageOptions::default(),
+            pricing: PriceTable::default(),
+            trash_retention_days: default_trash_retention_days(),
         }
     }
 }
@@ -144,12 +180,14 @@ async fn main() -> Result<()> {
         .with_writer(std::io::stderr)
         .init();
 
-    // Resolve app data directory (same as Tauri: com.seedkit.canvas)
-    let data_dir = resolve_data_dir()?;
-    std::fs::create_dir_all(&data_dir)?;
+    // Resolve app data directory (same as Tauri: com.seedkit.canvas) and
+    // the library, which settings.json may have moved elsewhere
+    let app_dir = datadir::app_dir()?;
+    std::fs::create_dir_all(&app_dir)?;
+    let data_dir = datadir::resolve(&app_dir)?;
 
     // Load settings
-    let settings_path = data_dir.join("settings.json");
+    let settings_path = app_dir.join("settings.json");
     let settings: Settings = match std::fs::read_to_string(&settings_path) {
         Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
         Err(_) => Settings::default(),
@@ -158,14 +196,15 @@ async fn main() -> Result<()> {
     info!(base_url = %settings.base_url, "loaded settings");
 
     // Open database
-    let db_path = data_dir.join("seedcanvas.db");
+    let db_path = data_dir.join(datadir::DB_FILE);
     let db = Db::open(&db_path).context("failed to open database")?;
+    let shared_db: SharedDb = Arc::new(std::sync::Mutex::new(db));
 
     // Create ARK client
     let ark = ArkClient::new(settings.base_url, settings.api_key);
 
     // Projects directory
-    let projects_dir = data_dir.join("projects");
+    let projects_dir = data_dir.join(datadir::PROJECTS_DIR);
     std::fs::create_dir_all(&projects_dir)?;
 
     // Build user defaults from settings
@@ -174,75 +213,57 @@ async fn main() -> Result<()> {
         default_video_model: settings.default_video_model,
     };
 
-    // Create headless task queue (no AppHandle — events won't emit to frontend)
-    let mut task_queue = TaskQueue::new_headless(db, ark, projects_dir, user_defaults);
+    // Create task queue (no AppHandle — lifecycle events go to bus subscribers)
+    let mut library = AssetLibrary::new(Arc::clone(&shared_db), projects_dir.clone());
+    library.set_storage_options(settings.asset_storage.clone());
+    library.set_trash_retention_days(settings.trash_retention_days);
+    let mut task_queue = TaskQueue::new_with_shared(shared_db, ark, projects_dir, user_defaults);
+    task_queue.set_poll_policies(settings.video_poll_policies);
+    task_queue.set_storage_options(settings.asset_storage);
+    task_queue.set_price_table(settings.pricing);
+
+    // Optional ARK callback receiver. If the desktop app already holds the
+    // listen address, this process falls back to polling.
+    if let Some(public_url) = settings.video_callback_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        match seedcanvas_lib::ark::callback::bind(&settings.video_callback_listen_addr, public_url) {
+            Ok((callbacks, server)) => {
+                task_queue.set_callbacks(callbacks);
+                tokio::spawn(async move {
+                    if let Err(e) = server.run().await {
+                        tracing::error!("ARK callback receiver failed: {e:#}");
+                    }
+                });
+            }
+            Err(e) => tracing::warn!("ARK callbacks disabled, polling instead: {e:#}"),
+        }
+    }
 
     // Try connecting to the running SeedCanvas app via Unix socket
-    let sock_path = data_dir.join("mcp.sock");
+    let sock_path = app_dir.join("mcp.sock");
     let canvas_tx = connect_canvas_socket(&sock_path).await;
 
-    // When connected to the app, register a task-completion callback that pushes
-    // results to canvas nodes via the existing socket bridge (canvas_batch).
+    // When connected to the app, push completed results to canvas nodes via the
+    // existing socket bridge (canvas_batch).
     if let Some(ref tx) = canvas_tx {
         let tx = tx.clone();
-        task_queue.set_on_complete(std::sync::Arc::new(move |task: seedcanvas_lib::db::TaskRow| {
-            if task.status != "done" {
-                return;
-            }
-            // Extract node_id and output from the completed task
-            let node_id = serde_json::from_str::<serde_json::Value>(&task.input)
-                .ok()
-                .and_then(|v| v["node_id"].as_str().map(String::from));
-            let output = task.output.as_deref()
-                .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok());
-
-            if let (Some(node_id), Some(output)) = (node_id, output) {
-                let asset_path = output["assetPath"].as_str().unwrap_or_default().to_string();
-                if asset_path.is_empty() {
-                    return;
-                }
-
-                // Build an update_node batch op with the asset URL
-                let (url_key, width, height) = if task.task_type == "image" {
-                    ("newImageUrl",
-                     output["width"].as_u64().unwrap_or(2048) as u32,
-                     output["height"].as_u64().unwrap_or(2048) as u32)
-                } else {
-                    ("newVideoUrl",
-                     output["width"].as_u64().unwrap_or(1280) as u32,
-                     output["height"].as_u64().unwrap_or(720) as u32)
-                };
-
-                let batch_op = serde_json::json!([{
-                    "op": "update_node",
-                    "nodeId": node_id,
-                    url_key: asset_path,
-                    "width": width,
-                    "height": height,
-                }]);
-
-                let tx = tx.clone();
-                tokio::spawn(async move {
-                    let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
-                    if tx.send(seedcanvas_lib::mcp::CanvasIpcRequest::Batch {
-                        operations: batch_op,
-                        reply: reply_tx,
-                    }).await.is_ok() {
-                        match reply_rx.await {
-                            Ok(Ok(_)) => info!("pushed task result to node {}", node_id),
-                            Ok(Err(e)) => tracing::warn!("failed to push result to node: {e}"),
-                            Err(_) => tracing::warn!("bridge response channel closed"),
-                        }
-                    }
-                });
+        tokio::spawn(for_each_event(task_queue.subscribe(), move |event| {
+            if let TaskEvent::Completed(payload) = event {
+                push_result_to_node(&tx, payload);
             }
         }));
     }
 
+    // Claim unfinished tasks no live process holds a lease on, and keep
+    // sweeping for ones orphaned by a crashed desktop app.
+    if let Err(e) = task_queue.resume_running_tasks() {
+        tracing::error!("failed to resume running tasks: {e:#}");
+    }
     let task_queue = Arc::new(task_queue);
+    tokio::spawn(Arc::clone(&task_queue).sweep_stale_leases());
+    tokio::spawn(seedcanvas_lib::trash::sweep(library.clone()));
 
     // Create MCP server and serve over stdio
-    let server = SeedCanvasMcp::new(task_queue, canvas_tx);
+    let server = SeedCanvasMcp::new(task_queue, library, canvas_tx);
 
     info!("SeedCanvas MCP server starting on stdio");
 
@@ -256,24 +277,50 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-/// Resolve the app data directory cross-platform.
-/// Uses the same identifier as the Tauri app: com.seedkit.canvas
-fn resolve_data_dir() -> Result<PathBuf> {
-    #[cfg(target_os = "macos")]
-    {
-        let home = dirs::home_dir().context("could not determine home directory")?;
-        Ok(home.join("Library/Application Support/com.seedkit.canvas"))
+/// Push a finished task's asset into the canvas node it was generated for.
+fn push_result_to_node(tx: &mpsc::Sender<CanvasIpcRequest>, task: TaskEventPayload) {
+    if task.status != TaskStatus::Done {
+        return;
     }
-
-    #[cfg(target_os = "linux")]
-    {
-        let data = dirs::data_dir().context("could not determine data directory")?;
-        Ok(data.join("com.seedkit.canvas"))
+    let (Some(node_id), Some(output)) = (task.node_id, task.output) else {
+        return;
+    };
+    let asset_path = output["assetPath"].as_str().unwrap_or_default().to_string();
+    if asset_path.is_empty() {
+        return;
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        let data = dirs::data_dir().context("could not determine data directory")?;
-        Ok(data.join("com.seedkit.canvas"))
-    }
+    // Build an update_node batch op with the asset URL
+    let (url_key, width, height) = if task.task_type == TaskType::Image {
+        ("newImageUrl",
+         output["width"].as_u64().unwrap_or(2048) as u32,
+         output["height"].as_u64().unwrap_or(2048) as u32)
+    } else {
+        ("newVideoUrl",
+         output["width"].as_u64().unwrap_or(1280) as u32,
+         output["height"].as_u64().unwrap_or(720) as u32)
+    };
+
+    let batch_op = serde_json::json!([{
+        "op": "update_node",
+        "nodeId": node_id,
+        url_key: asset_path,
+        "width": width,
+        "height": height,
+    }]);
+
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        if tx.send(CanvasIpcRequest::Batch {
+            operations: batch_op,
+            reply: reply_tx,
+        }).await.is_ok() {
+            match reply_rx.await {
+                Ok(Ok(_)) => info!("pushed task result to node {}", node_id),
+                Ok(Err(e)) => tracing::warn!("failed to push result to node: {e}"),
+                Err(_) => tracing::warn!("bridge response channel closed"),
+            }
+        }
+    });
 }
@@ -0,0 +1,733 @@
+//! Portable project bundles.
+//!
+//! [`export_project`] writes one zip file holding everything needed to rebuild
+//! a project on another machine:
+//!
+//! ```text
+//! seedcanvas-bundle.json   — format version and the task, asset and link rows
+//! project/manifest.json
+//! project/canvas.json
+//! project/assets/…
+//! project/thumbnails/…
+//! ```
+//!
+//! Asset rows hold absolute paths; in the bundle they are relative to the
+//! project directory. [`import_project`] extracts into a staging directory
+//! next to the other projects and rewrites everything that points into the
+//! old location — row paths, task outputs, media URLs in the canvas — before
+//! it moves the project into place and inserts the rows. Task and asset IDs
+//! are kept unless the project is imported under a different ID or the IDs
+//! are already taken. In that case new IDs are minted and every reference is
+//! updated to match.
+//!
+//! Only finished tasks are exported; collections are per-library and stay
+//! behind. Everything here is blocking.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::db::{AssetLink, AssetRow, TaskRow};
+use crate::library::AssetLibrary;
+
+/// Name of the index entry at the root of a bundle.
+pub const BUNDLE_INDEX: &str = "seedcanvas-bundle.json";
+/// Directory inside the zip holding the project files.
+const PROJECT_ENTRY: &str = "project";
+const BUNDLE_FORMAT: u32 = 1;
+/// Cap on the total size of the extracted files, against zip bombs.
+const MAX_UNPACKED_BYTES: u64 = 64 << 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleIndex {
+    format: u32,
+    project_id: String,
+    /// Absolute project directory at export time, to recognise it in canvas URLs.
+    project_dir: String,
+    exported_at: String,
+    tasks: Vec<TaskRow>,
+    /// Paths relative to the project directory, `/`-separated.
+    assets: Vec<AssetRow>,
+    #[serde(default)]
+    links: Vec<AssetLink>,
+}
+
+// ---------------------------------------------------------------------------
+// Export
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub path: String,
+    pub files: usize,
+    pub tasks: usize,
+    pub assets: usize,
+    /// Size of the written bundle.
+    pub bytes: u64,
+    pub warnings: Vec<String>,
+}
+
+/// Write `project_id` to a bundle at `dest`, replacing any file there.
+pub fn export_project(library: &AssetLibrary, project_id: &str, dest: &Path) -> Result<ExportReport> {
+    let project_dir = library.project_dir(project_id);
+    if !project_dir.join("manifest.json").is_file() {
+        bail!("project \"{project_id}\" does not exist (no manifest.json found)");
+    }
+    let (tasks, assets, links) = {
+        let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        (
+            db.get_tasks_by_project(project_id)?,
+            db.list_all_assets(Some(project_id))?,
+            db.list_asset_links(project_id)?,
+        )
+    };
+
+    let mut warnings = Vec::new();
+    let (tasks, unfinished): (Vec<TaskRow>, Vec<TaskRow>) =
+        tasks.into_iter().partition(|t| t.status.is_terminal());
+    for task in unfinished {
+        warnings.push(format!("task {} is still {}; not exported", task.id, task.status));
+    }
+
+    let mut exported = Vec::with_capacity(assets.len());
+    for mut asset in assets {
+        let Some(rel) = relative_to(&project_dir, &asset.file_path) else {
+            warnings.push(format!(
+                "asset {} is outside the project directory ({}); not exported",
+                asset.id, asset.file_path
+            ));
+            continue;
+        };
+        if !project_dir.join(&rel).is_file() {
+            warnings.push(format!("file of asset {} is missing: {}", asset.id, asset.file_path));
+        }
+        asset.file_path = rel;
+        asset.thumbnail_path = asset.thumbnail_path.and_then(|p| relative_to(&project_dir, &p));
+        asset.poster_path = asset.poster_path.and_then(|p| relative_to(&project_dir, &p));
+        exported.push(asset);
+    }
+    let kept: HashSet<&str> = exported.iter().map(|a| a.id.as_str()).collect();
+    let links: Vec<AssetLink> = links
+        .into_iter()
+        .filter(|l| kept.contains(l.source_id.as_str()) && kept.contains(l.asset_id.as_str()))
+        .collect();
+
+    let files = project_files(&project_dir)?;
+    let index = BundleIndex {
+        format: BUNDLE_FORMAT,
+        project_id: project_id.to_string(),
+        project_dir: project_dir.to_string_lossy().to_string(),
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        tasks,
+        assets: exported,
+        links,
+    };
+
+    let file_name = dest
+        .file_name()
+        .with_context(|| format!("{} is not a file path", dest.display()))?;
+    let tmp = dest.with_file_name(format!(".{}.partial", file_name.to_string_lossy()));
+    if let Err(e) = write_bundle(&tmp, &index, &files) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, dest).with_context(|| format!("failed to write {}", dest.display()))?;
+
+    Ok(ExportReport {
+        path: dest.to_string_lossy().to_string(),
+        files: files.len(),
+        tasks: index.tasks.len(),
+        assets: index.assets.len(),
+        bytes: std::fs::metadata(dest)?.len(),
+        warnings,
+    })
+}
+
+fn write_bundle(path: &Path, index: &BundleIndex, files: &[(PathBuf, String)]) -> Result<()> {
+    let out = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut zip = ZipWriter::new(BufWriter::new(out));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file(BUNDLE_INDEX, deflated)?;
+    serde_json::to_writer_pretty(&mut zip, index)?;
+
+    for (abs, rel) in files {
+        let mut src = File::open(abs).with_context(|| format!("failed to open {}", abs.display()))?;
+        let size = src.metadata()?.len();
+        // Media is already compressed; deflating it again only costs time
+        let method = if is_compressed_media(rel) { CompressionMethod::Stored } else { CompressionMethod::Deflated };
+        let options = SimpleFileOptions::default()
+            .compression_method(method)
+            .large_file(size >= u64::from(u32::MAX));
+        zip.start_file(format!("{PROJECT_ENTRY}/{rel}"), options)?;
+        std::io::copy(&mut src, &mut zip).with_context(|| format!("failed to read {}", abs.display()))?;
+    }
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// Every regular file under the project directory as `(absolute, relative)`,
+/// sorted. Hidden files — including half-written `.partial` files — are skipped.
+fn project_files(project_dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut out = Vec::new();
+    let mut dirs = vec![project_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let kind = entry.file_type()?;
+            if kind.is_dir() {
+                dirs.push(path);
+            } else if kind.is_file() {
+                if let Some(rel) = relative_to(project_dir, &path.to_string_lossy()) {
+                    out.push((path, rel));
+                }
+            }
+        }
+    }
+    out.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(out)
+}
+
+fn is_compressed_media(rel: &str) -> bool {
+    let ext = rel.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+    matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "webp" | "mp4")
+}
+
+/// `path` relative to `dir` with `/` separators, or `None` if it is not inside.
+fn relative_to(dir: &Path, path: &str) -> Option<String> {
+    let rel = Path::new(path).strip_prefix(dir).ok()?;
+    let parts: Vec<String> = rel
+        .components()
+        .map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+// ---------------------------------------------------------------------------
+// Import
+// ---------------------------------------------------------------------------
+
+/// What to do when the target project ID is already in use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Import under a new ID and leave the existing project alone.
+    #[default]
+    Rename,
+    /// Delete the existing project — files and rows — and take its place.
+    Replace,
+    /// Refuse to import.
+    Fail,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportOptions {
+    /// ID to import the project as; defaults to the one it was exported with.
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub project_id: String,
+    /// The ID the project was exported with.
+    pub original_project_id: String,
+    /// Project name from the manifest.
+    pub name: Option<String>,
+    /// The requested ID was taken, so a new one was minted.
+    pub renamed: bool,
+    /// An existing project was replaced.
+    pub replaced: bool,
+    pub files: usize,
+    pub tasks: usize,
+    pub assets: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Restore a project from a bundle written by [`export_project`].
+pub fn import_project(library: &AssetLibrary, bundle: &Path, options: &ImportOptions) -> Result<ImportReport> {
+    let file = File::open(bundle).with_context(|| format!("failed to open {}", bundle.display()))?;
+    let mut zip = ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("{} is not a zip file", bundle.display()))?;
+    let index: BundleIndex = {
+        let entry = zip
+            .by_name(BUNDLE_INDEX)
+            .with_context(|| format!("{} is not a SeedCanvas project bundle", bundle.display()))?;
+        serde_json::from_reader(entry).context("invalid bundle index")?
+    };
+    if index.format > BUNDLE_FORMAT {
+        bail!(
+            "bundle format {} is newer than this version of SeedCanvas supports ({BUNDLE_FORMAT})",
+            index.format
+        );
+    }
+
+    let wanted = options.project_id.clone().unwrap_or_else(|| index.project_id.clone());
+    validate_project_id(&wanted)?;
+    let (project_id, renamed, replace) = match (project_exists(library, &wanted)?, options.on_conflict) {
+        (false, _) => (wanted, false, false),
+        (true, ConflictPolicy::Fail) => bail!("project \"{wanted}\" already exists"),
+        (true, ConflictPolicy::Rename) => (uuid::Uuid::new_v4().to_string(), true, false),
+        (true, ConflictPolicy::Replace) => (wanted, false, true),
+    };
+
+    let projects_dir = library.projects_dir();
+    std::fs::create_dir_all(projects_dir)?;
+    let staging = projects_dir.join(format!(".import-{}", uuid::Uuid::new_v4()));
+    let result = import_staged(library, &mut zip, index, &staging, &project_id, replace);
+    if staging.exists() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+    let (files, tasks, assets, name, original_project_id, warnings) = result?;
+
+    Ok(ImportReport {
+        project_id,
+        original_project_id,
+        name,
+        renamed,
+        replaced: replace,
+        files,
+        tasks,
+        assets,
+        warnings,
+    })
+}
+
+type Staged = (usize, usize, usize, Option<String>, String, Vec<String>);
+
+fn import_staged<R: Read + Seek>(
+    library: &AssetLibrary,
+    zip: &mut ZipArchive<R>,
+    index: BundleIndex,
+    staging: &Path,
+    project_id: &str,
+    replace: bool,
+) -> Result<Staged> {
+    let files = extract(zip, staging)?;
+    let manifest_path = staging.join("manifest.json");
+    if !manifest_path.is_file() {
+        bail!("bundle has no {PROJECT_ENTRY}/manifest.json");
+    }
+    let project_dir = library.project_dir(project_id);
+    let mut warnings = Vec::new();
+
+    // Keep IDs where possible; mint new ones when the project changes ID or
+    // another project already uses them
+    let mut rewrite = Rewrite::new(&index.project_dir, &project_dir);
+    {
+        let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        let moved = project_id != index.project_id;
+        let keep = |owner: Option<String>| match owner {
+            None => !moved,
+            Some(owner) => !moved && replace && owner == project_id,
+        };
+        for task in &index.tasks {
+            if !keep(db.get_task(&task.id)?.map(|t| t.project_id)) {
+                rewrite.ids.insert(task.id.clone(), uuid::Uuid::new_v4().to_string());
+            }
+        }
+        // Trashed rows count: their IDs stay taken until they are purged
+        for asset in &index.assets {
+            if !keep(db.asset_project_id(&asset.id)?) {
+                rewrite.ids.insert(asset.id.clone(), uuid::Uuid::new_v4().to_string());
+            }
+        }
+    }
+
+    let mut manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest_path)?).context("invalid manifest.json")?;
+    manifest["id"] = project_id.into();
+    let name = manifest["name"].as_str().map(String::from);
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+
+    let canvas_path = staging.join("canvas.json");
+    if canvas_path.is_file() {
+        let mut canvas: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&canvas_path)?).context("invalid canvas.json")?;
+        rewrite.json(&mut canvas);
+        std::fs::write(&canvas_path, serde_json::to_string_pretty(&canvas)?)?;
+    }
+
+    let mut tasks = Vec::with_capacity(index.tasks.len());
+    for mut task in index.tasks {
+        if !task.status.is_terminal() {
+            warnings.push(format!("task {} is still {}; skipped", task.id, task.status));
+            continue;
+        }
+        task.id = rewrite.id(&task.id);
+        task.project_id = project_id.to_string();
+        if let Ok(mut input) = serde_json::from_str::<serde_json::Value>(&task.input) {
+            rewrite.json(&mut input);
+            input["project_id"] = project_id.into();
+            task.input = input.to_string();
+        }
+        task.output = task.output.map(|o| rewrite.text(&o));
+        tasks.push(task);
+    }
+
+    let mut assets = Vec::with_capacity(index.assets.len());
+    for mut asset in index.assets {
+        let Some(file_path) = join_relative(&project_dir, &asset.file_path) else {
+            warnings.push(format!("asset {} has an invalid path {:?}; skipped", asset.id, asset.file_path));
+            continue;
+        };
+        if !staging.join(&asset.file_path).is_file() {
+            warnings.push(format!("file of asset {} is missing from the bundle: {}", asset.id, asset.file_path));
+        }
+        asset.id = rewrite.id(&asset.id);
+        asset.project_id = project_id.to_string();
+        asset.task_id = asset.task_id.map(|id| rewrite.id(&id));
+        asset.file_path = file_path.to_string_lossy().to_string();
+        asset.thumbnail_path = asset
+            .thumbnail_path
+            .and_then(|p| join_relative(&project_dir, &p))
+            .map(|p| p.to_string_lossy().to_string());
+        asset.poster_path = asset
+            .poster_path
+            .and_then(|p| join_relative(&project_dir, &p))
+            .map(|p| p.to_string_lossy().to_string());
+        assets.push(asset);
+    }
+    let links: Vec<AssetLink> = index
+        .links
+        .into_iter()
+        .map(|mut link| {
+            link.source_id = rewrite.id(&link.source_id);
+            link.asset_id = rewrite.id(&link.asset_id);
+            link.task_id = link.task_id.map(|id| rewrite.id(&id));
+            link
+        })
+        .collect();
+
+    // Everything is prepared; swap the project in
+    let backup = if replace && project_dir.exists() {
+        let backup = library.projects_dir().join(format!(".replaced-{}", uuid::Uuid::new_v4()));
+        std::fs::rename(&project_dir, &backup)
+            .with_context(|| format!("failed to move {} aside", project_dir.display()))?;
+        Some(backup)
+    } else {
+        None
+    };
+    if let Err(e) = std::fs::rename(staging, &project_dir) {
+        if let Some(backup) = &backup {
+            let _ = std::fs::rename(backup, &project_dir);
+        }
+        return Err(e).with_context(|| format!("failed to move project into {}", project_dir.display()));
+    }
+
+    // One transaction, so a failed import leaves a replaced project's rows
+    // exactly as they were
+    let inserted = (|| -> Result<usize> {
+        let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        let tx = db.begin()?;
+        if replace {
+            db.delete_all_project_data(project_id)?;
+        }
+        for task in &tasks {
+            db.insert_task(task)?;
+        }
+        let mut inserted = 0;
+        for asset in &assets {
+            if db.insert_asset(asset)? {
+                inserted += 1;
+            } else {
+                warnings.push(format!("asset {} already exists; skipped", asset.id));
+            }
+        }
+        for link in &links {
+            db.add_asset_link(&link.source_id, &link.asset_id, link.relation, link.task_id.as_deref())?;
+        }
+        tx.commit()?;
+        Ok(inserted)
+    })();
+    let inserted = match inserted {
+        Ok(inserted) => inserted,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&project_dir);
+            if let Some(backup) = &backup {
+                let _ = std::fs::rename(backup, &project_dir);
+            }
+            return Err(e.context("failed to record imported rows"));
+        }
+    };
+    if let Some(backup) = backup {
+        if let Err(e) = std::fs::remove_dir_all(&backup) {
+            warnings.push(format!("could not remove the replaced project at {}: {e}", backup.display()));
+        }
+    }
+
+    Ok((files, tasks.len(), inserted, name, index.project_id, warnings))
+}
+
+/// Unpack the `project/` entries of the bundle into `dest`, refusing to
+/// write more than [`MAX_UNPACKED_BYTES`] in total.
+fn extract<R: Read + Seek>(zip: &mut ZipArchive<R>, dest: &Path) -> Result<usize> {
+    let declared: u64 = (0..zip.len())
+        .filter_map(|i| zip.by_index_raw(i).ok().map(|e| e.size()))
+        .fold(0, u64::saturating_add);
+    if declared > MAX_UNPACKED_BYTES {
+        bail!("bundle unpacks to {declared} bytes, more than the {MAX_UNPACKED_BYTES} allowed");
+    }
+    let mut budget = MAX_UNPACKED_BYTES;
+    let mut files = 0;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let Some(name) = entry.enclosed_name() else {
+            bail!("bundle entry {:?} has an unsafe path", entry.name());
+        };
+        let Ok(rel) = name.strip_prefix(PROJECT_ENTRY) else { continue };
+        if entry.is_dir() || rel.as_os_str().is_empty() {
+            continue;
+        }
+        let path = dest.join(rel);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut out = File::create(&path).with_context(|| format!("failed to create {}", path.display()))?;
+        // Declared sizes can lie; count what is actually written
+        let written = std::io::copy(&mut (&mut entry).take(budget + 1), &mut out)
+            .with_context(|| format!("failed to extract {}", entry.name()))?;
+        if written > budget {
+            bail!("bundle unpacks to more than the {MAX_UNPACKED_BYTES} bytes allowed");
+        }
+        budget -= written;
+        files += 1;
+    }
+    Ok(files)
+}
+
+pub(crate) fn validate_project_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        bail!("invalid project ID \"{id}\"");
+    }
+    Ok(())
+}
+
+fn project_exists(library: &AssetLibrary, project_id: &str) -> Result<bool> {
+    if library.project_dir(project_id).exists() {
+        return Ok(true);
+    }
+    let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+    Ok(db.has_tasks_for_project(project_id)?
+        || !db.list_all_assets(Some(project_id))?.is_empty()
+        || db.get_trashed_project(project_id)?.is_some())
+}
+
+/// `dir` joined with a bundle-relative `/`-separated path. `None` for
+/// anything that could step outside `dir`.
+fn join_relative(dir: &Path, rel: &str) -> Option<PathBuf> {
+    let mut path = dir.to_path_buf();
+    for part in rel.split('/') {
+        if part.is_empty() || part == "." || part == ".." || part.contains('\\') || Path::new(part).has_root() {
+            return None;
+        }
+        path.push(part);
+    }
+    Some(path)
+}
+
+/// Maps the exported project's directory and IDs onto the imported ones.
+pub(crate) struct Rewrite {
+    /// `(old, new)` directory prefixes, raw and URL-encoded.
+    prefixes: Vec<(String, String)>,
+    ids: HashMap<String, String>,
+}
+
+impl Rewrite {
+    pub(crate) fn new(old_dir: &str, new_dir: &Path) -> Self {
+        let old_dir = old_dir.trim_end_matches(['/', '\\']);
+        let new_dir = format!("{}{}", new_dir.to_string_lossy(), std::path::MAIN_SEPARATOR);
+        let mut prefixes = Vec::new();
+        // Either separator may follow the directory, whichever OS exported it
+        for sep in ['/', '\\'] {
+            let old = format!("{old_dir}{sep}");
+            prefixes.push((encode_uri_component(&old), encode_uri_component(&new_dir)));
+            prefixes.push((old, new_dir.clone()));
+        }
+        Self { prefixes, ids: HashMap::new() }
+    }
+
+    fn id(&self, id: &str) -> String {
+        self.ids.get(id).cloned().unwrap_or_else(|| id.to_string())
+    }
+
+    fn text(&self, s: &str) -> String {
+        self.prefixes
+            .iter()
+            .fold(s.to_string(), |acc, (old, new)| acc.replace(old.as_str(), new))
+    }
+
+    /// Rewrite every string in `value`: IDs when they match exactly, paths
+    /// and asset URLs anywhere inside.
+    pub(crate) fn json(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => {
+                *s = match self.ids.get(s.as_str()) {
+                    Some(id) => id.clone(),
+                    None => self.text(s),
+                }
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|v| self.json(v)),
+            serde_json::Value::Object(map) => map.values_mut().for_each(|v| self.json(v)),
+            _ => {}
+        }
+    }
+}
+
+/// JavaScript's `encodeURIComponent`, which `convertFileSrc` applies to paths.
+fn encode_uri_component(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.!~*'()".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{LinkRelation, TaskStatus, TaskType};
+    use crate::test_support::{add_asset, asset_row, setup};
+
+    fn asset(id: &str, task_id: Option<&str>, path: &Path) -> AssetRow {
+        AssetRow {
+            task_id: task_id.map(String::from),
+            prompt: Some("harbour at dawn".into()),
+            favorite: true,
+            rating: Some(5),
+            tags: vec!["keeper".into()],
+            ..asset_row(id, path)
+        }
+    }
+
+    #[test]
+    fn ids_held_by_trashed_rows_are_reminted() {
+        let (root, lib) = setup();
+        std::fs::write(lib.project_dir("p1").join("manifest.json"), r#"{"id":"p1","name":"Harbour"}"#).unwrap();
+        let still = add_asset(&lib, "a1", "still.png", b"still");
+        let bundle = root.join("harbour.zip");
+        export_project(&lib, "p1", &bundle).unwrap();
+
+        // a1 now lives on only as a trashed row in another project
+        {
+            let db = lib.db().lock().unwrap();
+            let elsewhere = lib.project_dir("p2").join("assets/still.png");
+            let moved = AssetRow { project_id: "p2".into(), file_path: elsewhere.to_string_lossy().to_string(), ..still };
+            db.update_asset_location(&moved).unwrap();
+            db.trash_asset_rows(&["a1".to_string()], &chrono::Utc::now().to_rfc3339()).unwrap();
+        }
+        std::fs::remove_dir_all(lib.project_dir("p1")).unwrap();
+
+        let report = import_project(&lib, &bundle, &ImportOptions::default()).unwrap();
+        assert_eq!((report.project_id.as_str(), report.assets), ("p1", 1));
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let assets = lib.db().lock().unwrap().list_all_assets(Some("p1")).unwrap();
+        assert_eq!(assets.len(), 1);
+        assert_ne!(assets[0].id, "a1");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn export_and_import_round_trip_with_new_ids_and_paths() {
+        let (root, lib) = setup();
+        let dir = lib.project_dir("p1");
+        std::fs::write(dir.join("manifest.json"), r#"{"id":"p1","name":"Harbour"}"#).unwrap();
+        let still = dir.join("assets/still.png");
+        let frame = dir.join("assets/frame.png");
+        std::fs::write(&still, b"still").unwrap();
+        std::fs::write(&frame, b"frame").unwrap();
+        let url = format!("asset://localhost/{}", encode_uri_component(&still.to_string_lossy()));
+        let canvas = serde_json::json!({ "nodes": [{ "id": "n1", "data": { "taskId": "t1", "url": url } }] });
+        std::fs::write(dir.join("canvas.json"), canvas.to_string()).unwrap();
+        {
+            let db = lib.db().lock().unwrap();
+            let now = chrono::Utc::now().to_rfc3339();
+            db.insert_task(&TaskRow {
+                id: "t1".into(),
+                project_id: "p1".into(),
+                task_type: TaskType::Image,
+                status: TaskStatus::Done,
+                input: r#"{"project_id":"p1","prompt":"harbour at dawn"}"#.into(),
+                output: Some(serde_json::json!({ "assetPath": still.to_string_lossy() }).to_string()),
+                ark_task_id: None,
+                error: None,
+                created_at: now.clone(),
+                updated_at: now,
+            })
+            .unwrap();
+            db.insert_asset(&asset("a1", Some("t1"), &still)).unwrap();
+            db.insert_asset(&asset("a2", None, &frame)).unwrap();
+            db.add_asset_link("a1", "a2", LinkRelation::VariantOf, None).unwrap();
+        }
+
+        let bundle = root.join("harbour.zip");
+        let exported = export_project(&lib, "p1", &bundle).unwrap();
+        assert_eq!((exported.files, exported.tasks, exported.assets), (4, 1, 2));
+        assert!(exported.warnings.is_empty(), "{:?}", exported.warnings);
+
+        // p1 still exists: the default policy imports a copy under a new ID
+        let copy = import_project(&lib, &bundle, &ImportOptions::default()).unwrap();
+        assert!(copy.renamed);
+        assert_ne!(copy.project_id, "p1");
+        assert_eq!(copy.name.as_deref(), Some("Harbour"));
+        let new_dir = lib.project_dir(&copy.project_id);
+        let db = lib.db().lock().unwrap();
+        let assets = db.list_all_assets(Some(&copy.project_id)).unwrap();
+        assert_eq!(assets.len(), 2);
+        assert!(assets.iter().all(|a| a.id != "a1" && a.id != "a2"));
+        let new_still = assets.iter().find(|a| a.file_name == "still.png").unwrap();
+        assert_eq!(new_still.file_path, new_dir.join("assets").join("still.png").to_string_lossy());
+        assert_eq!((new_still.favorite, new_still.rating, new_still.tags.clone()), (true, Some(5), vec!["keeper".to_string()]));
+        let task_id = new_still.task_id.clone().unwrap();
+        let task = db.get_task(&task_id).unwrap().unwrap();
+        assert!(task.output.unwrap().contains(&*new_dir.to_string_lossy()));
+        assert!(task.input.contains(&copy.project_id));
+        assert_eq!(db.get_asset_lineage(&new_still.id).unwrap().unwrap().descendants.len(), 1);
+        drop(db);
+
+        let canvas: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(new_dir.join("canvas.json")).unwrap()).unwrap();
+        let node = &canvas["nodes"][0]["data"];
+        assert_eq!(node["taskId"], task_id.as_str());
+        assert!(node["url"].as_str().unwrap().contains(&encode_uri_component(&new_dir.to_string_lossy())));
+        assert!(new_dir.join("assets/frame.png").is_file());
+
+        // Replacing keeps the original ID and IDs
+        let options = ImportOptions { on_conflict: ConflictPolicy::Fail, ..Default::default() };
+        assert!(import_project(&lib, &bundle, &options).is_err());
+        let options = ImportOptions { on_conflict: ConflictPolicy::Replace, ..Default::default() };
+        let replaced = import_project(&lib, &bundle, &options).unwrap();
+        assert!(replaced.replaced && !replaced.renamed);
+        let ids: Vec<String> = lib.db().lock().unwrap().list_all_assets(Some("p1")).unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&"a1".to_string()));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
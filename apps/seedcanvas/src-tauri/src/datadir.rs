@@ -0,0 +1,389 @@
+//! Where the library lives.
+//!
+//! ```text
+//! {app_dir}/settings.json    — always here; `dataDir` points the library elsewhere
+//! {app_dir}/projects.json    — the frontend's project index
+//! {app_dir}/mcp.sock
+//! {data_dir}/seedcanvas.db
+//! {data_dir}/projects/
+//! {data_dir}/trash/
+//! ```
+//!
+//! The app dir is the OS app data directory (`com.seedkit.canvas`); the data
+//! dir is the same directory unless settings.json says otherwise. The desktop
+//! app and seedcanvas-mcp both find it with [`resolve`], so they always agree.
+//!
+//! Rows store paths relative to their project's directory (see
+//! [`crate::db`]), so [`relocate`] only has to move the files, fix the media URLs saved in each
+//! canvas and record the new location. The frontend reads projects through
+//! `{app_dir}/projects`, so a relocated library leaves a link there.
+//!
+//! Everything here is blocking.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::bundle::Rewrite;
+use crate::db::{Db, SharedDb};
+
+pub const DB_FILE: &str = "seedcanvas.db";
+pub const PROJECTS_DIR: &str = "projects";
+pub const TRASH_DIR: &str = "trash";
+const SETTINGS_FILE: &str = "settings.json";
+const DATA_DIR_KEY: &str = "dataDir";
+
+/// The OS app data directory, the same one Tauri resolves for the
+/// `com.seedkit.canvas` identifier.
+pub fn app_dir() -> Result<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        let home = dirs::home_dir().context("could not determine home directory")?;
+        Ok(home.join("Library/Application Support/com.seedkit.canvas"))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let data = dirs::data_dir().context("could not determine data directory")?;
+        Ok(data.join("com.seedkit.canvas"))
+    }
+}
+
+/// The data directory configured in `app_dir`'s settings.json, or `app_dir`
+/// itself. A configured directory that is missing — an external drive that
+/// is not connected — is an error rather than a fresh, empty library.
+pub fn resolve(app_dir: &Path) -> Result<PathBuf> {
+    match configured(app_dir) {
+        None => Ok(app_dir.to_path_buf()),
+        Some(dir) if dir.is_dir() => Ok(dir),
+        Some(dir) => bail!("data directory {} is not available; is its drive connected?", dir.display()),
+    }
+}
+
+fn configured(app_dir: &Path) -> Option<PathBuf> {
+    let text = std::fs::read_to_string(app_dir.join(SETTINGS_FILE)).ok()?;
+    let settings: serde_json::Value = serde_json::from_str(&text).ok()?;
+    settings[DATA_DIR_KEY].as_str().filter(|d| !d.trim().is_empty()).map(PathBuf::from)
+}
+
+/// Record `data_dir` in settings.json, keeping every other setting. `None`
+/// goes back to the app dir.
+fn save_data_dir(app_dir: &Path, data_dir: Option<&Path>) -> Result<()> {
+    let path = app_dir.join(SETTINGS_FILE);
+    let mut settings = match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("{} is not valid JSON", path.display()))?,
+        Err(_) => serde_json::Value::Object(Default::default()),
+    };
+    let Some(map) = settings.as_object_mut() else {
+        bail!("{} is not a JSON object", path.display());
+    };
+    match data_dir {
+        Some(dir) => map.insert(DATA_DIR_KEY.to_string(), dir.to_string_lossy().into()),
+        None => map.remove(DATA_DIR_KEY),
+    };
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(&settings)?)?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("failed to write {}", path.display()))
+}
+
+// ---------------------------------------------------------------------------
+// Relocation
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocateReport {
+    pub from: String,
+    pub to: String,
+    /// Top-level entries moved, e.g. `projects`.
+    pub moved: Vec<String>,
+    /// Canvases whose media URLs were rewritten.
+    pub canvases_updated: usize,
+    /// Non-fatal problems, e.g. an old file that could not be removed.
+    pub warnings: Vec<String>,
+}
+
+/// Move the library to `to` and point settings.json at it. `db` is reopened
+/// there; everything else holding paths (the asset library, the task queue,
+/// a running seedcanvas-mcp) has to restart to follow.
+///
+/// Refused while tasks are unfinished or `to` already holds a library.
+/// Fails before anything is recorded if a move or the `{app_dir}/projects`
+/// link fails, putting back what was already moved. The files move without
+/// the DB lock — a copy to another drive can take minutes — which is only
+/// taken to swap the database.
+pub fn relocate(app_dir: &Path, db: &SharedDb, to: &Path) -> Result<RelocateReport> {
+    let lock = || db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"));
+    let from = {
+        let db = lock()?;
+        let from = db.root().to_path_buf();
+        check_target(&from, to)?;
+        ensure_idle(&db)?;
+        from
+    };
+    std::fs::create_dir_all(to).with_context(|| format!("failed to create {}", to.display()))?;
+
+    // The link in the app dir leads to the current location
+    let link = app_dir.join(PROJECTS_DIR);
+    if from != app_dir {
+        remove_link(&link);
+    }
+
+    let mut report = RelocateReport {
+        from: from.to_string_lossy().to_string(),
+        to: to.to_string_lossy().to_string(),
+        moved: Vec::new(),
+        canvases_updated: 0,
+        warnings: Vec::new(),
+    };
+    let mut moved = Vec::new();
+    let result = (|| -> Result<()> {
+        for name in [PROJECTS_DIR, TRASH_DIR] {
+            let (src, dest) = (from.join(name), to.join(name));
+            if src.exists() {
+                move_dir(&src, &dest)?;
+                moved.push((src, dest));
+            }
+        }
+        // The frontend finds projects only through the link, so a library it
+        // cannot reach is not worth moving
+        if to != app_dir {
+            link_dir(&to.join(PROJECTS_DIR), &link)
+                .with_context(|| format!("failed to link {} to the new location", link.display()))?;
+        }
+
+        // A task may have started while the files moved
+        let mut db = lock()?;
+        ensure_idle(&db)?;
+        db.checkpoint()?;
+        std::fs::copy(from.join(DB_FILE), to.join(DB_FILE)).context("failed to copy the database")?;
+        *db = Db::open(&to.join(DB_FILE))?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(to.join(DB_FILE));
+        if to != app_dir {
+            remove_link(&link);
+        }
+        for (src, dest) in moved.iter().rev() {
+            if let Err(e) = move_dir(dest, src) {
+                warn!("failed to move {} back to {}: {e:#}", dest.display(), src.display());
+            }
+        }
+        if from != app_dir {
+            let _ = link_dir(&from.join(PROJECTS_DIR), &link);
+        }
+        return Err(e);
+    }
+    report.moved = moved.iter().filter_map(|(src, _)| src.file_name()).map(|n| n.to_string_lossy().to_string()).collect();
+    report.moved.push(DB_FILE.to_string());
+
+    // The old connection is closed now
+    for suffix in ["", "-wal", "-shm"] {
+        let old = from.join(format!("{DB_FILE}{suffix}"));
+        if old.exists() {
+            if let Err(e) = std::fs::remove_file(&old) {
+                report.warnings.push(format!("failed to remove {}: {e}", old.display()));
+            }
+        }
+    }
+
+    let rewrite = Rewrite::new(&from.join(PROJECTS_DIR).to_string_lossy(), &to.join(PROJECTS_DIR));
+    for dir in [to.join(PROJECTS_DIR), to.join(TRASH_DIR).join("projects")] {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            match rewrite_canvas(&entry.path().join("canvas.json"), &rewrite) {
+                Ok(true) => report.canvases_updated += 1,
+                Ok(false) => {}
+                Err(e) => report.warnings.push(format!("{}: {e:#}", entry.path().display())),
+            }
+        }
+    }
+
+    let target = (to != app_dir).then_some(to);
+    if let Err(e) = save_data_dir(app_dir, target) {
+        // The rows are already relative, so the library works from either
+        // place; only settings.json still points at the old one
+        report.warnings.push(format!("failed to record the new location: {e:#}"));
+    }
+    Ok(report)
+}
+
+fn ensure_idle(db: &Db) -> Result<()> {
+    let active = db.count_active_tasks()?;
+    if active > 0 {
+        bail!("{active} tasks are unfinished; wait for them before moving the library");
+    }
+    Ok(())
+}
+
+fn check_target(from: &Path, to: &Path) -> Result<()> {
+    if !to.is_absolute() {
+        bail!("{} is not an absolute path", to.display());
+    }
+    if to == from {
+        bail!("the library is already in {}", to.display());
+    }
+    if to.starts_with(from) || from.starts_with(to) {
+        bail!("{} and {} are inside one another", to.display(), from.display());
+    }
+    if to.exists() && !to.is_dir() {
+        bail!("{} is not a directory", to.display());
+    }
+    let holds_projects = std::fs::symlink_metadata(to.join(PROJECTS_DIR)).is_ok_and(|m| !m.is_symlink());
+    if holds_projects || to.join(DB_FILE).exists() || to.join(TRASH_DIR).exists() {
+        bail!("{} already holds a library", to.display());
+    }
+    Ok(())
+}
+
+/// Rename, or copy and delete when `dest` is on another file system.
+fn move_dir(src: &Path, dest: &Path) -> Result<()> {
+    if std::fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+    if let Err(e) = copy_dir(src, dest) {
+        let _ = std::fs::remove_dir_all(dest);
+        return Err(e.context(format!("failed to copy {} to {}", src.display(), dest.display())));
+    }
+    if let Err(e) = std::fs::remove_dir_all(src) {
+        warn!("failed to remove {} after copying it: {e}", src.display());
+    }
+    Ok(())
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Point the media URLs in a canvas at the new projects directory. Returns
+/// whether the file changed.
+fn rewrite_canvas(path: &Path, rewrite: &Rewrite) -> Result<bool> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Ok(false);
+    };
+    let mut canvas: serde_json::Value = serde_json::from_str(&text).context("invalid canvas.json")?;
+    let original = canvas.clone();
+    rewrite.json(&mut canvas);
+    if canvas == original {
+        return Ok(false);
+    }
+    std::fs::write(path, serde_json::to_string(&canvas)?)?;
+    Ok(true)
+}
+
+/// Link `link` to the directory `target`. A Windows symlink needs Developer
+/// Mode or administrator rights, so a junction is the fallback there.
+fn link_dir(target: &Path, link: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)?;
+        Ok(())
+    }
+    #[cfg(windows)]
+    {
+        let Err(symlink_err) = std::os::windows::fs::symlink_dir(target, link) else {
+            return Ok(());
+        };
+        let junction = std::process::Command::new("cmd")
+            .args(["/C", "mklink", "/J"])
+            .arg(link)
+            .arg(target)
+            .stdout(std::process::Stdio::null())
+            .status();
+        match junction {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => bail!(
+                "a symlink needs Developer Mode or administrator rights ({symlink_err}), \
+                 and creating a junction instead failed ({status})"
+            ),
+            Err(e) => bail!(
+                "a symlink needs Developer Mode or administrator rights ({symlink_err}), \
+                 and creating a junction instead failed: {e}"
+            ),
+        }
+    }
+}
+
+/// Remove `link` if it is a symlink or junction; a real directory is left alone.
+fn remove_link(link: &Path) {
+    if std::fs::symlink_metadata(link).is_ok_and(|m| m.is_symlink()) {
+        let _ = std::fs::remove_file(link).or_else(|_| std::fs::remove_dir(link));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::asset_row;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn relocates_library_and_back() {
+        let base = std::env::temp_dir().join(format!("seedcanvas-datadir-{}", uuid::Uuid::new_v4()));
+        let (app, external) = (base.join("app"), base.join("external"));
+        let project = app.join(PROJECTS_DIR).join("p1");
+        std::fs::create_dir_all(project.join("assets")).unwrap();
+        std::fs::write(project.join("assets/a.png"), b"png").unwrap();
+        let url = project.join("assets/a.png").to_string_lossy().to_string();
+        std::fs::write(project.join("canvas.json"), serde_json::json!({ "nodes": [{ "url": url }] }).to_string())
+            .unwrap();
+        let db: SharedDb = Arc::new(Mutex::new(Db::open(&app.join(DB_FILE)).unwrap()));
+        db.lock().unwrap().insert_asset(&asset_row("a", &project.join("assets/a.png"))).unwrap();
+
+        assert!(relocate(&app, &db, &app.join("inside")).is_err());
+        let report = relocate(&app, &db, &external).unwrap();
+        assert_eq!(report.canvases_updated, 1);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(resolve(&app).unwrap(), external);
+        assert!(!app.join(DB_FILE).exists());
+
+        let moved = external.join(PROJECTS_DIR).join("p1/assets/a.png");
+        let row = db.lock().unwrap().get_assets_by_ids(&["a".into()]).unwrap().pop().unwrap();
+        assert_eq!(Path::new(&row.file_path), moved);
+        assert_eq!(std::fs::read(&row.file_path).unwrap(), b"png");
+        let canvas = std::fs::read_to_string(external.join(PROJECTS_DIR).join("p1/canvas.json")).unwrap();
+        assert!(canvas.contains(&*moved.to_string_lossy()));
+        #[cfg(unix)]
+        assert!(app.join(PROJECTS_DIR).join("p1/assets/a.png").exists());
+
+        // Already there
+        assert!(relocate(&app, &db, &external).is_err());
+
+        // A directory where the link belongs fails the move and puts it back
+        #[cfg(unix)]
+        {
+            std::fs::remove_file(app.join(PROJECTS_DIR)).unwrap();
+            std::fs::create_dir(app.join(PROJECTS_DIR)).unwrap();
+            assert!(relocate(&app, &db, &base.join("elsewhere")).is_err());
+            assert!(external.join(PROJECTS_DIR).join("p1/assets/a.png").exists());
+            assert_eq!(resolve(&app).unwrap(), external);
+            std::fs::remove_dir(app.join(PROJECTS_DIR)).unwrap();
+        }
+
+        relocate(&app, &db, &app).unwrap();
+        assert_eq!(resolve(&app).unwrap(), app);
+        assert!(std::fs::symlink_metadata(app.join(PROJECTS_DIR)).is_ok_and(|m| m.is_dir()));
+        let row = db.lock().unwrap().get_assets_by_ids(&["a".into()]).unwrap().pop().unwrap();
+        assert_eq!(Path::new(&row.file_path), project.join("assets/a.png"));
+
+        std::fs::write(app.join(SETTINGS_FILE), r#"{"dataDir": "/nonexistent/seedcanvas"}"#).unwrap();
+        assert!(resolve(&app).is_err());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
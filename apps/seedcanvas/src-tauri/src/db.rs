@@ -1,13 +1,196 @@
-use anyhow::{Context, Result};
-use rusqlite::{params, Connection};
+use anyhow::{bail, Context, Result};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
+use crate::datadir;
+use crate::media::palette::{self, PaletteColor};
+use crate::media::probe::{self, MediaInfo};
+use crate::search;
+use crate::storage;
+
 /// Thread-safe database handle. rusqlite::Connection is !Sync,
 /// so we wrap Db in a Mutex for cross-thread access.
 pub type SharedDb = Arc<Mutex<Db>>;
 
+// ---------------------------------------------------------------------------
+// Task state machine
+//
+//   pending ──► running ──► queued_remote ──► downloading ──► done
+//      │           │  ▲            │               │
+//      │           │  └────────────┴───────────────┘  (resume after restart)
+//      └───────────┴──────────────► failed / cancelled
+//
+// `done`, `failed` and `cancelled` are terminal. In-flight states may be
+// re-entered (e.g. `running` again with the ARK task ID filled in).
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    /// Row created, not yet picked up by a worker.
+    Pending,
+    /// A worker is executing the task locally.
+    Running,
+    /// Submitted to ARK, waiting for the remote job to finish.
+    QueuedRemote,
+    /// Remote job finished; fetching the result into the assets directory.
+    Downloading,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+/// Allowed `from → to` moves. Anything not listed is rejected by `Db::update_task`.
+const TASK_TRANSITIONS: &[(TaskStatus, &[TaskStatus])] = {
+    use TaskStatus::*;
+    &[
+        (Pending, &[Running, QueuedRemote, Failed, Cancelled]),
+        (Running, &[Running, QueuedRemote, Downloading, Done, Failed, Cancelled]),
+        (QueuedRemote, &[Running, QueuedRemote, Downloading, Done, Failed, Cancelled]),
+        (Downloading, &[Running, Downloading, Done, Failed]),
+        (Done, &[]),
+        (Failed, &[]),
+        (Cancelled, &[]),
+    ]
+};
+
+/// SQL list of the non-terminal states, for `status IN (…)` clauses.
+const ACTIVE_STATUSES_SQL: &str = "('pending', 'running', 'queued_remote', 'downloading')";
+
+impl TaskStatus {
+    pub const ALL: [TaskStatus; 7] = [
+        TaskStatus::Pending,
+        TaskStatus::Running,
+        TaskStatus::QueuedRemote,
+        TaskStatus::Downloading,
+        TaskStatus::Done,
+        TaskStatus::Failed,
+        TaskStatus::Cancelled,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskStatus::Pending => "pending",
+            TaskStatus::Running => "running",
+            TaskStatus::QueuedRemote => "queued_remote",
+            TaskStatus::Downloading => "downloading",
+            TaskStatus::Done => "done",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_terminal(self) -> bool {
+        matches!(self, TaskStatus::Done | TaskStatus::Failed | TaskStatus::Cancelled)
+    }
+
+    pub fn can_transition_to(self, next: TaskStatus) -> bool {
+        TASK_TRANSITIONS
+            .iter()
+            .find(|(from, _)| *from == self)
+            .is_some_and(|(_, to)| to.contains(&next))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskType {
+    Image,
+    Video,
+}
+
+impl TaskType {
+    pub const ALL: [TaskType; 2] = [TaskType::Image, TaskType::Video];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskType::Image => "image",
+            TaskType::Video => "video",
+        }
+    }
+}
+
+/// `Display`/`FromStr` use the same lowercase strings as serde and the DB.
+macro_rules! impl_text_enum {
+    ($ty:ident, $what:literal) => {
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $ty {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> Result<Self> {
+                $ty::ALL
+                    .into_iter()
+                    .find(|v| v.as_str() == s)
+                    .ok_or_else(|| anyhow::anyhow!(concat!("unknown ", $what, " \"{}\""), s))
+            }
+        }
+
+        impl ToSql for $ty {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.as_str()))
+            }
+        }
+
+        impl FromSql for $ty {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                value
+                    .as_str()?
+                    .parse()
+                    .map_err(|e: anyhow::Error| FromSqlError::Other(e.into()))
+            }
+        }
+    };
+}
+
+impl_text_enum!(TaskStatus, "task status");
+impl_text_enum!(TaskType, "task type");
+impl_text_enum!(LinkRelation, "link relation");
+
+/// How a source asset relates to an asset derived from it, read as
+/// "source <relation> derived" for the first two and "derived <relation>
+/// source" for the last two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkRelation {
+    /// The source image was passed to the model as a reference.
+    ReferenceOf,
+    /// The source image was the first frame of the derived video.
+    FirstFrameOf,
+    /// The derived asset is a variation of the source (new settings or edit).
+    VariantOf,
+    /// The derived asset re-ran the source's generation.
+    RetryOf,
+}
+
+impl LinkRelation {
+    pub const ALL: [LinkRelation; 4] = [
+        LinkRelation::ReferenceOf,
+        LinkRelation::FirstFrameOf,
+        LinkRelation::VariantOf,
+        LinkRelation::RetryOf,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkRelation::ReferenceOf => "reference_of",
+            LinkRelation::FirstFrameOf => "first_frame_of",
+            LinkRelation::VariantOf => "variant_of",
+            LinkRelation::RetryOf => "retry_of",
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Task row model
 // ---------------------------------------------------------------------------
@@ -17,9 +200,9 @@ pub struct TaskRow {
     pub id: String,
     pub project_id: String,
     #[serde(rename = "type")]
-    pub task_type: String, // "image" | "video"
-    pub status: String,    // "pending" | "running" | "done" | "failed"
-    pub input: String,     // JSON
+    pub task_type: TaskType,
+    pub status: TaskStatus,
+    pub input: String, // JSON
     pub output: Option<String>,
     pub ark_task_id: Option<String>,
     pub error: Option<String>,
@@ -31,7 +214,7 @@ pub struct TaskRow {
 // Asset row model
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AssetRow {
     pub id: String,
@@ -39,6 +222,8 @@ pub struct AssetRow {
     pub task_id: Option<String>,
     #[serde(rename = "type")]
     pub asset_type: String, // "image" | "video"
+    /// Absolute, though stored relative to the project directory (see
+    /// "Stored paths" on [`Db`]).
     pub file_path: String,
     pub file_name: String,
     pub prompt: Option<String>,
@@ -48,6 +233,111 @@ pub struct AssetRow {
     pub file_size: Option<i64>,
     pub source: String, // "generated" | "imported"
     pub created_at: String,
+    /// Seconds (video only).
+    pub duration: Option<f64>,
+    pub frame_rate: Option<f64>,
+    pub codec: Option<String>,
+    /// Downscaled WebP preview (see media::thumbnail).
+    pub thumbnail_path: Option<String>,
+    /// Full-size last frame of a video.
+    pub poster_path: Option<String>,
+    /// Hex SHA-256 of the file content (see storage::verify_assets).
+    pub sha256: Option<String>,
+    pub favorite: bool,
+    /// 1–5 stars; `None` is unrated.
+    pub rating: Option<i32>,
+    /// Tag names, sorted. Filled by the list queries; `insert_asset` links them.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Dominant colors of an image, heaviest first (see media::palette).
+    /// Filled by the list queries; `insert_asset` stores it.
+    #[serde(default)]
+    pub palette: Vec<PaletteColor>,
+    /// Free-form user notes, searchable alongside the prompt.
+    pub notes: Option<String>,
+}
+
+impl AssetRow {
+    /// Fill dimensions and video metadata from a probe of the file.
+    pub fn set_media_info(&mut self, info: &MediaInfo) {
+        self.width = info.width.map(|w| w as i32);
+        self.height = info.height.map(|h| h as i32);
+        self.duration = info.duration;
+        self.frame_rate = info.frame_rate;
+        self.codec = info.codec.clone();
+    }
+}
+
+/// Filters for [`Db::list_assets`]. Unset fields do not filter.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetFilter {
+    pub project_id: Option<String>,
+    pub asset_type: Option<String>,
+    /// Full-text query over prompt, notes, tags and file name.
+    pub query: Option<String>,
+    /// Assets must carry every one of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub collection_id: Option<String>,
+    pub favorite: Option<bool>,
+    pub min_rating: Option<i32>,
+    /// `#rrggbb`; assets with a palette color within `color_distance` of it.
+    pub color: Option<String>,
+    /// ΔE in Lab space. Defaults to `palette::DEFAULT_COLOR_DISTANCE`.
+    pub color_distance: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub asset: AssetRow,
+    /// Relevance, higher is better. Only comparable within one search.
+    pub score: f64,
+    /// Best-matching excerpt with matches wrapped in `<mark>…</mark>`.
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub name: String,
+    pub asset_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub asset_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// One edge of the lineage graph: `asset_id` was derived from `source_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetLink {
+    pub source_id: String,
+    pub asset_id: String,
+    pub relation: LinkRelation,
+    pub task_id: Option<String>,
+    pub created_at: String,
+}
+
+/// Everything an asset came from and everything made from it, transitively.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetLineage {
+    pub asset: AssetRow,
+    /// Sources, nearest first.
+    pub ancestors: Vec<AssetRow>,
+    /// Derived assets, nearest first.
+    pub descendants: Vec<AssetRow>,
+    /// Every edge between the assets above.
+    pub links: Vec<AssetLink>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +359,56 @@ pub struct UsageStats {
     pub failed: i64,
     pub daily_counts: Vec<DailyCount>,
     pub recent_tasks: Vec<TaskRow>,
+    pub spend: Vec<SpendTotal>,
+}
+
+/// An asset in the trash, with when it was deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedAsset {
+    pub asset: AssetRow,
+    pub deleted_at: String,
+}
+
+/// A project directory in the trash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedProject {
+    pub project_id: String,
+    /// From the project's manifest, when it had one.
+    pub name: Option<String>,
+    pub trash_dir: String,
+    pub deleted_at: String,
+}
+
+/// Spend in one currency. `estimated` covers every priced task submitted;
+/// `actual` only finished ones, at their settled cost.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendTotal {
+    pub currency: String,
+    pub estimated: f64,
+    pub actual: f64,
+    pub priced_tasks: i64,
+    pub usage_tokens: i64,
+}
+
+/// A task's cost record joined with the task it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskCostRow {
+    pub task_id: String,
+    pub project_id: String,
+    pub task_type: TaskType,
+    pub status: TaskStatus,
+    pub model: Option<String>,
+    pub created_at: String,
+    pub currency: String,
+    pub estimated_cost: Option<f64>,
+    pub actual_cost: Option<f64>,
+    pub usage_tokens: Option<i64>,
+    /// `estimate`, `output` or `tokens` — see [`crate::pricing::CostBasis`].
+    pub basis: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,31 +418,187 @@ pub struct DailyCount {
     pub count: i64,
 }
 
+/// Half-open `[from, to)` window over `created_at`. Either bound may be an
+/// RFC 3339 timestamp or a bare `YYYY-MM-DD` date; a missing bound is open.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsRange {
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+impl StatsRange {
+    /// Bounds in the UTC form `created_at` is stored in, so SQLite can
+    /// compare them as strings.
+    fn bounds(&self) -> Result<(Option<String>, Option<String>)> {
+        let normalize = |bound: &Option<String>| -> Result<Option<String>> {
+            let Some(raw) = bound.as_deref().map(str::trim).filter(|s| !s.is_empty()) else {
+                return Ok(None);
+            };
+            if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(raw) {
+                return Ok(Some(ts.with_timezone(&chrono::Utc).to_rfc3339()));
+            }
+            chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .map(|d| Some(d.format("%Y-%m-%d").to_string()))
+                .map_err(|_| anyhow::anyhow!("invalid date \"{raw}\": expected YYYY-MM-DD or RFC 3339"))
+        };
+        Ok((normalize(&self.from)?, normalize(&self.to)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsGroupBy {
+    Project,
+    Model,
+    Type,
+}
+
+impl StatsGroupBy {
+    pub const ALL: [StatsGroupBy; 3] = [StatsGroupBy::Project, StatsGroupBy::Model, StatsGroupBy::Type];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StatsGroupBy::Project => "project",
+            StatsGroupBy::Model => "model",
+            StatsGroupBy::Type => "type",
+        }
+    }
+
+    /// SQL expression for the group key over `tasks`.
+    fn task_key(self) -> &'static str {
+        match self {
+            StatsGroupBy::Project => "project_id",
+            StatsGroupBy::Model => "json_extract(input, '$.model')",
+            StatsGroupBy::Type => "type",
+        }
+    }
+
+    /// SQL expression for the group key over `assets`.
+    fn asset_key(self) -> &'static str {
+        match self {
+            StatsGroupBy::Project => "project_id",
+            StatsGroupBy::Model => "model",
+            StatsGroupBy::Type => "type",
+        }
+    }
+}
+
+impl_text_enum!(StatsGroupBy, "stats grouping");
+
+/// Task outcomes for one group. Latencies run from `created_at` to
+/// `updated_at` of finished (`done`) tasks, in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskBreakdown {
+    /// Project ID, model or task type; `None` for tasks that named no model.
+    pub key: Option<String>,
+    pub total: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub cancelled: i64,
+    /// Still pending or running.
+    pub in_progress: i64,
+    /// `succeeded / (succeeded + failed)`; `None` until one of them is non-zero.
+    pub success_ratio: Option<f64>,
+    pub median_latency_secs: Option<f64>,
+    pub p95_latency_secs: Option<f64>,
+    /// Costs of the group's priced tasks, one entry per currency.
+    pub spend: Vec<SpendTotal>,
+}
+
+/// Assets recorded in one group and the bytes of their distinct files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetBreakdown {
+    pub key: Option<String>,
+    pub count: i64,
+    pub images: i64,
+    pub videos: i64,
+    pub total_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsBreakdown {
+    pub group_by: StatsGroupBy,
+    pub range: StatsRange,
+    pub tasks: Vec<TaskBreakdown>,
+    pub assets: Vec<AssetBreakdown>,
+}
+
 // ---------------------------------------------------------------------------
 // Database wrapper
 // ---------------------------------------------------------------------------
 
+/// Guard returned by [`Db::begin`]. Dereferences to the connection.
+pub struct Atomic<'a> {
+    conn: &'a Connection,
+    committed: bool,
+}
+
+impl Atomic<'_> {
+    pub fn commit(mut self) -> Result<()> {
+        self.conn.execute_batch("RELEASE db_atomic")?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl std::ops::Deref for Atomic<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+    }
+}
+
+impl Drop for Atomic<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.conn.execute_batch("ROLLBACK TO db_atomic; RELEASE db_atomic");
+        }
+    }
+}
+
 pub struct Db {
     conn: Connection,
+    /// The data directory: the one holding the DB file, `projects/` and
+    /// `trash/`. Stored paths are relative to it (see "Stored paths").
+    root: PathBuf,
 }
 
 impl Db {
-    /// Open (or create) the database at `path` and run migrations.
+    /// Open (or create) the database at `path` and run migrations. Paths in
+    /// the rows are resolved against the directory `path` is in.
     pub fn open(path: &Path) -> Result<Self> {
         let conn = Connection::open(path).context("failed to open SQLite database")?;
         conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")?;
-        let db = Db { conn };
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let db = Db { conn, root };
         db.migrate()?;
         Ok(db)
     }
 
+    /// Start a transaction that nests: inside another one it becomes a
+    /// savepoint, so several `Db` calls can be made atomic together (e.g.
+    /// replacing a project's rows on import). Rolls back unless committed.
+    pub fn begin(&self) -> Result<Atomic<'_>> {
+        self.conn.execute_batch("SAVEPOINT db_atomic")?;
+        Ok(Atomic { conn: &self.conn, committed: false })
+    }
+
     fn migrate(&self) -> Result<()> {
         self.conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS tasks (
                 id          TEXT PRIMARY KEY,
                 project_id  TEXT NOT NULL,
-                type        TEXT NOT NULL,
-                status      TEXT NOT NULL DEFAULT 'pending',
+                type        TEXT NOT NULL CHECK (type IN ('image', 'video')),
+                status      TEXT NOT NULL DEFAULT 'pending' CHECK (status IN (
+                                'pending', 'running', 'queued_remote', 'downloading',
+                                'done', 'failed', 'cancelled')),
                 input       TEXT NOT NULL,
                 output      TEXT,
                 ark_task_id TEXT,
@@ -110,8 +606,6 @@ impl Db {
                 created_at  TEXT NOT NULL,
                 updated_at  TEXT NOT NULL
             );
-            CREATE INDEX IF NOT EXISTS idx_tasks_project ON tasks(project_id);
-            CREATE INDEX IF NOT EXISTS idx_tasks_status  ON tasks(status);
 
             CREATE TABLE IF NOT EXISTS assets (
                 id          TEXT PRIMARY KEY,
@@ -138,14 +632,412 @@ impl Db {
             DROP TABLE IF EXISTS chat_messages;
             DROP TABLE IF EXISTS chat_sessions;",
         )?;
+
+        // Task leases — which process (app or seedcanvas-mcp) is executing a task
+        self.add_column_if_missing("tasks", "lease_owner", "TEXT")?;
+        self.add_column_if_missing("tasks", "heartbeat_at", "TEXT")?;
+        self.add_column_if_missing("tasks", "lease_expires_at", "TEXT")?;
+
+        // Probed media metadata (see media::probe)
+        self.add_column_if_missing("assets", "duration", "REAL")?;
+        self.add_column_if_missing("assets", "frame_rate", "REAL")?;
+        self.add_column_if_missing("assets", "codec", "TEXT")?;
+        self.add_column_if_missing("assets", "thumbnail_path", "TEXT")?;
+        self.add_column_if_missing("assets", "poster_path", "TEXT")?;
+        self.add_column_if_missing("assets", "sha256", "TEXT")?;
+        self.conn
+            .execute_batch("CREATE INDEX IF NOT EXISTS idx_assets_sha256 ON assets(sha256);")?;
+
+        // Organization: favorite/rating on the row, tags and collections as
+        // many-to-many links that disappear with the asset
+        self.add_column_if_missing("assets", "favorite", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("assets", "rating", "INTEGER CHECK (rating BETWEEN 1 AND 5)")?;
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tags (
+                id    INTEGER PRIMARY KEY,
+                name  TEXT NOT NULL UNIQUE COLLATE NOCASE
+            );
+            CREATE TABLE IF NOT EXISTS asset_tags (
+                asset_id TEXT NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+                tag_id   INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                PRIMARY KEY (asset_id, tag_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_asset_tags_tag ON asset_tags(tag_id);
+
+            CREATE TABLE IF NOT EXISTS collections (
+                id          TEXT PRIMARY KEY,
+                name        TEXT NOT NULL,
+                description TEXT,
+                created_at  TEXT NOT NULL,
+                updated_at  TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS collection_assets (
+                collection_id TEXT NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+                asset_id      TEXT NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+                added_at      TEXT NOT NULL,
+                PRIMARY KEY (collection_id, asset_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_collection_assets_asset ON collection_assets(asset_id);",
+        )?;
+
+        self.add_column_if_missing("assets", "notes", "TEXT")?;
+        self.create_search_index()?;
+
+        // Lineage: which assets a generation was derived from
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS asset_links (
+                source_id  TEXT NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+                asset_id   TEXT NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+                relation   TEXT NOT NULL CHECK (relation IN ('reference_of', 'first_frame_of', 'variant_of', 'retry_of')),
+                task_id    TEXT,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (source_id, asset_id, relation),
+                CHECK (source_id <> asset_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_asset_links_asset ON asset_links(asset_id);",
+        )?;
+
+        self.add_task_check_constraints()?;
+        self.conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_tasks_project ON tasks(project_id);
+             CREATE INDEX IF NOT EXISTS idx_tasks_status  ON tasks(status);",
+        )?;
+
+        // Spend tracking (see crate::pricing). Created after the rebuild
+        // above, since dropping `tasks` takes its triggers with it.
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS task_costs (
+                task_id        TEXT PRIMARY KEY,
+                currency       TEXT NOT NULL,
+                estimated_cost REAL,
+                actual_cost    REAL,
+                usage_tokens   INTEGER,
+                basis          TEXT CHECK (basis IN ('estimate', 'output', 'tokens')),
+                estimated_at   TEXT,
+                settled_at     TEXT
+            );
+            CREATE TRIGGER IF NOT EXISTS tasks_costs_delete AFTER DELETE ON tasks BEGIN
+                DELETE FROM task_costs WHERE task_id=old.id;
+            END;",
+        )?;
+
+        // Trash: soft-deleted rows keep their data until the retention sweep
+        // purges them (see crate::trash). Also after the rebuild, which only
+        // carries the columns it knows.
+        self.add_column_if_missing("assets", "deleted_at", "TEXT")?;
+        self.add_column_if_missing("tasks", "deleted_at", "TEXT")?;
+        // Set when a task's asset is deleted for good, so the backfill does
+        // not bring the row back from a file that is still on disk
+        self.add_column_if_missing("tasks", "output_deleted", "INTEGER NOT NULL DEFAULT 0")?;
+        self.conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_assets_deleted_at ON assets(deleted_at);
+             CREATE INDEX IF NOT EXISTS idx_tasks_deleted_at ON tasks(deleted_at);
+             CREATE TABLE IF NOT EXISTS trashed_projects (
+                project_id TEXT PRIMARY KEY,
+                name       TEXT,
+                trash_dir  TEXT NOT NULL,
+                deleted_at TEXT NOT NULL
+             );",
+        )?;
+
+        // 64-bit difference hash of image assets, stored as its signed bit
+        // pattern (see crate::media::perceptual)
+        self.add_column_if_missing("assets", "dhash", "INTEGER")?;
+        // Set when the file could not be read or decoded, so it is not
+        // retried on every scan; cleared when the asset is relinked
+        self.add_column_if_missing("assets", "dhash_failed", "INTEGER NOT NULL DEFAULT 0")?;
+
+        // Dominant colors, with Lab components for the color filter in SQL
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS asset_colors (
+                asset_id TEXT NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+                rank     INTEGER NOT NULL,
+                hex      TEXT NOT NULL,
+                weight   REAL NOT NULL,
+                l        REAL NOT NULL,
+                a        REAL NOT NULL,
+                b        REAL NOT NULL,
+                PRIMARY KEY (asset_id, rank)
+            );",
+        )?;
+        // Set once extraction has run, even if it failed or found nothing, so
+        // the startup backfill does not decode the same file every launch
+        self.add_column_if_missing("assets", "palette_checked", "INTEGER NOT NULL DEFAULT 0")?;
+
+        // Paths used to be stored absolute, which tied the library to the
+        // directory it was created in
+        self.relativize_stored_paths()?;
+        Ok(())
+    }
+
+    /// DB files created before the task state machine have no CHECK
+    /// constraints on `tasks`. SQLite cannot add one with ALTER TABLE, so the
+    /// table is rebuilt once. Unknown statuses become `failed`; rows with an
+    /// unknown type cannot be executed or displayed and are dropped.
+    fn add_task_check_constraints(&self) -> Result<()> {
+        let sql: String = self.conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type='table' AND name='tasks'",
+            [],
+            |r| r.get(0),
+        )?;
+        if sql.contains("CHECK") {
+            return Ok(());
+        }
+
+        let tx = self.begin()?;
+        let dropped: Vec<(String, String, String)> = tx
+            .prepare("SELECT id, project_id, type FROM tasks WHERE type NOT IN ('image', 'video')")?
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        for (id, project_id, task_type) in &dropped {
+            tracing::warn!(task_id = %id, project_id = %project_id, task_type = %task_type,
+                "dropping task with unknown type during schema upgrade");
+        }
+
+        tx.execute_batch(
+            "CREATE TABLE tasks_new (
+                id               TEXT PRIMARY KEY,
+                project_id       TEXT NOT NULL,
+                type             TEXT NOT NULL CHECK (type IN ('image', 'video')),
+                status           TEXT NOT NULL DEFAULT 'pending' CHECK (status IN (
+                                     'pending', 'running', 'queued_remote', 'downloading',
+                                     'done', 'failed', 'cancelled')),
+                input            TEXT NOT NULL,
+                output           TEXT,
+                ark_task_id      TEXT,
+                error            TEXT,
+                created_at       TEXT NOT NULL,
+                updated_at       TEXT NOT NULL,
+                lease_owner      TEXT,
+                heartbeat_at     TEXT,
+                lease_expires_at TEXT
+             );
+             INSERT INTO tasks_new
+                SELECT id, project_id, type,
+                       CASE WHEN status IN ('pending', 'running', 'queued_remote', 'downloading',
+                                            'done', 'failed', 'cancelled')
+                            THEN status ELSE 'failed' END,
+                       input, output, ark_task_id, error, created_at, updated_at,
+                       lease_owner, heartbeat_at, lease_expires_at
+                FROM tasks WHERE type IN ('image', 'video');
+             DROP TABLE tasks;
+             ALTER TABLE tasks_new RENAME TO tasks;",
+        )?;
+        tx.commit()?;
         Ok(())
     }
 
+    /// FTS5 index over prompt, notes, tags and file name (see crate::search),
+    /// keyed by `assets.rowid`. Writers refresh it through [`index_assets`]
+    /// so the CJK segmentation happens in Rust and any other connection can
+    /// still write the tables; only the plain row delete is a trigger.
+    fn create_search_index(&self) -> Result<()> {
+        // The first version was keyed by an `asset_id` column and kept in
+        // sync by triggers calling a custom SQL function
+        let legacy: bool = self
+            .conn
+            .query_row("SELECT 1 FROM pragma_table_info('assets_fts') WHERE name='asset_id'", [], |_| Ok(true))
+            .optional()?
+            .unwrap_or(false);
+        if legacy {
+            self.conn.execute_batch(
+                "DROP TRIGGER IF EXISTS assets_fts_insert;
+                 DROP TRIGGER IF EXISTS assets_fts_update;
+                 DROP TRIGGER IF EXISTS assets_fts_delete;
+                 DROP TRIGGER IF EXISTS asset_tags_fts_insert;
+                 DROP TRIGGER IF EXISTS asset_tags_fts_delete;
+                 DROP TRIGGER IF EXISTS tags_fts_rename;
+                 DROP TABLE assets_fts;",
+            )?;
+        }
+
+        let exists: bool = self
+            .conn
+            .query_row("SELECT 1 FROM sqlite_master WHERE name='assets_fts'", [], |_| Ok(true))
+            .optional()?
+            .unwrap_or(false);
+        self.conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS assets_fts USING fts5(
+                prompt, notes, tags, file_name,
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+            CREATE TRIGGER IF NOT EXISTS assets_fts_delete AFTER DELETE ON assets BEGIN
+                DELETE FROM assets_fts WHERE rowid=old.rowid;
+            END;",
+        )?;
+
+        // First run on an existing library: index what is already there
+        if !exists {
+            self.rebuild_search_index()?;
+        }
+        Ok(())
+    }
+
+    /// Re-index every asset from scratch. `VACUUM` may renumber the implicit
+    /// rowids the index is keyed by, so run this after one.
+    pub fn rebuild_search_index(&self) -> Result<()> {
+        let tx = self.begin()?;
+        tx.execute("DELETE FROM assets_fts", [])?;
+        let ids: Vec<String> = tx
+            .prepare("SELECT id FROM assets")?
+            .query_map([], |r| r.get(0))?
+            .collect::<std::result::Result<_, _>>()?;
+        index_assets(&tx, &ids)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `ALTER TABLE … ADD COLUMN` for DB files created before the column existed.
+    fn add_column_if_missing(&self, table: &str, column: &str, decl: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({table})"))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|r| r.ok())
+            .any(|name| name == column);
+        if !exists {
+            self.conn
+                .execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+        }
+        Ok(())
+    }
+
+    /// Convert paths stored absolute by older versions: asset files,
+    /// thumbnails and posters and task output to project-relative, trashed
+    /// project directories to data-relative. A path is recognised by its
+    /// `projects/{project_id}/` part, so rows still convert after the data
+    /// directory was moved by hand. Anything else stays absolute.
+    fn relativize_stored_paths(&self) -> Result<()> {
+        type PathRow = (String, String, String, Option<String>, Option<String>);
+        let tx = self.begin()?;
+        let assets: Vec<PathRow> = tx
+            .prepare(
+                // Only rows that mention their project ID can need converting
+                "SELECT id, project_id, file_path, thumbnail_path, poster_path FROM assets
+                 WHERE instr(file_path, project_id) > 0 OR instr(coalesce(thumbnail_path, ''), project_id) > 0
+                    OR instr(coalesce(poster_path, ''), project_id) > 0",
+            )?
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        let mut converted = 0usize;
+        for (id, project_id, file, thumbnail, poster) in assets {
+            let convert = |p: &str| legacy_project_relative(p, &project_id);
+            let (new_file, new_thumbnail, new_poster) =
+                (convert(&file), thumbnail.as_deref().and_then(convert), poster.as_deref().and_then(convert));
+            if new_file.is_none() && new_thumbnail.is_none() && new_poster.is_none() {
+                continue;
+            }
+            tx.execute(
+                "UPDATE assets SET file_path=?2, thumbnail_path=?3, poster_path=?4 WHERE id=?1",
+                params![id, new_file.unwrap_or(file), new_thumbnail.or(thumbnail), new_poster.or(poster)],
+            )?;
+            converted += 1;
+        }
+
+        let tasks: Vec<(String, String, String)> = tx
+            .prepare("SELECT id, project_id, output FROM tasks WHERE instr(output, 'assetPath') > 0 AND instr(output, project_id) > 0")?
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        for (id, project_id, output) in tasks {
+            let mut changed = false;
+            let new_output = map_asset_path(&output, |p| match legacy_project_relative(p, &project_id) {
+                Some(rel) => {
+                    changed = true;
+                    rel
+                }
+                None => p.to_string(),
+            });
+            if changed {
+                tx.execute("UPDATE tasks SET output=?2 WHERE id=?1", params![id, new_output])?;
+                converted += 1;
+            }
+        }
+
+        let trashed: Vec<(String, String)> = tx
+            .prepare("SELECT project_id, trash_dir FROM trashed_projects")?
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        for (project_id, trash_dir) in trashed {
+            if Path::new(&trash_dir).is_absolute() {
+                tx.execute(
+                    "UPDATE trashed_projects SET trash_dir=?2 WHERE project_id=?1",
+                    params![project_id, format!("{}/projects/{project_id}", datadir::TRASH_DIR)],
+                )?;
+                converted += 1;
+            }
+        }
+        tx.commit()?;
+        if converted > 0 {
+            tracing::info!(rows = converted, "converted stored paths to relative");
+        }
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------
+    // Stored paths
+    //
+    // Asset files, thumbnails and posters, and `assetPath` in task output,
+    // are stored relative to their project's directory (`assets/x.png`);
+    // trashed project directories relative to the data directory. Moving
+    // the data directory leaves every row valid. Rows are resolved to
+    // absolute paths as they are read, so nothing outside this file sees the
+    // stored form. A path outside the project's directory is stored as is.
+    // -----------------------------------------------------------------------
+
+    /// The data directory the DB was opened in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn project_dir(&self, project_id: &str) -> PathBuf {
+        self.root.join(datadir::PROJECTS_DIR).join(project_id)
+    }
+
+    /// `path` as stored for a row of `project_id`.
+    fn store_path(&self, project_id: &str, path: &str) -> String {
+        relative_to(&self.project_dir(project_id), path).unwrap_or_else(|| path.to_string())
+    }
+
+    fn resolve_path(&self, project_id: &str, stored: &str) -> String {
+        resolve_against(&self.project_dir(project_id), stored)
+    }
+
+    fn store_output(&self, project_id: &str, output: &str) -> String {
+        map_asset_path(output, |p| self.store_path(project_id, p))
+    }
+
+    fn resolve_asset(&self, mut asset: AssetRow) -> AssetRow {
+        asset.file_path = self.resolve_path(&asset.project_id, &asset.file_path);
+        asset.thumbnail_path = asset.thumbnail_path.map(|p| self.resolve_path(&asset.project_id, &p));
+        asset.poster_path = asset.poster_path.map(|p| self.resolve_path(&asset.project_id, &p));
+        asset
+    }
+
+    fn resolve_task(&self, mut task: TaskRow) -> TaskRow {
+        if let Some(output) = &task.output {
+            task.output = Some(map_asset_path(output, |p| self.resolve_path(&task.project_id, p)));
+        }
+        task
+    }
+
+    /// `(project_id, stored path)` to look up rows by an absolute file path.
+    /// The project is `None` for a path outside the projects directory.
+    fn locate_file(&self, path: &str) -> (Option<String>, String) {
+        let projects_dir = self.root.join(datadir::PROJECTS_DIR);
+        let Some(rel) = relative_to(&projects_dir, path) else {
+            return (None, path.to_string());
+        };
+        match rel.split_once('/') {
+            Some((project_id, rest)) => (Some(project_id.to_string()), rest.to_string()),
+            None => (None, path.to_string()),
+        }
+    }
+
     // -----------------------------------------------------------------------
     // CRUD
     // -----------------------------------------------------------------------
 
     pub fn insert_task(&self, task: &TaskRow) -> Result<()> {
+        let output = task.output.as_deref().map(|o| self.store_output(&task.project_id, o));
         self.conn.execute(
             "INSERT INTO tasks (id, project_id, type, status, input, output, ark_task_id, error, created_at, updated_at)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
@@ -155,7 +1047,7 @@ impl Db {
                 task.task_type,
                 task.status,
                 task.input,
-                task.output,
+                output,
                 task.ark_task_id,
                 task.error,
                 task.created_at,
@@ -165,19 +1057,38 @@ impl Db {
         Ok(())
     }
 
+    /// Move a task to `status`. Fails if the task does not exist or the move
+    /// is not in the transition table (e.g. `done → running`).
     pub fn update_task(
         &self,
         id: &str,
-        status: &str,
+        status: TaskStatus,
         output: Option<&str>,
         ark_task_id: Option<&str>,
         error: Option<&str>,
     ) -> Result<()> {
+        let current: Option<(TaskStatus, String)> = self
+            .conn
+            .query_row("SELECT status, project_id FROM tasks WHERE id=?1", params![id], |r| Ok((r.get(0)?, r.get(1)?)))
+            .optional()?;
+        let Some((current, project_id)) = current else {
+            bail!("task {id} not found");
+        };
+        if !current.can_transition_to(status) {
+            bail!("illegal task transition {current} → {status} for task {id}");
+        }
+
+        // Conditional on the status we validated against, so a concurrent
+        // writer in the other process cannot slip an illegal move past us.
         let now = chrono::Utc::now().to_rfc3339();
-        self.conn.execute(
-            "UPDATE tasks SET status=?2, output=?3, ark_task_id=?4, error=?5, updated_at=?6 WHERE id=?1",
-            params![id, status, output, ark_task_id, error, now],
+        let output = output.map(|o| self.store_output(&project_id, o));
+        let changed = self.conn.execute(
+            "UPDATE tasks SET status=?2, output=?3, ark_task_id=?4, error=?5, updated_at=?6 WHERE id=?1 AND status=?7",
+            params![id, status, output, ark_task_id, error, now, current],
         )?;
+        if changed == 0 {
+            bail!("task {id} changed status concurrently (was {current})");
+        }
         Ok(())
     }
 
@@ -185,43 +1096,138 @@ impl Db {
         let mut stmt = self.conn.prepare(
             "SELECT id, project_id, type, status, input, output, ark_task_id, error, created_at, updated_at FROM tasks WHERE id=?1",
         )?;
-        let mut rows = stmt.query_map(params![id], row_to_task)?;
+        let mut rows = stmt.query_map(params![id], |r| row_to_task(r).map(|t| self.resolve_task(t)))?;
         Ok(rows.next().transpose()?)
     }
 
-    pub fn get_running_tasks(&self) -> Result<Vec<TaskRow>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, project_id, type, status, input, output, ark_task_id, error, created_at, updated_at FROM tasks WHERE status='running'",
-        )?;
-        let rows = stmt.query_map([], row_to_task)?;
-        rows.collect::<std::result::Result<Vec<_>, _>>()
-            .context("failed to collect running tasks")
+    /// Tasks not yet finished, whichever process runs them.
+    pub fn count_active_tasks(&self) -> Result<i64> {
+        Ok(self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM tasks WHERE status IN {ACTIVE_STATUSES_SQL} AND deleted_at IS NULL"),
+            [],
+            |r| r.get(0),
+        )?)
     }
 
-    #[allow(dead_code)] // Used in Phase 4b (MCP server)
-    pub fn get_tasks_by_project(&self, project_id: &str) -> Result<Vec<TaskRow>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, project_id, type, status, input, output, ark_task_id, error, created_at, updated_at FROM tasks WHERE project_id=?1 ORDER BY created_at DESC",
-        )?;
-        let rows = stmt.query_map(params![project_id], row_to_task)?;
-        rows.collect::<std::result::Result<Vec<_>, _>>()
-            .context("failed to collect project tasks")
+    /// Fold the WAL into the main DB file, so that file alone is a complete
+    /// copy. Fails while another connection keeps the checkpoint from
+    /// finishing.
+    pub fn checkpoint(&self) -> Result<()> {
+        let busy: i64 = self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |r| r.get(0))?;
+        if busy != 0 {
+            bail!("the database is in use by another process");
+        }
+        Ok(())
     }
 
     // -------------------------------------------------------------------
-    // Asset CRUD
+    // Task leases
+    //
+    // A process executes a task only while it holds the lease: `lease_owner`
+    // is its owner ID and `lease_expires_at` is in the future. The holder
+    // renews on a heartbeat; a lease that expired (owner crashed or hung) may
+    // be claimed by any other process. Each claim is a single UPDATE, so
+    // concurrent claimers are serialized by SQLite and exactly one wins.
     // -------------------------------------------------------------------
 
-    pub fn insert_asset(&self, asset: &AssetRow) -> Result<()> {
+    /// Insert a new task already leased to `owner`, so no other process can
+    /// pick it up between insert and execution.
+    pub fn insert_claimed_task(&self, task: &TaskRow, owner: &str, ttl: std::time::Duration) -> Result<()> {
+        self.insert_task(task)?;
+        let now = lease_now();
         self.conn.execute(
-            "INSERT OR IGNORE INTO assets (id, project_id, task_id, type, file_path, file_name, prompt, model, width, height, file_size, source, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            "UPDATE tasks SET lease_owner=?2, heartbeat_at=?3, lease_expires_at=?4 WHERE id=?1",
+            params![task.id, owner, now, lease_deadline(ttl)],
+        )?;
+        Ok(())
+    }
+
+    /// Unfinished tasks whose lease is free or expired.
+    pub fn get_claimable_tasks(&self) -> Result<Vec<TaskRow>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, project_id, type, status, input, output, ark_task_id, error, created_at, updated_at FROM tasks
+             WHERE status IN {ACTIVE_STATUSES_SQL} AND deleted_at IS NULL
+             AND (lease_owner IS NULL OR lease_expires_at IS NULL OR lease_expires_at < ?1)"
+        ))?;
+        let rows = stmt.query_map(params![lease_now()], |r| row_to_task(r).map(|t| self.resolve_task(t)))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect claimable tasks")
+    }
+
+    /// Take the lease on a free or expired task. Returns false if another
+    /// process holds a live lease.
+    pub fn claim_task(&self, id: &str, owner: &str, ttl: std::time::Duration) -> Result<bool> {
+        let now = lease_now();
+        let changed = self.conn.execute(
+            &format!(
+                "UPDATE tasks SET lease_owner=?2, heartbeat_at=?3, lease_expires_at=?4
+                 WHERE id=?1 AND status IN {ACTIVE_STATUSES_SQL}
+                 AND (lease_owner IS NULL OR lease_expires_at IS NULL OR lease_expires_at < ?3)"
+            ),
+            params![id, owner, now, lease_deadline(ttl)],
+        )?;
+        Ok(changed == 1)
+    }
+
+    /// Extend `owner`'s lease. Returns false if the lease was lost.
+    pub fn renew_lease(&self, id: &str, owner: &str, ttl: std::time::Duration) -> Result<bool> {
+        let changed = self.conn.execute(
+            "UPDATE tasks SET heartbeat_at=?3, lease_expires_at=?4 WHERE id=?1 AND lease_owner=?2",
+            params![id, owner, lease_now(), lease_deadline(ttl)],
+        )?;
+        Ok(changed == 1)
+    }
+
+    pub fn release_lease(&self, id: &str, owner: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE tasks SET lease_owner=NULL, lease_expires_at=NULL WHERE id=?1 AND lease_owner=?2",
+            params![id, owner],
+        )?;
+        Ok(())
+    }
+
+    #[allow(dead_code)] // Used in Phase 4b (MCP server)
+    pub fn get_tasks_by_project(&self, project_id: &str) -> Result<Vec<TaskRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, project_id, type, status, input, output, ark_task_id, error, created_at, updated_at FROM tasks WHERE project_id=?1 AND deleted_at IS NULL ORDER BY created_at DESC",
+        )?;
+        let rows = stmt.query_map(params![project_id], |r| row_to_task(r).map(|t| self.resolve_task(t)))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect project tasks")
+    }
+
+    /// Finished tasks (optionally one project's), oldest first.
+    pub fn list_done_tasks(&self, project_id: Option<&str>) -> Result<Vec<TaskRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, project_id, type, status, input, output, ark_task_id, error, created_at, updated_at FROM tasks \
+             WHERE status='done' AND deleted_at IS NULL AND (?1 IS NULL OR project_id=?1) ORDER BY created_at ASC",
+        )?;
+        let rows = stmt.query_map(params![project_id], |r| row_to_task(r).map(|t| self.resolve_task(t)))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect done tasks")
+    }
+
+    pub fn delete_task(&self, id: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM tasks WHERE id=?1", params![id])? > 0)
+    }
+
+    // -------------------------------------------------------------------
+    // Asset CRUD
+    // -------------------------------------------------------------------
+
+    /// Insert an asset row. Returns `false`, changing nothing, when a row
+    /// with its ID already exists — trashed or not.
+    pub fn insert_asset(&self, asset: &AssetRow) -> Result<bool> {
+        let store = |p: &str| self.store_path(&asset.project_id, p);
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO assets (id, project_id, task_id, type, file_path, file_name, prompt, model, width, height, file_size, source, created_at, duration, frame_rate, codec, thumbnail_path, poster_path, sha256, favorite, rating, notes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
             params![
                 asset.id,
                 asset.project_id,
                 asset.task_id,
                 asset.asset_type,
-                asset.file_path,
+                store(&asset.file_path),
                 asset.file_name,
                 asset.prompt,
                 asset.model,
@@ -230,54 +1236,669 @@ impl Db {
                 asset.file_size,
                 asset.source,
                 asset.created_at,
+                asset.duration,
+                asset.frame_rate,
+                asset.codec,
+                asset.thumbnail_path.as_deref().map(store),
+                asset.poster_path.as_deref().map(store),
+                asset.sha256,
+                asset.favorite,
+                asset.rating,
+                asset.notes,
             ],
         )?;
-        Ok(())
+        if inserted > 0 {
+            index_assets(&self.conn, std::slice::from_ref(&asset.id))?;
+        }
+        if inserted > 0 && !asset.tags.is_empty() {
+            self.add_tags(std::slice::from_ref(&asset.id), &asset.tags)?;
+        }
+        if inserted > 0 && !asset.palette.is_empty() {
+            self.set_asset_palette(&asset.id, &asset.palette)?;
+        }
+        Ok(inserted > 0)
     }
 
-    pub fn list_assets(
+    /// The project an asset row belongs to, trashed rows included.
+    pub fn asset_project_id(&self, id: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT project_id FROM assets WHERE id=?1", params![id], |r| r.get(0))
+            .optional()?)
+    }
+
+    /// Assets matching `filter`, newest first. `filter.query` goes through
+    /// the full-text index; use [`search_assets`](Self::search_assets) for
+    /// relevance order and snippets.
+    pub fn list_assets(&self, filter: &AssetFilter, limit: usize, offset: usize) -> Result<Vec<AssetRow>> {
+        let mut sql = format!("SELECT {ASSET_COLUMNS} FROM assets WHERE 1=1");
+        let mut param_values: Vec<Box<dyn ToSql>> = Vec::new();
+        push_asset_filter(filter, "", &mut sql, &mut param_values)?;
+
+        param_values.push(Box::new(limit as i64));
+        sql.push_str(&format!(" ORDER BY created_at DESC LIMIT ?{}", param_values.len()));
+        param_values.push(Box::new(offset as i64));
+        sql.push_str(&format!(" OFFSET ?{}", param_values.len()));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let params_ref: Vec<&dyn ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(params_ref.as_slice(), |r| row_to_asset(r).map(|a| self.resolve_asset(a)))?;
+        let mut assets = rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect assets")?;
+        self.fill_details(&mut assets)?;
+        Ok(assets)
+    }
+
+    /// Full-text search (see crate::search for the query syntax), best match
+    /// first. The other `filter` fields narrow the results; `filter.query`
+    /// is ignored in favour of `query`.
+    pub fn search_assets(
         &self,
-        project_id: Option<&str>,
-        asset_type: Option<&str>,
-        query: Option<&str>,
+        query: &str,
+        filter: &AssetFilter,
         limit: usize,
         offset: usize,
-    ) -> Result<Vec<AssetRow>> {
-        let mut sql = String::from(
-            "SELECT id, project_id, task_id, type, file_path, file_name, prompt, model, width, height, file_size, source, created_at FROM assets WHERE 1=1"
+    ) -> Result<Vec<SearchHit>> {
+        let Some(fts_query) = search::match_query(query) else {
+            return Ok(Vec::new());
+        };
+        // bm25 weights follow the column order: prompt, notes, tags, file_name
+        let mut sql = format!(
+            "SELECT {}, bm25(assets_fts, 4.0, 3.0, 2.0, 1.0) AS score,
+                    snippet(assets_fts, -1, '{}', '{}', '…', 16)
+             FROM assets_fts JOIN assets a ON a.rowid = assets_fts.rowid
+             WHERE assets_fts MATCH ?1",
+            prefixed_asset_columns("a"),
+            search::MARK_START,
+            search::MARK_END,
         );
-        let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
-
-        if let Some(pid) = project_id {
-            param_values.push(Box::new(pid.to_string()));
-            sql.push_str(&format!(" AND project_id=?{}", param_values.len()));
-        }
-        if let Some(atype) = asset_type {
-            param_values.push(Box::new(atype.to_string()));
-            sql.push_str(&format!(" AND type=?{}", param_values.len()));
-        }
-        if let Some(q) = query {
-            param_values.push(Box::new(format!("%{q}%")));
-            sql.push_str(&format!(" AND prompt LIKE ?{}", param_values.len()));
-        }
+        let mut param_values: Vec<Box<dyn ToSql>> = vec![Box::new(fts_query)];
+        let filter = AssetFilter { query: None, ..filter.clone() };
+        push_asset_filter(&filter, "a.", &mut sql, &mut param_values)?;
 
         param_values.push(Box::new(limit as i64));
-        sql.push_str(&format!(" ORDER BY created_at DESC LIMIT ?{}", param_values.len()));
+        sql.push_str(&format!(" ORDER BY score, a.created_at DESC LIMIT ?{}", param_values.len()));
         param_values.push(Box::new(offset as i64));
         sql.push_str(&format!(" OFFSET ?{}", param_values.len()));
 
+        let score_col = ASSET_COLUMNS.split(',').count();
         let mut stmt = self.conn.prepare(&sql)?;
-        let params_ref: Vec<&dyn rusqlite::types::ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
-        let rows = stmt.query_map(params_ref.as_slice(), row_to_asset)?;
+        let params_ref: Vec<&dyn ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(params_ref.as_slice(), |row| {
+            Ok(SearchHit {
+                asset: self.resolve_asset(row_to_asset(row)?),
+                // bm25 is lower-is-better and negative; flip it for callers
+                score: -row.get::<_, f64>(score_col)?,
+                snippet: search::clean_snippet(&row.get::<_, String>(score_col + 1)?),
+            })
+        })?;
+        let mut hits = rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect search results")?;
+        for hit in &mut hits {
+            self.fill_details(std::slice::from_mut(&mut hit.asset))?;
+        }
+        Ok(hits)
+    }
+
+    pub fn set_asset_notes(&self, id: &str, notes: Option<&str>) -> Result<()> {
+        let notes = notes.map(str::trim).filter(|n| !n.is_empty());
+        if self.conn.execute("UPDATE assets SET notes=?2 WHERE id=?1", params![id, notes])? == 0 {
+            bail!("asset {id} not found");
+        }
+        index_assets(&self.conn, &[id])?;
+        Ok(())
+    }
+
+    /// Every asset (optionally of one project), oldest first, without paging.
+    pub fn list_all_assets(&self, project_id: Option<&str>) -> Result<Vec<AssetRow>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ASSET_COLUMNS} FROM assets WHERE deleted_at IS NULL AND (?1 IS NULL OR project_id=?1)
+             ORDER BY created_at ASC, id ASC"
+        ))?;
+        let rows = stmt.query_map(params![project_id], |r| row_to_asset(r).map(|a| self.resolve_asset(a)))?;
+        let mut assets = rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect assets")?;
+        self.fill_details(&mut assets)?;
+        Ok(assets)
+    }
+
+    pub fn set_asset_sha256(&self, id: &str, sha256: &str) -> Result<()> {
+        self.conn.execute("UPDATE assets SET sha256=?2 WHERE id=?1", params![id, sha256])?;
+        Ok(())
+    }
+
+    /// Number of asset rows sharing one file — with content-addressed storage
+    /// identical outputs point at the same path.
+    pub fn count_asset_file_refs(&self, file_path: &str) -> Result<i64> {
+        let (project_id, stored) = self.locate_file(file_path);
+        Ok(self.conn.query_row(
+            "SELECT COUNT(*) FROM assets WHERE file_path=?1 AND (?2 IS NULL OR project_id=?2)",
+            params![stored, project_id],
+            |r| r.get(0),
+        )?)
+    }
+
+    /// Like [`count_asset_file_refs`](Self::count_asset_file_refs), but only
+    /// rows that are not in the trash.
+    pub fn count_live_asset_file_refs(&self, file_path: &str) -> Result<i64> {
+        let (project_id, stored) = self.locate_file(file_path);
+        Ok(self.conn.query_row(
+            "SELECT COUNT(*) FROM assets WHERE file_path=?1 AND (?2 IS NULL OR project_id=?2) AND deleted_at IS NULL",
+            params![stored, project_id],
+            |r| r.get(0),
+        )?)
+    }
+
+    /// Point an asset row at a different file (it was moved or renamed).
+    pub fn relink_asset_file(&self, id: &str, file_path: &str, file_name: &str) -> Result<()> {
+        let project_id: Option<String> = self
+            .conn
+            .query_row("SELECT project_id FROM assets WHERE id=?1", params![id], |r| r.get(0))
+            .optional()?;
+        let Some(project_id) = project_id else {
+            return Ok(());
+        };
+        self.conn.execute(
+            "UPDATE assets SET file_path=?2, file_name=?3, dhash_failed=0, palette_checked=0 WHERE id=?1",
+            params![id, self.store_path(&project_id, file_path), file_name],
+        )?;
+        index_assets(&self.conn, &[id])?;
+        Ok(())
+    }
+
+    /// Move an asset row to another project and file, keeping its ID and
+    /// everything attached to it. The task that generated it follows when
+    /// this row is its recorded output, so the task's `assetPath` keeps
+    /// resolving and deleting the old project leaves the task alone.
+    pub fn update_asset_location(&self, asset: &AssetRow) -> Result<()> {
+        let tx = self.begin()?;
+        let old: Option<(String, String)> = tx
+            .query_row("SELECT project_id, file_path FROM assets WHERE id=?1", params![asset.id], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .optional()?;
+        let Some((old_project, old_path)) = old else {
+            bail!("asset {} does not exist", asset.id);
+        };
+        let store = |p: &str| self.store_path(&asset.project_id, p);
+        tx.execute(
+            "UPDATE assets SET project_id=?2, file_path=?3, file_name=?4, file_size=?5, sha256=?6,
+                               thumbnail_path=?7, poster_path=?8
+             WHERE id=?1",
+            params![
+                asset.id,
+                asset.project_id,
+                store(&asset.file_path),
+                asset.file_name,
+                asset.file_size,
+                asset.sha256,
+                asset.thumbnail_path.as_deref().map(store),
+                asset.poster_path.as_deref().map(store),
+            ],
+        )?;
+        if let Some(task_id) = &asset.task_id {
+            let output: Option<String> = tx
+                .query_row(
+                    "SELECT output FROM tasks WHERE id=?1 AND project_id=?2",
+                    params![task_id, old_project],
+                    |r| r.get(0),
+                )
+                .optional()?
+                .flatten();
+            let produced = output.as_deref().is_some_and(|o| {
+                serde_json::from_str::<serde_json::Value>(o).is_ok_and(|v| v["assetPath"] == old_path.as_str())
+            });
+            if let (true, Some(output)) = (produced, output) {
+                let moved = map_asset_path(&output, |_| store(&asset.file_path));
+                tx.execute(
+                    "UPDATE tasks SET project_id=?2, output=?3 WHERE id=?1",
+                    params![task_id, asset.project_id, moved],
+                )?;
+            }
+        }
+        index_assets(&tx, std::slice::from_ref(&asset.id))?;
+        tx.commit()
+    }
+
+    /// Task IDs recorded on asset rows in any project, trashed ones included:
+    /// a trashed asset can still be restored and needs its task.
+    pub fn asset_task_ids(&self) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare("SELECT DISTINCT task_id FROM assets WHERE task_id IS NOT NULL")?;
+        let ids = stmt.query_map([], |r| r.get(0))?.collect::<std::result::Result<_, _>>()?;
+        Ok(ids)
+    }
+
+    /// Asset rows for the given IDs; unknown and trashed IDs are skipped.
+    pub fn get_assets_by_ids(&self, ids: &[String]) -> Result<Vec<AssetRow>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {ASSET_COLUMNS} FROM assets WHERE id=?1 AND deleted_at IS NULL"))?;
+        let mut out = Vec::with_capacity(ids.len());
+        for id in ids {
+            let row = stmt.query_row(params![id], row_to_asset).optional()?;
+            if let Some(row) = row {
+                out.push(self.resolve_asset(row));
+            }
+        }
+        self.fill_details(&mut out)?;
+        Ok(out)
+    }
+
+    /// Delete asset rows for good, marking the tasks that generated them.
+    pub fn delete_asset_rows(&self, ids: &[String]) -> Result<usize> {
+        let tx = self.begin()?;
+        let mut deleted = 0;
+        for id in ids {
+            tx.execute(
+                "UPDATE tasks SET output_deleted=1 WHERE id=(SELECT task_id FROM assets WHERE id=?1)",
+                params![id],
+            )?;
+            deleted += tx.execute("DELETE FROM assets WHERE id=?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Tags and palettes, which live in their own tables. Fetched in batches
+    /// to stay under SQLite's bound-parameter limit.
+    fn fill_details(&self, assets: &mut [AssetRow]) -> Result<()> {
+        for batch in assets.chunks_mut(500) {
+            let ids: Vec<String> = batch.iter().map(|a| a.id.clone()).collect();
+            let placeholders = vec!["?"; ids.len()].join(",");
+
+            let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT at.asset_id, t.name FROM asset_tags at JOIN tags t ON t.id=at.tag_id
+                 WHERE at.asset_id IN ({placeholders}) ORDER BY t.name"
+            ))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(&ids), |r| Ok((r.get(0)?, r.get(1)?)))?;
+            for row in rows {
+                let (id, name): (String, String) = row?;
+                tags.entry(id).or_default().push(name);
+            }
+
+            let mut colors: HashMap<String, Vec<PaletteColor>> = HashMap::new();
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT asset_id, hex, weight FROM asset_colors WHERE asset_id IN ({placeholders}) ORDER BY rank"
+            ))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(&ids), |r| {
+                Ok((r.get::<_, String>(0)?, PaletteColor { hex: r.get(1)?, weight: r.get(2)? }))
+            })?;
+            for row in rows {
+                let (id, color) = row?;
+                colors.entry(id).or_default().push(color);
+            }
+
+            for asset in batch {
+                asset.tags = tags.get(&asset.id).cloned().unwrap_or_default();
+                asset.palette = colors.get(&asset.id).cloned().unwrap_or_default();
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace an asset's palette.
+    pub fn set_asset_palette(&self, id: &str, palette: &[PaletteColor]) -> Result<()> {
+        let tx = self.begin()?;
+        tx.execute("DELETE FROM asset_colors WHERE asset_id=?1", params![id])?;
+        tx.execute("UPDATE assets SET palette_checked=1 WHERE id=?1", params![id])?;
+        for (rank, color) in palette.iter().enumerate() {
+            let Some([l, a, b]) = color.lab() else { continue };
+            tx.execute(
+                "INSERT INTO asset_colors (asset_id, rank, hex, weight, l, a, b) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![id, rank as i64, color.hex, color.weight, l, a, b],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record that palette extraction ran for an asset but produced nothing.
+    pub fn mark_asset_palette_checked(&self, id: &str) -> Result<()> {
+        self.conn.execute("UPDATE assets SET palette_checked=1 WHERE id=?1", params![id])?;
+        Ok(())
+    }
+
+    /// Live image assets with no palette and no extraction attempt yet, as
+    /// `(id, file_path)`.
+    pub fn list_images_without_palette(&self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, project_id, file_path FROM assets
+             WHERE type='image' AND deleted_at IS NULL AND palette_checked=0
+             AND id NOT IN (SELECT asset_id FROM asset_colors)",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok((r.get(0)?, self.resolve_path(&r.get::<_, String>(1)?, &r.get::<_, String>(2)?)))
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect assets without palette")
+    }
+
+    // -------------------------------------------------------------------
+    // Asset organization — favorites, ratings, tags, collections
+    // -------------------------------------------------------------------
+
+    pub fn set_asset_favorite(&self, ids: &[String], favorite: bool) -> Result<usize> {
+        let tx = self.begin()?;
+        let mut n = 0;
+        for id in ids {
+            n += tx.execute("UPDATE assets SET favorite=?2 WHERE id=?1", params![id, favorite])?;
+        }
+        tx.commit()?;
+        Ok(n)
+    }
+
+    /// Set (1–5) or clear (`None`) the rating of each asset.
+    pub fn set_asset_rating(&self, ids: &[String], rating: Option<i32>) -> Result<usize> {
+        if let Some(r) = rating.filter(|r| !(1..=5).contains(r)) {
+            bail!("rating must be between 1 and 5, got {r}");
+        }
+        let tx = self.begin()?;
+        let mut n = 0;
+        for id in ids {
+            n += tx.execute("UPDATE assets SET rating=?2 WHERE id=?1", params![id, rating])?;
+        }
+        tx.commit()?;
+        Ok(n)
+    }
+
+    /// Attach tags to assets, creating tags that do not exist yet. Names are
+    /// trimmed and matched case-insensitively.
+    pub fn add_tags(&self, asset_ids: &[String], tags: &[String]) -> Result<()> {
+        let tx = self.begin()?;
+        for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+            for id in asset_ids {
+                tx.execute(
+                    "INSERT OR IGNORE INTO asset_tags (asset_id, tag_id)
+                     SELECT ?1, id FROM tags WHERE name=?2",
+                    params![id, tag],
+                )?;
+            }
+        }
+        index_assets(&tx, asset_ids)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Detach tags from assets. Tags left on no asset are deleted.
+    pub fn remove_tags(&self, asset_ids: &[String], tags: &[String]) -> Result<()> {
+        let tx = self.begin()?;
+        for tag in tags {
+            for id in asset_ids {
+                tx.execute(
+                    "DELETE FROM asset_tags WHERE asset_id=?1 AND tag_id=(SELECT id FROM tags WHERE name=?2)",
+                    params![id, tag.trim()],
+                )?;
+            }
+        }
+        tx.execute("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM asset_tags)", [])?;
+        index_assets(&tx, asset_ids)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn rename_tag(&self, from: &str, to: &str) -> Result<()> {
+        let to = to.trim();
+        if to.is_empty() {
+            bail!("tag name must not be empty");
+        }
+        let tx = self.begin()?;
+        let tag_id = |name: &str| -> Result<Option<i64>> {
+            Ok(tx.query_row("SELECT id FROM tags WHERE name=?1", params![name], |r| r.get(0)).optional()?)
+        };
+        let Some(from_id) = tag_id(from)? else {
+            bail!("tag \"{from}\" not found");
+        };
+        let tagged = tag_asset_ids(&tx, from_id)?;
+        match tag_id(to)? {
+            // Merge into the existing tag (unless only the case changes)
+            Some(to_id) if to_id != from_id => {
+                tx.execute(
+                    "INSERT OR IGNORE INTO asset_tags (asset_id, tag_id) SELECT asset_id, ?2 FROM asset_tags WHERE tag_id=?1",
+                    params![from_id, to_id],
+                )?;
+                tx.execute("DELETE FROM tags WHERE id=?1", params![from_id])?;
+            }
+            _ => {
+                tx.execute("UPDATE tags SET name=?2 WHERE id=?1", params![from_id, to])?;
+            }
+        }
+        index_assets(&tx, &tagged)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete_tag(&self, name: &str) -> Result<bool> {
+        let tx = self.begin()?;
+        let Some(id) = tx
+            .query_row("SELECT id FROM tags WHERE name=?1", params![name], |r| r.get(0))
+            .optional()?
+        else {
+            return Ok(false);
+        };
+        let tagged = tag_asset_ids(&tx, id)?;
+        tx.execute("DELETE FROM tags WHERE id=?1", params![id])?;
+        index_assets(&tx, &tagged)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// All tags with usage counts, most used first.
+    pub fn list_tags(&self) -> Result<Vec<Tag>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.name, COUNT(at.asset_id) AS n FROM tags t
+             LEFT JOIN asset_tags at ON at.tag_id=t.id
+             GROUP BY t.id ORDER BY n DESC, t.name",
+        )?;
+        let rows = stmt.query_map([], |r| Ok(Tag { name: r.get(0)?, asset_count: r.get(1)? }))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect tags")
+    }
+
+    pub fn create_collection(&self, name: &str, description: Option<&str>) -> Result<Collection> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("collection name must not be empty");
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        let id = uuid::Uuid::new_v4().to_string();
+        self.conn.execute(
+            "INSERT INTO collections (id, name, description, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![id, name, description, now],
+        )?;
+        Ok(Collection {
+            id,
+            name: name.to_string(),
+            description: description.map(String::from),
+            asset_count: 0,
+            created_at: now.clone(),
+            updated_at: now,
+        })
+    }
+
+    /// Rename and/or re-describe a collection. `description: Some("")` clears it.
+    pub fn update_collection(&self, id: &str, name: Option<&str>, description: Option<&str>) -> Result<()> {
+        if name.is_some_and(|n| n.trim().is_empty()) {
+            bail!("collection name must not be empty");
+        }
+        let updated = self.conn.execute(
+            "UPDATE collections SET
+                name = COALESCE(?2, name),
+                description = CASE WHEN ?3 IS NULL THEN description ELSE NULLIF(?3, '') END,
+                updated_at = ?4
+             WHERE id=?1",
+            params![id, name.map(str::trim), description, chrono::Utc::now().to_rfc3339()],
+        )?;
+        if updated == 0 {
+            bail!("collection {id} not found");
+        }
+        Ok(())
+    }
+
+    pub fn delete_collection(&self, id: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM collections WHERE id=?1", params![id])? > 0)
+    }
+
+    pub fn list_collections(&self) -> Result<Vec<Collection>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.id, c.name, c.description, COUNT(ca.asset_id), c.created_at, c.updated_at
+             FROM collections c LEFT JOIN collection_assets ca ON ca.collection_id=c.id
+             GROUP BY c.id ORDER BY c.name COLLATE NOCASE",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok(Collection {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                description: r.get(2)?,
+                asset_count: r.get(3)?,
+                created_at: r.get(4)?,
+                updated_at: r.get(5)?,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect collections")
+    }
+
+    /// Add assets to a collection; IDs that are not assets are skipped.
+    /// Returns how many were newly added.
+    pub fn add_to_collection(&self, collection_id: &str, asset_ids: &[String]) -> Result<usize> {
+        let tx = self.begin()?;
+        let exists: bool = tx
+            .query_row("SELECT 1 FROM collections WHERE id=?1", params![collection_id], |_| Ok(true))
+            .optional()?
+            .unwrap_or(false);
+        if !exists {
+            bail!("collection {collection_id} not found");
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut added = 0;
+        for id in asset_ids {
+            added += tx.execute(
+                "INSERT OR IGNORE INTO collection_assets (collection_id, asset_id, added_at)
+                 SELECT ?1, id, ?3 FROM assets WHERE id=?2 AND deleted_at IS NULL",
+                params![collection_id, id, now],
+            )?;
+        }
+        tx.execute("UPDATE collections SET updated_at=?2 WHERE id=?1", params![collection_id, now])?;
+        tx.commit()?;
+        Ok(added)
+    }
+
+    pub fn remove_from_collection(&self, collection_id: &str, asset_ids: &[String]) -> Result<usize> {
+        let tx = self.begin()?;
+        let mut removed = 0;
+        for id in asset_ids {
+            removed += tx.execute(
+                "DELETE FROM collection_assets WHERE collection_id=?1 AND asset_id=?2",
+                params![collection_id, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    // -------------------------------------------------------------------
+    // Lineage
+    // -------------------------------------------------------------------
+
+    /// Give `to` the same sources as `from` — a copied asset was derived
+    /// from whatever the original was. Returns the number of links added.
+    pub fn copy_asset_sources(&self, from: &str, to: &str) -> Result<usize> {
+        Ok(self.conn.execute(
+            "INSERT OR IGNORE INTO asset_links (source_id, asset_id, relation, task_id, created_at)
+             SELECT source_id, ?2, relation, task_id, created_at FROM asset_links
+             WHERE asset_id=?1 AND source_id<>?2",
+            params![from, to],
+        )?)
+    }
+
+    /// Record that `asset_id` was derived from `source_id`. Returns false when
+    /// the link already exists.
+    pub fn add_asset_link(
+        &self,
+        source_id: &str,
+        asset_id: &str,
+        relation: LinkRelation,
+        task_id: Option<&str>,
+    ) -> Result<bool> {
+        if source_id == asset_id {
+            bail!("asset {asset_id} cannot be derived from itself");
+        }
+        let n = self.conn.execute(
+            "INSERT OR IGNORE INTO asset_links (source_id, asset_id, relation, task_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![source_id, asset_id, relation, task_id, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// The ancestor and descendant graph of an asset, or `None` if it does
+    /// not exist.
+    pub fn get_asset_lineage(&self, asset_id: &str) -> Result<Option<AssetLineage>> {
+        let Some(asset) = self.get_assets_by_ids(&[asset_id.to_string()])?.pop() else {
+            return Ok(None);
+        };
+        let ancestors = self.lineage_walk(asset_id, "source_id", "asset_id")?;
+        let descendants = self.lineage_walk(asset_id, "asset_id", "source_id")?;
+
+        let mut ids: Vec<&str> = vec![asset_id];
+        ids.extend(ancestors.iter().chain(&descendants).map(|a| a.id.as_str()));
+        let placeholders = vec!["?"; ids.len()].join(",");
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT source_id, asset_id, relation, task_id, created_at FROM asset_links
+             WHERE source_id IN ({placeholders}) AND asset_id IN ({placeholders})
+             ORDER BY created_at, source_id"
+        ))?;
+        let bound: Vec<&str> = ids.iter().chain(&ids).copied().collect();
+        let links = stmt
+            .query_map(rusqlite::params_from_iter(bound), row_to_link)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Some(AssetLineage { asset, ancestors, descendants, links }))
+    }
+
+    /// Links whose two ends both belong to `project_id`.
+    pub fn list_asset_links(&self, project_id: &str) -> Result<Vec<AssetLink>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT l.source_id, l.asset_id, l.relation, l.task_id, l.created_at FROM asset_links l
+             JOIN assets s ON s.id = l.source_id JOIN assets d ON d.id = l.asset_id
+             WHERE s.project_id=?1 AND d.project_id=?1 AND s.deleted_at IS NULL AND d.deleted_at IS NULL
+             ORDER BY l.created_at, l.source_id",
+        )?;
+        let rows = stmt.query_map(params![project_id], row_to_link)?;
         rows.collect::<std::result::Result<Vec<_>, _>>()
-            .context("failed to collect assets")
+            .context("failed to collect asset links")
+    }
+
+    /// Assets reachable from `start` by following links from `from` to `to`,
+    /// ordered by distance. The depth cap keeps a linking cycle from recursing
+    /// forever.
+    fn lineage_walk(&self, start: &str, to: &str, from: &str) -> Result<Vec<AssetRow>> {
+        let sql = format!(
+            "WITH RECURSIVE walk(id, depth) AS (
+                SELECT ?1, 0
+                UNION
+                SELECT l.{to}, w.depth + 1 FROM asset_links l JOIN walk w ON l.{from} = w.id
+                WHERE w.depth < {MAX_LINEAGE_DEPTH}
+            )
+            SELECT {cols} FROM assets a
+            JOIN (SELECT id, MIN(depth) AS depth FROM walk WHERE id <> ?1 GROUP BY id) w ON w.id = a.id
+            WHERE a.deleted_at IS NULL
+            ORDER BY w.depth, a.created_at",
+            cols = prefixed_asset_columns("a"),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt
+            .query_map(params![start], |r| row_to_asset(r).map(|a| self.resolve_asset(a)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        self.fill_details(&mut rows)?;
+        Ok(rows)
     }
 
     pub fn get_asset_stats(&self) -> Result<AssetStats> {
-        let total: i64 = self.conn.query_row("SELECT COUNT(*) FROM assets", [], |r| r.get(0))?;
-        let images: i64 = self.conn.query_row("SELECT COUNT(*) FROM assets WHERE type='image'", [], |r| r.get(0))?;
-        let videos: i64 = self.conn.query_row("SELECT COUNT(*) FROM assets WHERE type='video'", [], |r| r.get(0))?;
-        let total_size: i64 = self.conn.query_row("SELECT COALESCE(SUM(file_size), 0) FROM assets", [], |r| r.get(0))?;
+        let total: i64 = self.conn.query_row("SELECT COUNT(*) FROM assets WHERE deleted_at IS NULL", [], |r| r.get(0))?;
+        let images: i64 = self.conn.query_row("SELECT COUNT(*) FROM assets WHERE type='image' AND deleted_at IS NULL", [], |r| r.get(0))?;
+        let videos: i64 = self.conn.query_row("SELECT COUNT(*) FROM assets WHERE type='video' AND deleted_at IS NULL", [], |r| r.get(0))?;
+        let total_size: i64 = self.conn.query_row("SELECT COALESCE(SUM(file_size), 0) FROM assets WHERE deleted_at IS NULL", [], |r| r.get(0))?;
         Ok(AssetStats { total, images, videos, total_size })
     }
 
@@ -295,7 +1916,7 @@ impl Db {
     /// Since projects are file-based, we check the filesystem — this method checks DB-side only.
     pub fn has_tasks_for_project(&self, project_id: &str) -> Result<bool> {
         let count: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM tasks WHERE project_id=?1",
+            "SELECT COUNT(*) FROM tasks WHERE project_id=?1 AND deleted_at IS NULL",
             params![project_id],
             |r| r.get(0),
         )?;
@@ -309,11 +1930,12 @@ impl Db {
         Ok(())
     }
 
-    /// Backfill asset rows from existing done tasks that don't already have an asset record.
+    /// Backfill asset rows from existing done tasks that don't already have an
+    /// asset record and whose asset was not deleted.
     pub fn backfill_assets_from_tasks(&self) -> Result<usize> {
         let mut stmt = self.conn.prepare(
             "SELECT id, project_id, type, input, output, created_at FROM tasks
-             WHERE status='done' AND output IS NOT NULL
+             WHERE status='done' AND output IS NOT NULL AND deleted_at IS NULL AND output_deleted=0
              AND id NOT IN (SELECT task_id FROM assets WHERE task_id IS NOT NULL)"
         )?;
 
@@ -326,11 +1948,13 @@ impl Db {
         let mut count = 0usize;
         for (task_id, project_id, task_type, input_json, output_json, created_at) in &tasks {
             let input: serde_json::Value = serde_json::from_str(input_json).unwrap_or_default();
-            let output: serde_json::Value = serde_json::from_str(output_json).unwrap_or_default();
+            let output_json = map_asset_path(output_json, |p| self.resolve_path(project_id, p));
+            let output: serde_json::Value = serde_json::from_str(&output_json).unwrap_or_default();
 
+            // A file gone from disk was deleted on purpose — don't resurrect its row
             let asset_path = match output["assetPath"].as_str() {
-                Some(p) => p,
-                None => continue,
+                Some(p) if Path::new(p).exists() => p,
+                _ => continue,
             };
             let file_name = std::path::Path::new(asset_path)
                 .file_name()
@@ -344,7 +1968,7 @@ impl Db {
             // Try to get file size from disk
             let file_size = std::fs::metadata(asset_path).ok().map(|m| m.len() as i64);
 
-            let asset = AssetRow {
+            let mut asset = AssetRow {
                 id: uuid::Uuid::new_v4().to_string(),
                 project_id: project_id.clone(),
                 task_id: Some(task_id.clone()),
@@ -358,7 +1982,25 @@ impl Db {
                 file_size,
                 source: "generated".to_string(),
                 created_at: created_at.clone(),
+                duration: None,
+                frame_rate: None,
+                codec: None,
+                thumbnail_path: None,
+                poster_path: None,
+                sha256: None,
+                favorite: false,
+                rating: None,
+                tags: Vec::new(),
+                palette: Vec::new(),
+                notes: None,
             };
+            // Prefer the real file over the dimensions recorded in the task output
+            if let Ok(info) = probe::probe_file(Path::new(asset_path)) {
+                asset.set_media_info(&info);
+            }
+            if let Ok((sha256, _)) = storage::sha256_file(Path::new(asset_path)) {
+                asset.sha256 = Some(sha256);
+            }
 
             self.insert_asset(&asset)?;
             count += 1;
@@ -367,6 +2009,292 @@ impl Db {
         Ok(count)
     }
 
+    // -------------------------------------------------------------------
+    // Perceptual hashes
+    // -------------------------------------------------------------------
+
+    pub fn set_asset_dhash(&self, id: &str, dhash: u64) -> Result<()> {
+        self.conn.execute("UPDATE assets SET dhash=?2 WHERE id=?1", params![id, dhash as i64])?;
+        Ok(())
+    }
+
+    /// Record that an asset's file could not be hashed.
+    pub fn mark_asset_dhash_failed(&self, id: &str) -> Result<()> {
+        self.conn.execute("UPDATE assets SET dhash_failed=1 WHERE id=?1", params![id])?;
+        Ok(())
+    }
+
+    /// Live image assets with no hash yet and no failed attempt, as
+    /// `(id, file_path)`.
+    pub fn list_unhashed_images(&self, project_id: Option<&str>) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, project_id, file_path FROM assets
+             WHERE type='image' AND dhash IS NULL AND dhash_failed=0 AND deleted_at IS NULL
+             AND (?1 IS NULL OR project_id=?1)",
+        )?;
+        let rows = stmt.query_map(params![project_id], |r| {
+            Ok((r.get(0)?, self.resolve_path(&r.get::<_, String>(1)?, &r.get::<_, String>(2)?)))
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect unhashed assets")
+    }
+
+    /// Hashes of live image assets, as `(id, dhash)`, oldest first.
+    pub fn list_asset_dhashes(&self, project_id: Option<&str>) -> Result<Vec<(String, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, dhash FROM assets
+             WHERE dhash IS NOT NULL AND deleted_at IS NULL AND (?1 IS NULL OR project_id=?1)
+             ORDER BY created_at, id",
+        )?;
+        let rows = stmt.query_map(params![project_id], |r| Ok((r.get(0)?, r.get::<_, i64>(1)? as u64)))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect asset hashes")
+    }
+
+    pub fn get_asset_dhash(&self, id: &str) -> Result<Option<u64>> {
+        Ok(self
+            .conn
+            .query_row("SELECT dhash FROM assets WHERE id=?1", params![id], |r| r.get::<_, Option<i64>>(0))
+            .optional()?
+            .flatten()
+            .map(|h| h as u64))
+    }
+
+    // -------------------------------------------------------------------
+    // Trash
+    //
+    // Trashed rows keep everything but get `deleted_at`; queries above skip
+    // them. A trashed project marks all its live rows with the project's
+    // `deleted_at`, so restoring it leaves separately trashed assets alone.
+    // -------------------------------------------------------------------
+
+    /// Mark assets deleted. Returns how many were live.
+    pub fn trash_asset_rows(&self, ids: &[String], deleted_at: &str) -> Result<usize> {
+        let tx = self.begin()?;
+        let mut n = 0;
+        for id in ids {
+            n += tx.execute(
+                "UPDATE assets SET deleted_at=?2 WHERE id=?1 AND deleted_at IS NULL",
+                params![id, deleted_at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(n)
+    }
+
+    pub fn restore_asset_rows(&self, ids: &[String]) -> Result<usize> {
+        let tx = self.begin()?;
+        let mut n = 0;
+        for id in ids {
+            n += tx.execute("UPDATE assets SET deleted_at=NULL WHERE id=?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(n)
+    }
+
+    /// Mark a project's live tasks (and, unless `tasks_only`, assets) deleted.
+    pub fn trash_project_rows(&self, project_id: &str, deleted_at: &str, tasks_only: bool) -> Result<()> {
+        let tx = self.begin()?;
+        tx.execute(
+            "UPDATE tasks SET deleted_at=?2 WHERE project_id=?1 AND deleted_at IS NULL",
+            params![project_id, deleted_at],
+        )?;
+        if !tasks_only {
+            tx.execute(
+                "UPDATE assets SET deleted_at=?2 WHERE project_id=?1 AND deleted_at IS NULL",
+                params![project_id, deleted_at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Undo [`trash_project_rows`](Self::trash_project_rows) for the rows
+    /// trashed at `deleted_at`.
+    pub fn restore_project_rows(&self, project_id: &str, deleted_at: &str) -> Result<()> {
+        let tx = self.begin()?;
+        for table in ["tasks", "assets"] {
+            tx.execute(
+                &format!("UPDATE {table} SET deleted_at=NULL WHERE project_id=?1 AND deleted_at=?2"),
+                params![project_id, deleted_at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Assets trashed on their own (not as part of a trashed project),
+    /// optionally only those deleted before `before`. Newest first.
+    pub fn list_trashed_assets(&self, before: Option<&str>) -> Result<Vec<TrashedAsset>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ASSET_COLUMNS}, deleted_at FROM assets
+             WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at < ?1)
+             AND project_id NOT IN (SELECT project_id FROM trashed_projects)
+             ORDER BY deleted_at DESC"
+        ))?;
+        let deleted_col = ASSET_COLUMNS.split(',').count();
+        let mut rows = stmt
+            .query_map(params![before], |row| {
+                Ok(TrashedAsset { asset: self.resolve_asset(row_to_asset(row)?), deleted_at: row.get(deleted_col)? })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect trashed assets")?;
+        for row in &mut rows {
+            self.fill_details(std::slice::from_mut(&mut row.asset))?;
+        }
+        Ok(rows)
+    }
+
+    /// Trashed rows among `ids`; unknown and live IDs are skipped.
+    pub fn get_trashed_assets(&self, ids: &[String]) -> Result<Vec<TrashedAsset>> {
+        let trashed = self.list_trashed_assets(None)?;
+        Ok(ids
+            .iter()
+            .filter_map(|id| trashed.iter().find(|t| &t.asset.id == id).cloned())
+            .collect())
+    }
+
+    /// Other trashed rows pointing at `file_path` (content-addressed storage
+    /// shares files).
+    pub fn trashed_asset_ids_for_file(&self, file_path: &str) -> Result<Vec<String>> {
+        let (project_id, stored) = self.locate_file(file_path);
+        let mut stmt = self.conn.prepare(
+            "SELECT id FROM assets WHERE file_path=?1 AND (?2 IS NULL OR project_id=?2)
+             AND deleted_at IS NOT NULL ORDER BY deleted_at",
+        )?;
+        let rows = stmt.query_map(params![stored, project_id], |r| r.get(0))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect trashed assets")
+    }
+
+    /// Tasks trashed on their own (a project deleted with `keepAssets`),
+    /// optionally only those deleted before `before`.
+    pub fn list_trashed_task_ids(&self, before: Option<&str>) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id FROM tasks WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at < ?1)
+             AND project_id NOT IN (SELECT project_id FROM trashed_projects)",
+        )?;
+        let rows = stmt.query_map(params![before], |r| r.get(0))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect trashed tasks")
+    }
+
+    /// Whether a row exists for `id`, trashed or not.
+    pub fn asset_row_exists(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row("SELECT 1 FROM assets WHERE id=?1", params![id], |_| Ok(()))
+            .optional()?
+            .is_some())
+    }
+
+    pub fn insert_trashed_project(&self, project: &TrashedProject) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO trashed_projects (project_id, name, trash_dir, deleted_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                project.project_id,
+                project.name,
+                relative_to(&self.root, &project.trash_dir).unwrap_or_else(|| project.trash_dir.clone()),
+                project.deleted_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Trashed projects, optionally only those deleted before `before`.
+    /// Newest first.
+    pub fn list_trashed_projects(&self, before: Option<&str>) -> Result<Vec<TrashedProject>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT project_id, name, trash_dir, deleted_at FROM trashed_projects
+             WHERE ?1 IS NULL OR deleted_at < ?1 ORDER BY deleted_at DESC",
+        )?;
+        let rows = stmt.query_map(params![before], |row| {
+            Ok(TrashedProject {
+                project_id: row.get(0)?,
+                name: row.get(1)?,
+                trash_dir: resolve_against(&self.root, &row.get::<_, String>(2)?),
+                deleted_at: row.get(3)?,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect trashed projects")
+    }
+
+    pub fn get_trashed_project(&self, project_id: &str) -> Result<Option<TrashedProject>> {
+        Ok(self.list_trashed_projects(None)?.into_iter().find(|p| p.project_id == project_id))
+    }
+
+    pub fn delete_trashed_project(&self, project_id: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM trashed_projects WHERE project_id=?1", params![project_id])? > 0)
+    }
+
+    // -------------------------------------------------------------------
+    // Task costs
+    // -------------------------------------------------------------------
+
+    /// Record the submission estimate for a task.
+    pub fn set_task_estimate(&self, task_id: &str, currency: &str, estimated: f64) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO task_costs (task_id, currency, estimated_cost, basis, estimated_at)
+             VALUES (?1, ?2, ?3, 'estimate', ?4)
+             ON CONFLICT(task_id) DO UPDATE SET
+                currency=excluded.currency, estimated_cost=excluded.estimated_cost,
+                estimated_at=excluded.estimated_at",
+            params![task_id, currency, estimated, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Record the settled cost of a finished task, keeping its estimate.
+    pub fn set_task_actual_cost(
+        &self,
+        task_id: &str,
+        currency: &str,
+        actual: f64,
+        usage_tokens: Option<i64>,
+        basis: &str,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO task_costs (task_id, currency, actual_cost, usage_tokens, basis, settled_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(task_id) DO UPDATE SET
+                currency=excluded.currency, actual_cost=excluded.actual_cost,
+                usage_tokens=excluded.usage_tokens, basis=excluded.basis,
+                settled_at=excluded.settled_at",
+            params![task_id, currency, actual, usage_tokens, basis, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Cost records of tasks created within `range`, oldest first.
+    pub fn list_task_costs(&self, range: &StatsRange) -> Result<Vec<TaskCostRow>> {
+        let (from, to) = range.bounds()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.project_id, t.type, t.status, json_extract(t.input, '$.model'), t.created_at,
+                    c.currency, c.estimated_cost, c.actual_cost, c.usage_tokens, c.basis
+             FROM task_costs c JOIN tasks t ON t.id=c.task_id
+             WHERE (?1 IS NULL OR t.created_at >= ?1) AND (?2 IS NULL OR t.created_at < ?2)
+             ORDER BY t.created_at ASC",
+        )?;
+        let rows = stmt.query_map(params![from, to], |row| {
+            Ok(TaskCostRow {
+                task_id: row.get(0)?,
+                project_id: row.get(1)?,
+                task_type: row.get(2)?,
+                status: row.get(3)?,
+                model: row.get(4)?,
+                created_at: row.get(5)?,
+                currency: row.get(6)?,
+                estimated_cost: row.get(7)?,
+                actual_cost: row.get(8)?,
+                usage_tokens: row.get(9)?,
+                basis: row.get(10)?,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect task costs")
+    }
+
     // -------------------------------------------------------------------
     // Usage stats
     // -------------------------------------------------------------------
@@ -400,10 +2328,32 @@ impl Db {
              FROM tasks ORDER BY created_at DESC LIMIT 20"
         )?;
         let recent_tasks = recent_stmt
-            .query_map([], row_to_task)?
+            .query_map([], |r| row_to_task(r).map(|t| self.resolve_task(t)))?
             .collect::<std::result::Result<Vec<_>, _>>()
             .context("failed to collect recent tasks")?;
 
+        let mut spend_stmt = self.conn.prepare(
+            "SELECT c.currency,
+                    COALESCE(SUM(c.estimated_cost), 0),
+                    COALESCE(SUM(CASE WHEN t.status='done' THEN c.actual_cost END), 0),
+                    COUNT(*),
+                    COALESCE(SUM(c.usage_tokens), 0)
+             FROM task_costs c JOIN tasks t ON t.id=c.task_id
+             GROUP BY c.currency ORDER BY c.currency",
+        )?;
+        let spend = spend_stmt
+            .query_map([], |row| {
+                Ok(SpendTotal {
+                    currency: row.get(0)?,
+                    estimated: row.get(1)?,
+                    actual: row.get(2)?,
+                    priced_tasks: row.get(3)?,
+                    usage_tokens: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect spend totals")?;
+
         Ok(UsageStats {
             total_tasks,
             images_generated,
@@ -412,8 +2362,141 @@ impl Db {
             failed,
             daily_counts,
             recent_tasks,
+            spend,
         })
     }
+
+    /// Task outcomes and asset totals created within `range`, grouped by
+    /// `group_by`. Groups are ordered by task count, then asset count.
+    pub fn get_stats_breakdown(&self, range: &StatsRange, group_by: StatsGroupBy) -> Result<StatsBreakdown> {
+        let (from, to) = range.bounds()?;
+        let window = "(?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2)";
+
+        let sql = format!(
+            "SELECT {key}, status, (julianday(updated_at) - julianday(created_at)) * 86400.0,
+                    c.currency, c.estimated_cost, c.actual_cost, c.usage_tokens
+             FROM tasks LEFT JOIN task_costs c ON c.task_id=tasks.id
+             WHERE {window}",
+            key = group_by.task_key(),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(params![from, to], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, TaskStatus>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    (row.get::<_, Option<f64>>(4)?, row.get::<_, Option<f64>>(5)?, row.get::<_, Option<i64>>(6)?),
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect task stats")?;
+
+        // Amounts in different currencies are never added together
+        type Group = (TaskBreakdown, Vec<f64>, BTreeMap<String, SpendTotal>);
+        let mut groups: BTreeMap<Option<String>, Group> = BTreeMap::new();
+        for (key, status, latency, currency, (estimated, actual, tokens)) in rows {
+            let (group, latencies, spend) = groups.entry(key.clone()).or_insert_with(|| {
+                let empty = TaskBreakdown {
+                    key,
+                    total: 0,
+                    succeeded: 0,
+                    failed: 0,
+                    cancelled: 0,
+                    in_progress: 0,
+                    success_ratio: None,
+                    median_latency_secs: None,
+                    p95_latency_secs: None,
+                    spend: Vec::new(),
+                };
+                (empty, Vec::new(), BTreeMap::new())
+            });
+            group.total += 1;
+            let mut cost = currency.map(|currency| {
+                let total = spend.entry(currency.clone()).or_insert_with(|| SpendTotal {
+                    currency,
+                    estimated: 0.0,
+                    actual: 0.0,
+                    priced_tasks: 0,
+                    usage_tokens: 0,
+                });
+                total.estimated += estimated.unwrap_or(0.0);
+                total.priced_tasks += 1;
+                total.usage_tokens += tokens.unwrap_or(0);
+                total
+            });
+            match status {
+                TaskStatus::Done => {
+                    group.succeeded += 1;
+                    if let Some(cost) = cost.as_mut() {
+                        cost.actual += actual.unwrap_or(0.0);
+                    }
+                    latencies.extend(latency.filter(|l| l.is_finite() && *l >= 0.0));
+                }
+                TaskStatus::Failed => group.failed += 1,
+                TaskStatus::Cancelled => group.cancelled += 1,
+                _ => group.in_progress += 1,
+            }
+        }
+        let mut tasks: Vec<TaskBreakdown> = groups
+            .into_values()
+            .map(|(mut group, mut latencies, spend)| {
+                group.spend = spend.into_values().collect();
+                let finished = group.succeeded + group.failed;
+                group.success_ratio = (finished > 0).then(|| group.succeeded as f64 / finished as f64);
+                latencies.sort_by(f64::total_cmp);
+                group.median_latency_secs = percentile(&latencies, 0.5);
+                group.p95_latency_secs = percentile(&latencies, 0.95);
+                group
+            })
+            .collect();
+        tasks.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.key.cmp(&b.key)));
+
+        // Content-addressed rows can share a file; its bytes count once per group
+        let sql = format!(
+            "SELECT key, COUNT(*), SUM(type = 'image'), SUM(type = 'video'),
+                    COALESCE(SUM(CASE WHEN first_ref THEN file_size END), 0)
+             FROM (SELECT {key} AS key, type, file_size,
+                          ROW_NUMBER() OVER (PARTITION BY {key}, project_id, file_path) = 1 AS first_ref
+                   FROM assets WHERE deleted_at IS NULL AND {window})
+             GROUP BY 1 ORDER BY 2 DESC, 1",
+            key = group_by.asset_key(),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let assets = stmt
+            .query_map(params![from, to], |row| {
+                Ok(AssetBreakdown {
+                    key: row.get(0)?,
+                    count: row.get(1)?,
+                    images: row.get(2)?,
+                    videos: row.get(3)?,
+                    total_size: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .context("failed to collect asset stats")?;
+
+        Ok(StatsBreakdown { group_by, range: range.clone(), tasks, assets })
+    }
+}
+
+/// Linearly interpolated percentile (`p` in 0..=1) of an ascending slice.
+fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = p.clamp(0.0, 1.0) * last as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64))
+}
+
+/// Lease timestamps use a fixed-width UTC format so SQLite can compare them as strings.
+fn lease_now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn lease_deadline(ttl: std::time::Duration) -> String {
+    let ttl = chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::seconds(30));
+    (chrono::Utc::now() + ttl).to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
 }
 
 fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<TaskRow> {
@@ -431,6 +2514,172 @@ fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<TaskRow> {
     })
 }
 
+/// Append `AND …` clauses for `filter` to `sql`, with asset columns
+/// qualified by `prefix` (e.g. `"a."` when joined).
+fn push_asset_filter(filter: &AssetFilter, prefix: &str, sql: &mut String, params: &mut Vec<Box<dyn ToSql>>) -> Result<()> {
+    sql.push_str(&format!(" AND {prefix}deleted_at IS NULL"));
+    if let Some(pid) = &filter.project_id {
+        params.push(Box::new(pid.clone()));
+        sql.push_str(&format!(" AND {prefix}project_id=?{}", params.len()));
+    }
+    if let Some(atype) = &filter.asset_type {
+        params.push(Box::new(atype.clone()));
+        sql.push_str(&format!(" AND {prefix}type=?{}", params.len()));
+    }
+    if let Some(q) = filter.query.as_deref().and_then(search::match_query) {
+        params.push(Box::new(q));
+        sql.push_str(&format!(
+            " AND {prefix}rowid IN (SELECT rowid FROM assets_fts WHERE assets_fts MATCH ?{})",
+            params.len()
+        ));
+    }
+    for tag in &filter.tags {
+        params.push(Box::new(tag.clone()));
+        sql.push_str(&format!(
+            " AND {prefix}id IN (SELECT at.asset_id FROM asset_tags at JOIN tags t ON t.id=at.tag_id WHERE t.name=?{})",
+            params.len()
+        ));
+    }
+    if let Some(cid) = &filter.collection_id {
+        params.push(Box::new(cid.clone()));
+        sql.push_str(&format!(
+            " AND {prefix}id IN (SELECT asset_id FROM collection_assets WHERE collection_id=?{})",
+            params.len()
+        ));
+    }
+    if let Some(fav) = filter.favorite {
+        params.push(Box::new(fav));
+        sql.push_str(&format!(" AND {prefix}favorite=?{}", params.len()));
+    }
+    if let Some(min) = filter.min_rating {
+        params.push(Box::new(min));
+        sql.push_str(&format!(" AND {prefix}rating>=?{}", params.len()));
+    }
+    if let Some(color) = &filter.color {
+        let rgb = palette::parse_hex(color).with_context(|| format!("invalid color \"{color}\", expected #rrggbb"))?;
+        let [l, a, b] = palette::rgb_to_lab(rgb);
+        let max = filter.color_distance.unwrap_or(palette::DEFAULT_COLOR_DISTANCE);
+        let n = params.len();
+        params.extend([Box::new(l) as Box<dyn ToSql>, Box::new(a), Box::new(b), Box::new(max * max)]);
+        sql.push_str(&format!(
+            " AND {prefix}id IN (SELECT asset_id FROM asset_colors
+               WHERE (l-?{l})*(l-?{l}) + (a-?{a})*(a-?{a}) + (b-?{b})*(b-?{b}) <= ?{max})",
+            l = n + 1,
+            a = n + 2,
+            b = n + 3,
+            max = n + 4
+        ));
+    }
+    Ok(())
+}
+
+/// Rewrite the full-text index rows of `ids` from their current prompt,
+/// notes, tags and file name, segmented for CJK (see crate::search).
+/// Unknown IDs are skipped.
+fn index_assets<S: AsRef<str>>(conn: &Connection, ids: &[S]) -> Result<()> {
+    let mut source = conn.prepare_cached(
+        "SELECT a.rowid, a.prompt, a.notes,
+                (SELECT group_concat(t.name, ' ') FROM asset_tags at JOIN tags t ON t.id=at.tag_id WHERE at.asset_id=a.id),
+                a.file_name
+         FROM assets a WHERE a.id=?1",
+    )?;
+    let mut delete = conn.prepare_cached("DELETE FROM assets_fts WHERE rowid=?1")?;
+    let mut insert =
+        conn.prepare_cached("INSERT INTO assets_fts (rowid, prompt, notes, tags, file_name) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+    let segment = |text: Option<String>| text.map(|t| search::segment(&t));
+    for id in ids {
+        let row = source
+            .query_row(params![id.as_ref()], |r| {
+                Ok((r.get::<_, i64>(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))
+            })
+            .optional()?;
+        let Some((rowid, prompt, notes, tags, file_name)) = row else { continue };
+        delete.execute(params![rowid])?;
+        insert.execute(params![rowid, segment(prompt), segment(notes), segment(tags), segment(file_name)])?;
+    }
+    Ok(())
+}
+
+/// IDs of the assets carrying tag `tag_id`.
+fn tag_asset_ids(conn: &Connection, tag_id: i64) -> Result<Vec<String>> {
+    Ok(conn
+        .prepare("SELECT asset_id FROM asset_tags WHERE tag_id=?1")?
+        .query_map(params![tag_id], |r| r.get(0))?
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+/// `path` relative to `dir`, `/`-separated. `None` unless it is inside.
+fn relative_to(dir: &Path, path: &str) -> Option<String> {
+    let rest = Path::new(path).strip_prefix(dir).ok()?;
+    let parts: Vec<_> = rest.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// A stored path as an absolute one; absolute paths are returned as is.
+fn resolve_against(dir: &Path, stored: &str) -> String {
+    let path = Path::new(stored);
+    if stored.is_empty() || path.is_absolute() || path.has_root() {
+        return stored.to_string();
+    }
+    stored
+        .split('/')
+        .fold(dir.to_path_buf(), |p, part| p.join(part))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// What follows the last `projects/{project_id}/` of an absolute path,
+/// `/`-separated — wherever the data directory was when it was written.
+fn legacy_project_relative(path: &str, project_id: &str) -> Option<String> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return None;
+    }
+    let parts: Vec<String> = path.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    let at = parts.windows(2).rposition(|w| w[0] == datadir::PROJECTS_DIR && w[1] == project_id)?;
+    let rest = &parts[at + 2..];
+    (!rest.is_empty()).then(|| rest.join("/"))
+}
+
+/// Task output JSON with `f` applied to its `assetPath`. Output without one
+/// is returned unchanged.
+fn map_asset_path(output: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let Ok(mut value) = serde_json::from_str::<serde_json::Value>(output) else {
+        return output.to_string();
+    };
+    let Some(path) = value.get("assetPath").and_then(|p| p.as_str()).map(&mut f) else {
+        return output.to_string();
+    };
+    value["assetPath"] = path.into();
+    value.to_string()
+}
+
+/// How many generations [`Db::get_asset_lineage`] follows in each direction.
+const MAX_LINEAGE_DEPTH: usize = 64;
+
+/// Column list matching `row_to_asset`.
+const ASSET_COLUMNS: &str = "id, project_id, task_id, type, file_path, file_name, prompt, model, width, height, \
+     file_size, source, created_at, duration, frame_rate, codec, thumbnail_path, poster_path, sha256, favorite, rating, notes";
+
+fn row_to_link(r: &rusqlite::Row) -> rusqlite::Result<AssetLink> {
+    Ok(AssetLink {
+        source_id: r.get(0)?,
+        asset_id: r.get(1)?,
+        relation: r.get(2)?,
+        task_id: r.get(3)?,
+        created_at: r.get(4)?,
+    })
+}
+
+/// [`ASSET_COLUMNS`] qualified with a table alias, for joins.
+fn prefixed_asset_columns(alias: &str) -> String {
+    ASSET_COLUMNS
+        .split(',')
+        .map(|c| format!("{alias}.{}", c.trim()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 fn row_to_asset(row: &rusqlite::Row) -> rusqlite::Result<AssetRow> {
     Ok(AssetRow {
         id: row.get(0)?,
@@ -446,5 +2695,433 @@ fn row_to_asset(row: &rusqlite::Row) -> rusqlite::Result<AssetRow> {
         file_size: row.get(10)?,
         source: row.get(11)?,
         created_at: row.get(12)?,
+        duration: row.get(13)?,
+        frame_rate: row.get(14)?,
+        codec: row.get(15)?,
+        thumbnail_path: row.get(16)?,
+        poster_path: row.get(17)?,
+        sha256: row.get(18)?,
+        favorite: row.get(19)?,
+        rating: row.get(20)?,
+        tags: Vec::new(),
+        palette: Vec::new(),
+        notes: row.get(21)?,
     })
 }
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("seedcanvas-db-{}.db", uuid::Uuid::new_v4()))
+    }
+
+    fn task(id: &str, status: TaskStatus) -> TaskRow {
+        let now = chrono::Utc::now().to_rfc3339();
+        TaskRow {
+            id: id.into(),
+            project_id: "p1".into(),
+            task_type: TaskType::Video,
+            status,
+            input: "{}".into(),
+            output: None,
+            ark_task_id: None,
+            error: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn status_strings_round_trip_through_serde_and_from_str() {
+        for status in TaskStatus::ALL {
+            let json = serde_json::to_value(status).unwrap();
+            assert_eq!(json, status.as_str());
+            assert_eq!(status.as_str().parse::<TaskStatus>().unwrap(), status);
+        }
+        assert_eq!(serde_json::to_value(TaskStatus::QueuedRemote).unwrap(), "queued_remote");
+        assert!("finished".parse::<TaskStatus>().is_err());
+    }
+
+    #[test]
+    fn terminal_states_have_no_exits() {
+        for from in TaskStatus::ALL {
+            let exits = TaskStatus::ALL.iter().filter(|to| from.can_transition_to(**to)).count();
+            assert_eq!(from.is_terminal(), exits == 0, "{from}");
+        }
+        assert!(TaskStatus::Pending.can_transition_to(TaskStatus::Running));
+        assert!(!TaskStatus::Pending.can_transition_to(TaskStatus::Done));
+        assert!(!TaskStatus::Downloading.can_transition_to(TaskStatus::Cancelled));
+    }
+
+    #[test]
+    fn update_task_rejects_illegal_transitions() {
+        let path = temp_db_path();
+        let db = Db::open(&path).unwrap();
+        db.insert_task(&task("t1", TaskStatus::Pending)).unwrap();
+
+        db.update_task("t1", TaskStatus::Running, None, None, None).unwrap();
+        db.update_task("t1", TaskStatus::QueuedRemote, None, Some("cgt-1"), None).unwrap();
+        db.update_task("t1", TaskStatus::Downloading, None, Some("cgt-1"), None).unwrap();
+        db.update_task("t1", TaskStatus::Done, Some("{}"), Some("cgt-1"), None).unwrap();
+
+        let err = db.update_task("t1", TaskStatus::Running, None, None, None).unwrap_err();
+        assert!(err.to_string().contains("done → running"), "{err}");
+        assert_eq!(db.get_task("t1").unwrap().unwrap().status, TaskStatus::Done);
+        assert!(db.update_task("missing", TaskStatus::Running, None, None, None).is_err());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn check_constraint_rejects_unknown_values_and_legacy_tables_are_upgraded() {
+        let path = temp_db_path();
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE tasks (
+                    id TEXT PRIMARY KEY, project_id TEXT NOT NULL, type TEXT NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'pending', input TEXT NOT NULL, output TEXT,
+                    ark_task_id TEXT, error TEXT, created_at TEXT NOT NULL, updated_at TEXT NOT NULL
+                 );
+                 INSERT INTO tasks VALUES ('ok', 'p1', 'image', 'done', '{}', NULL, NULL, NULL, 't', 't');
+                 INSERT INTO tasks VALUES ('odd', 'p1', 'video', 'stuck', '{}', NULL, NULL, NULL, 't', 't');
+                 INSERT INTO tasks VALUES ('bad', 'p1', 'audio', 'done', '{}', NULL, NULL, NULL, 't', 't');",
+            )
+            .unwrap();
+        }
+
+        let db = Db::open(&path).unwrap();
+        assert_eq!(db.get_task("ok").unwrap().unwrap().status, TaskStatus::Done);
+        assert_eq!(db.get_task("odd").unwrap().unwrap().status, TaskStatus::Failed);
+        assert!(db.get_task("bad").unwrap().is_none());
+
+        let raw = db.conn.execute(
+            "INSERT INTO tasks (id, project_id, type, status, input, created_at, updated_at)
+             VALUES ('x', 'p1', 'image', 'finished', '{}', 't', 't')",
+            [],
+        );
+        assert!(raw.is_err());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn asset(id: &str, created_at: &str) -> AssetRow {
+        AssetRow {
+            id: id.into(),
+            project_id: "p1".into(),
+            asset_type: "image".into(),
+            file_path: format!("/tmp/{id}.png"),
+            file_name: format!("{id}.png"),
+            source: "generated".into(),
+            created_at: created_at.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn transactions_nest_and_roll_back_together() {
+        let path = temp_db_path();
+        let db = Db::open(&path).unwrap();
+        let mut tagged = asset("a", "2026-01-01");
+        tagged.tags = vec!["keep".into()];
+
+        // insert_asset opens its own transaction for the tags inside ours
+        let tx = db.begin().unwrap();
+        db.insert_asset(&tagged).unwrap();
+        drop(tx);
+        assert!(db.get_assets_by_ids(&["a".to_string()]).unwrap().is_empty());
+        assert!(db.list_tags().unwrap().is_empty());
+
+        let tx = db.begin().unwrap();
+        db.insert_asset(&tagged).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(db.get_assets_by_ids(&["a".to_string()]).unwrap()[0].tags, vec!["keep".to_string()]);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn paths_are_stored_relative_and_legacy_absolute_paths_converted() {
+        let root = std::env::temp_dir().join(format!("seedcanvas-paths-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("test.db");
+        let stored = |db: &Db, id: &str| -> String {
+            db.conn.query_row("SELECT file_path FROM assets WHERE id=?1", params![id], |r| r.get(0)).unwrap()
+        };
+
+        // Rows written by an older version, from a data directory since moved
+        {
+            let db = Db::open(&path).unwrap();
+            let mut legacy = asset("old", "2026-01-01");
+            legacy.file_path = "/Volumes/Old/com.seedkit.canvas/projects/p1/assets/old.png".into();
+            legacy.thumbnail_path = Some("/Volumes/Old/com.seedkit.canvas/projects/p1/thumbnails/old.jpg".into());
+            db.insert_asset(&legacy).unwrap();
+            let mut outside = asset("outside", "2026-01-02");
+            outside.file_path = "/tmp/outside.png".into();
+            db.insert_asset(&outside).unwrap();
+            let mut t = task("t1", TaskStatus::Done);
+            t.output = Some(r#"{"assetPath":"/Volumes/Old/com.seedkit.canvas/projects/p1/assets/old.png","width":1}"#.into());
+            db.insert_task(&t).unwrap();
+            assert!(stored(&db, "old").starts_with("/Volumes"));
+        }
+
+        let db = Db::open(&path).unwrap();
+        let project_dir = root.join("projects").join("p1");
+        assert_eq!(stored(&db, "old"), "assets/old.png");
+        assert_eq!(stored(&db, "outside"), "/tmp/outside.png");
+        let old = db.get_assets_by_ids(&["old".into()]).unwrap().pop().unwrap();
+        assert_eq!(Path::new(&old.file_path), project_dir.join("assets").join("old.png"));
+        assert_eq!(Path::new(old.thumbnail_path.as_deref().unwrap()), project_dir.join("thumbnails").join("old.jpg"));
+        let output: serde_json::Value = serde_json::from_str(&db.get_task("t1").unwrap().unwrap().output.unwrap()).unwrap();
+        assert_eq!(Path::new(output["assetPath"].as_str().unwrap()), project_dir.join("assets").join("old.png"));
+        assert_eq!(output["width"], 1);
+
+        // New rows are stored relative; lookups by absolute path still match
+        let mut fresh = asset("new", "2026-01-03");
+        fresh.file_path = project_dir.join("assets").join("new.png").to_string_lossy().to_string();
+        db.insert_asset(&fresh).unwrap();
+        assert_eq!(stored(&db, "new"), "assets/new.png");
+        assert_eq!(db.count_asset_file_refs(&fresh.file_path).unwrap(), 1);
+        assert_eq!(db.count_asset_file_refs(&old.file_path).unwrap(), 1);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn tags_collections_and_ratings_filter_assets() {
+        let path = temp_db_path();
+        let db = Db::open(&path).unwrap();
+        for (i, id) in ["a", "b", "c"].iter().enumerate() {
+            db.insert_asset(&asset(id, &format!("2026-01-0{}", i + 1))).unwrap();
+        }
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let listed = |f: &AssetFilter| {
+            db.list_assets(f, 50, 0).unwrap().into_iter().map(|a| a.id).collect::<Vec<_>>()
+        };
+
+        db.add_tags(&ids(&["a", "b"]), &ids(&["hero-shot-candidates", " night "])).unwrap();
+        db.add_tags(&ids(&["c"]), &ids(&["Night"])).unwrap();
+        db.set_asset_favorite(&ids(&["b"]), true).unwrap();
+        db.set_asset_rating(&ids(&["a", "c"]), Some(4)).unwrap();
+        assert!(db.set_asset_rating(&ids(&["a"]), Some(6)).is_err());
+        let col = db.create_collection("Moodboard", None).unwrap();
+        assert_eq!(db.add_to_collection(&col.id, &ids(&["c", "a", "ghost"])).unwrap(), 2);
+
+        let tagged = AssetFilter { tags: ids(&["night", "hero-shot-candidates"]), ..Default::default() };
+        assert_eq!(listed(&tagged), ids(&["b", "a"]));
+        assert_eq!(listed(&AssetFilter { favorite: Some(true), ..Default::default() }), ids(&["b"]));
+        assert_eq!(listed(&AssetFilter { min_rating: Some(4), ..Default::default() }), ids(&["c", "a"]));
+        let in_col = AssetFilter { collection_id: Some(col.id.clone()), tags: ids(&["hero-shot-candidates"]), ..Default::default() };
+        assert_eq!(listed(&in_col), ids(&["a"]));
+
+        let color = |hex: &str, weight| PaletteColor { hex: hex.into(), weight };
+        db.set_asset_palette("a", &[color("#d02020", 0.7), color("#1030c0", 0.3)]).unwrap();
+        db.set_asset_palette("b", &[color("#20a040", 1.0)]).unwrap();
+        let near = |hex: &str, distance| AssetFilter { color: Some(hex.into()), color_distance: distance, ..Default::default() };
+        assert_eq!(listed(&near("#cc2222", None)), ids(&["a"]));
+        assert_eq!(listed(&near("#2244cc", Some(10.0))), ids(&["a"]));
+        assert!(listed(&near("#ffff00", None)).is_empty());
+        assert!(db.list_assets(&near("red", None), 50, 0).is_err());
+
+        let a = &db.get_assets_by_ids(&ids(&["a"])).unwrap()[0];
+        assert_eq!(a.tags, ids(&["hero-shot-candidates", "night"]));
+        assert_eq!(a.palette[0].hex, "#d02020");
+        let bc = db.get_assets_by_ids(&ids(&["b", "c"])).unwrap();
+        assert_eq!((bc[0].tags.len(), bc[0].palette.len()), (2, 1));
+        assert_eq!((bc[1].tags.clone(), bc[1].palette.len()), (ids(&["night"]), 0));
+        assert_eq!(db.list_images_without_palette().unwrap().len(), 1);
+        db.mark_asset_palette_checked("c").unwrap();
+        assert!(db.list_images_without_palette().unwrap().is_empty());
+        assert_eq!(db.list_tags().unwrap()[0].asset_count, 3);
+        assert!(db.rename_tag("missing", "night").is_err());
+        assert_eq!(db.list_tags().unwrap()[0].asset_count, 3);
+
+        // Deleting an asset drops its links; unused tags go with remove_tags
+        db.delete_asset_rows(&ids(&["a"])).unwrap();
+        assert_eq!(db.list_collections().unwrap()[0].asset_count, 1);
+        db.remove_tags(&ids(&["b"]), &ids(&["hero-shot-candidates"])).unwrap();
+        assert_eq!(db.list_tags().unwrap().len(), 1);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn search_index_follows_prompts_notes_and_tags() {
+        let path = temp_db_path();
+        let db = Db::open(&path).unwrap();
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let hits = |q: &str| {
+            db.search_assets(q, &AssetFilter::default(), 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|h| h.asset.id)
+                .collect::<Vec<_>>()
+        };
+
+        let mut a = asset("a", "2026-01-01");
+        a.prompt = Some("城市夜景，霓虹灯倒映在雨后的街道".into());
+        let mut b = asset("b", "2026-01-02");
+        b.prompt = Some("Golden hour over a quiet beach".into());
+        let mut c = asset("c", "2026-01-03");
+        c.prompt = Some("beach volleyball at noon".into());
+        for x in [&a, &b, &c] {
+            db.insert_asset(x).unwrap();
+        }
+
+        assert_eq!(hits("夜景"), ids(&["a"]));
+        assert!(hits("景夜").is_empty());
+        assert_eq!(hits("\"golden hour\""), ids(&["b"]));
+        assert_eq!(hits("volley"), ids(&["c"]));
+        assert_eq!(hits("beach -volleyball"), ids(&["b"]));
+
+        let snippet = &db.search_assets("霓虹", &AssetFilter::default(), 1, 0).unwrap()[0].snippet;
+        assert!(snippet.contains("<mark>霓虹</mark>"), "{snippet}");
+
+        // Tags and notes are indexed as they change
+        db.add_tags(&ids(&["c"]), &ids(&["hero-shot-candidates"])).unwrap();
+        db.set_asset_notes("b", Some("client favourite")).unwrap();
+        assert_eq!(hits("hero"), ids(&["c"]));
+        assert_eq!(hits("client"), ids(&["b"]));
+        db.rename_tag("hero-shot-candidates", "finalist").unwrap();
+        assert_eq!(hits("finalist"), ids(&["c"]));
+        assert!(hits("hero").is_empty());
+
+        let filtered = AssetFilter { query: Some("beach".into()), ..Default::default() };
+        assert_eq!(db.list_assets(&filtered, 10, 0).unwrap().len(), 2);
+
+        db.delete_asset_rows(&ids(&["a"])).unwrap();
+        assert!(hits("夜景").is_empty());
+        db.delete_tag("finalist").unwrap();
+        assert!(hits("finalist").is_empty());
+
+        // Other connections need no custom SQL functions to write the tables
+        let other = Connection::open(&path).unwrap();
+        other.execute("UPDATE assets SET notes='x' WHERE id='b'", []).unwrap();
+        other.execute("DELETE FROM assets WHERE id='c'", []).unwrap();
+        assert!(hits("volley").is_empty());
+        drop(other);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn lineage_walks_both_directions_and_survives_cycles() {
+        let path = temp_db_path();
+        let db = Db::open(&path).unwrap();
+        for (i, id) in ["ref", "still", "retry", "video", "other"].iter().enumerate() {
+            db.insert_asset(&asset(id, &format!("2026-01-0{}", i + 1))).unwrap();
+        }
+        // ref → still → retry → video (first frame)
+        db.add_asset_link("ref", "still", LinkRelation::ReferenceOf, Some("t1")).unwrap();
+        db.add_asset_link("still", "retry", LinkRelation::RetryOf, Some("t2")).unwrap();
+        db.add_asset_link("retry", "video", LinkRelation::FirstFrameOf, Some("t3")).unwrap();
+        assert!(!db.add_asset_link("retry", "video", LinkRelation::FirstFrameOf, None).unwrap());
+        assert!(db.add_asset_link("video", "video", LinkRelation::VariantOf, None).is_err());
+
+        let ids = |rows: &[AssetRow]| rows.iter().map(|a| a.id.clone()).collect::<Vec<_>>();
+        let video = db.get_asset_lineage("video").unwrap().unwrap();
+        assert_eq!(ids(&video.ancestors), ["retry", "still", "ref"]);
+        assert!(video.descendants.is_empty());
+        assert_eq!(video.links.len(), 3);
+        assert_eq!(video.links[2].relation, LinkRelation::FirstFrameOf);
+
+        let still = db.get_asset_lineage("still").unwrap().unwrap();
+        assert_eq!(ids(&still.ancestors), ["ref"]);
+        assert_eq!(ids(&still.descendants), ["retry", "video"]);
+        assert!(db.get_asset_lineage("other").unwrap().unwrap().links.is_empty());
+        assert!(db.get_asset_lineage("ghost").unwrap().is_none());
+
+        // A cycle ends at the depth cap instead of recursing forever
+        db.add_asset_link("video", "ref", LinkRelation::VariantOf, None).unwrap();
+        assert_eq!(db.get_asset_lineage("ref").unwrap().unwrap().ancestors.len(), 3);
+
+        // Deleting an asset removes its links
+        db.delete_asset_rows(&["retry".to_string()]).unwrap();
+        let still = db.get_asset_lineage("still").unwrap().unwrap();
+        assert!(still.descendants.is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn stats_breakdown_groups_ranges_and_ranks_latency() {
+        let path = temp_db_path();
+        let db = Db::open(&path).unwrap();
+        let at = |day: u32, secs: u32| format!("2026-03-{day:02}T10:00:{secs:02}.123456789+00:00");
+        let add = |id: &str, project: &str, model: Option<&str>, status, day, secs| {
+            let mut t = task(id, status);
+            t.project_id = project.into();
+            t.input = match model {
+                Some(m) => serde_json::json!({ "prompt": "x", "model": m }).to_string(),
+                None => r#"{"prompt":"x"}"#.into(),
+            };
+            t.created_at = at(day, 0);
+            t.updated_at = at(day, secs);
+            db.insert_task(&t).unwrap();
+        };
+        for (i, secs) in [10, 20, 30, 40].into_iter().enumerate() {
+            add(&format!("a{i}"), "p1", Some("m1"), TaskStatus::Done, 2, secs);
+        }
+        add("a4", "p1", Some("m1"), TaskStatus::Failed, 2, 5);
+        add("b0", "p2", None, TaskStatus::Done, 3, 50);
+        add("b1", "p2", None, TaskStatus::Pending, 3, 0);
+        add("old", "p2", Some("m1"), TaskStatus::Failed, 1, 0);
+        let mut big = asset("big", &at(2, 0));
+        big.file_size = Some(1000);
+        db.insert_asset(&big).unwrap();
+        // Same content-addressed file as `big`
+        let twin = AssetRow { id: "twin".into(), ..big.clone() };
+        db.insert_asset(&twin).unwrap();
+
+        db.set_task_estimate("a0", "CNY", 1.5).unwrap();
+        db.set_task_actual_cost("a0", "CNY", 1.25, Some(1000), "tokens").unwrap();
+        db.set_task_estimate("a4", "CNY", 1.5).unwrap();
+        db.set_task_estimate("a1", "USD", 0.5).unwrap();
+
+        let range = StatsRange { from: Some("2026-03-02".into()), to: Some("2026-03-04".into()) };
+        let stats = db.get_stats_breakdown(&range, StatsGroupBy::Model).unwrap();
+        assert_eq!(stats.tasks.len(), 2);
+        let m1 = &stats.tasks[0];
+        assert_eq!((m1.key.as_deref(), m1.total, m1.succeeded, m1.failed), (Some("m1"), 5, 4, 1));
+        assert_eq!(m1.success_ratio, Some(0.8));
+        let spent: Vec<_> = m1.spend.iter().map(|t| (t.currency.as_str(), t.estimated, t.actual, t.priced_tasks)).collect();
+        assert_eq!(spent, vec![("CNY", 3.0, 1.25, 2), ("USD", 0.5, 0.0, 1)]);
+        assert!((m1.median_latency_secs.unwrap() - 25.0).abs() < 1e-3);
+        assert!((m1.p95_latency_secs.unwrap() - 38.5).abs() < 1e-3);
+        let unnamed = &stats.tasks[1];
+        assert_eq!((unnamed.key.as_deref(), unnamed.in_progress), (None, 1));
+        assert_eq!(unnamed.success_ratio, Some(1.0));
+
+        let by_project = db.get_stats_breakdown(&StatsRange::default(), StatsGroupBy::Project).unwrap();
+        let p2 = by_project.tasks.iter().find(|g| g.key.as_deref() == Some("p2")).unwrap();
+        assert_eq!(p2.total, 3);
+        assert_eq!((by_project.assets[0].count, by_project.assets[0].total_size), (2, 1000));
+
+        let spend = db.get_usage_stats().unwrap().spend;
+        assert_eq!((spend[0].estimated, spend[0].actual, spend[0].priced_tasks), (3.0, 1.25, 2));
+        let costs = db.list_task_costs(&range).unwrap();
+        let a0 = costs.iter().find(|c| c.task_id == "a0").unwrap();
+        assert_eq!(a0.basis.as_deref(), Some("tokens"));
+        db.delete_task("a4").unwrap();
+        assert_eq!(db.list_task_costs(&range).unwrap().len(), 2);
+
+        let bad = StatsRange { from: Some("March".into()), to: None };
+        assert!(db.get_stats_breakdown(&bad, StatsGroupBy::Type).is_err());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,474 @@
+//! Batch export of assets into a plain folder, under readable names.
+//!
+//! Unlike a project bundle (see `bundle`), this is for handing files to
+//! people: each asset is copied to `dest` under a name rendered from a
+//! template, optionally with a JSON sidecar of its generation details next
+//! to it (`{file name}.json`). Template placeholders:
+//!
+//! | placeholder     | value                                              |
+//! |-----------------|----------------------------------------------------|
+//! | `{project}`     | project name from its manifest, else its ID        |
+//! | `{project_id}`  | project ID                                         |
+//! | `{date}`        | creation date, `YYYY-MM-DD`                        |
+//! | `{time}`        | creation time, `HHMMSS` (UTC)                      |
+//! | `{prompt_slug}` | first words of the prompt, lowercase, `-`-joined   |
+//! | `{model}`       | model ID, or `unknown`                             |
+//! | `{type}`        | `image` or `video`                                 |
+//! | `{id}`          | asset ID; `{short_id}` is its first 8 characters   |
+//! | `{n}`           | position in the export, zero-padded to the count   |
+//! | `{ext}`         | the stored file's extension                        |
+//!
+//! `/` in the template makes subfolders. Values never contain path
+//! separators, so a prompt cannot steer a file outside `dest`. Everything
+//! here is blocking.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use crate::db::{AssetFilter, AssetRow};
+use crate::library::AssetLibrary;
+
+pub const DEFAULT_TEMPLATE: &str = "{project}_{date}_{prompt_slug}_{n}.{ext}";
+/// Longest `{prompt_slug}`, in characters.
+const SLUG_MAX_CHARS: usize = 40;
+const PLACEHOLDERS: &[&str] =
+    &["project", "project_id", "date", "time", "prompt_slug", "model", "type", "id", "short_id", "n", "ext"];
+/// Page size when resolving a filter.
+const FILTER_PAGE: usize = 500;
+
+/// What to do when a rendered name is taken — by an existing file or an
+/// earlier asset in the same export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Append `-2`, `-3`, … before the extension.
+    #[default]
+    Rename,
+    /// Leave the asset out.
+    Skip,
+    /// Replace an existing file. Two assets of one export never overwrite
+    /// each other; the later one is renamed.
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    /// Assets to export, in this order. Takes precedence over `filter`.
+    #[serde(default)]
+    pub asset_ids: Option<Vec<String>>,
+    /// Export what `list_assets` returns for this filter, newest first.
+    #[serde(default)]
+    pub filter: Option<AssetFilter>,
+    /// Defaults to [`DEFAULT_TEMPLATE`].
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Write `{file name}.json` with prompt, model, seed and the rest.
+    #[serde(default)]
+    pub sidecars: bool,
+    #[serde(default)]
+    pub on_collision: CollisionPolicy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedAsset {
+    pub asset_id: String,
+    pub path: String,
+    pub sidecar: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collision {
+    pub asset_id: String,
+    /// The name the template produced.
+    pub wanted: String,
+    /// Where the file went; `None` when it was skipped.
+    pub path: Option<String>,
+    pub resolution: CollisionPolicy,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetExportReport {
+    pub dest: String,
+    pub exported: Vec<ExportedAsset>,
+    /// Requested IDs with no live asset.
+    pub not_found: Vec<String>,
+    pub collisions: Vec<Collision>,
+    /// Assets that failed to copy, one message each.
+    pub errors: Vec<String>,
+}
+
+/// Sent after each asset, whatever happened to it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub done: usize,
+    pub total: usize,
+    pub asset_id: String,
+}
+
+/// Generation details written next to an exported file.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Sidecar<'a> {
+    asset_id: &'a str,
+    project_id: &'a str,
+    project: &'a str,
+    #[serde(rename = "type")]
+    asset_type: &'a str,
+    prompt: Option<&'a str>,
+    model: Option<&'a str>,
+    seed: Option<i64>,
+    width: Option<i32>,
+    height: Option<i32>,
+    duration: Option<f64>,
+    created_at: &'a str,
+    task_id: Option<&'a str>,
+    source: &'a str,
+    tags: &'a [String],
+    rating: Option<i32>,
+    sha256: Option<&'a str>,
+    original_file: &'a str,
+}
+
+/// Copy assets into `dest`, calling `progress` after each one.
+pub fn export_assets(
+    library: &AssetLibrary,
+    dest: &Path,
+    options: &ExportOptions,
+    mut progress: impl FnMut(&ExportProgress),
+) -> Result<AssetExportReport> {
+    let template = options.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    validate_template(template)?;
+    let (assets, not_found) = select(library, options)?;
+    std::fs::create_dir_all(dest).with_context(|| format!("failed to create {}", dest.display()))?;
+
+    let mut report = AssetExportReport {
+        dest: dest.to_string_lossy().to_string(),
+        not_found,
+        ..Default::default()
+    };
+    let width = assets.len().to_string().len();
+    let mut project_names = HashMap::new();
+    let mut taken = HashSet::new();
+
+    for (i, asset) in assets.iter().enumerate() {
+        let project = project_names
+            .entry(asset.project_id.clone())
+            .or_insert_with(|| project_name(library, &asset.project_id))
+            .clone();
+        let rel = render(template, asset, &project, i + 1, width);
+        match place(dest, &rel, options.on_collision, &mut taken) {
+            Placement::Free(path) => {
+                export_one(library, asset, &project, &path, options.sidecars, &mut report);
+            }
+            Placement::Collided(path, resolution) => {
+                report.collisions.push(Collision {
+                    asset_id: asset.id.clone(),
+                    wanted: rel.clone(),
+                    path: path.as_ref().map(|p| p.to_string_lossy().to_string()),
+                    resolution,
+                });
+                if let Some(path) = path {
+                    export_one(library, asset, &project, &path, options.sidecars, &mut report);
+                }
+            }
+        }
+        progress(&ExportProgress { done: i + 1, total: assets.len(), asset_id: asset.id.clone() });
+    }
+    Ok(report)
+}
+
+fn select(library: &AssetLibrary, options: &ExportOptions) -> Result<(Vec<AssetRow>, Vec<String>)> {
+    let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+    if let Some(ids) = &options.asset_ids {
+        let found = db.get_assets_by_ids(ids)?;
+        let not_found = ids.iter().filter(|id| !found.iter().any(|a| &a.id == *id)).cloned().collect();
+        return Ok((found, not_found));
+    }
+    let Some(filter) = &options.filter else {
+        bail!("nothing to export: pass asset IDs or a filter");
+    };
+    let mut assets = Vec::new();
+    loop {
+        let page = db.list_assets(filter, FILTER_PAGE, assets.len())?;
+        let last = page.len() < FILTER_PAGE;
+        assets.extend(page);
+        if last {
+            return Ok((assets, Vec::new()));
+        }
+    }
+}
+
+fn export_one(
+    library: &AssetLibrary,
+    asset: &AssetRow,
+    project: &str,
+    path: &Path,
+    sidecars: bool,
+    report: &mut AssetExportReport,
+) {
+    let copied = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::copy(&asset.file_path, path));
+    if let Err(e) = copied {
+        report.errors.push(format!("{}: failed to copy {}: {e}", asset.id, asset.file_path));
+        return;
+    }
+    let mut sidecar = None;
+    if sidecars {
+        let sidecar_path = PathBuf::from(format!("{}.json", path.to_string_lossy()));
+        match write_sidecar(library, asset, project, &sidecar_path) {
+            Ok(()) => sidecar = Some(sidecar_path.to_string_lossy().to_string()),
+            Err(e) => report.errors.push(format!("{}: {e:#}", asset.id)),
+        }
+    }
+    report.exported.push(ExportedAsset {
+        asset_id: asset.id.clone(),
+        path: path.to_string_lossy().to_string(),
+        sidecar,
+    });
+}
+
+fn write_sidecar(library: &AssetLibrary, asset: &AssetRow, project: &str, path: &Path) -> Result<()> {
+    // The seed is only in the task input
+    let seed = match &asset.task_id {
+        Some(task_id) => library
+            .db()
+            .lock()
+            .map_err(|e| anyhow::anyhow!("db lock: {e}"))?
+            .get_task(task_id)?
+            .and_then(|t| serde_json::from_str::<serde_json::Value>(&t.input).ok())
+            .and_then(|input| input["seed"].as_i64()),
+        None => None,
+    };
+    let sidecar = Sidecar {
+        asset_id: &asset.id,
+        project_id: &asset.project_id,
+        project,
+        asset_type: &asset.asset_type,
+        prompt: asset.prompt.as_deref(),
+        model: asset.model.as_deref(),
+        seed,
+        width: asset.width,
+        height: asset.height,
+        duration: asset.duration,
+        created_at: &asset.created_at,
+        task_id: asset.task_id.as_deref(),
+        source: &asset.source,
+        tags: &asset.tags,
+        rating: asset.rating,
+        sha256: asset.sha256.as_deref(),
+        original_file: &asset.file_name,
+    };
+    std::fs::write(path, serde_json::to_vec_pretty(&sidecar)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn project_name(library: &AssetLibrary, project_id: &str) -> String {
+    std::fs::read_to_string(library.project_dir(project_id).join("manifest.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .and_then(|m| m["name"].as_str().map(String::from))
+        .unwrap_or_else(|| project_id.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Names
+// ---------------------------------------------------------------------------
+
+/// Reject unknown placeholders and templates that could leave `dest`.
+pub fn validate_template(template: &str) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}').with_context(|| format!("unclosed '{{' in template \"{template}\""))?;
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            bail!("unknown placeholder {{{name}}} in template; known: {}", PLACEHOLDERS.join(", "));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    let path = Path::new(template);
+    if template.trim().is_empty()
+        || template.ends_with('/')
+        || path.components().any(|c| !matches!(c, Component::Normal(_)))
+    {
+        bail!("template \"{template}\" must be a relative file name, optionally with subfolders");
+    }
+    Ok(())
+}
+
+/// `dest`-relative path for one asset.
+fn render(template: &str, asset: &AssetRow, project: &str, n: usize, width: usize) -> String {
+    let created = chrono::DateTime::parse_from_rfc3339(&asset.created_at).ok().map(|t| t.with_timezone(&chrono::Utc));
+    let ext = Path::new(&asset.file_name).extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    let value = |name: &str| -> String {
+        match name {
+            "project" => sanitize(project),
+            "project_id" => sanitize(&asset.project_id),
+            "date" => created.map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_else(|| "undated".into()),
+            "time" => created.map(|t| t.format("%H%M%S").to_string()).unwrap_or_else(|| "000000".into()),
+            "prompt_slug" => slug(asset.prompt.as_deref().unwrap_or("")),
+            "model" => sanitize(asset.model.as_deref().unwrap_or("unknown")),
+            "type" => sanitize(&asset.asset_type),
+            "id" => sanitize(&asset.id),
+            "short_id" => sanitize(&asset.id.chars().take(8).collect::<String>()),
+            "n" => format!("{n:0width$}"),
+            "ext" => sanitize(&ext),
+            _ => String::new(),
+        }
+    };
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else { break };
+        out.push_str(&rest[..start]);
+        out.push_str(&value(&rest[start + 1..start + end]));
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Lowercase words of `text` joined by `-`, at most [`SLUG_MAX_CHARS`].
+/// Letters of any script are kept.
+fn slug(text: &str) -> String {
+    let mut out = String::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        let sep = usize::from(!out.is_empty());
+        let room = SLUG_MAX_CHARS.saturating_sub(out.chars().count() + sep);
+        if room == 0 {
+            break;
+        }
+        if sep == 1 {
+            out.push('-');
+        }
+        out.extend(word.to_lowercase().chars().take(room));
+    }
+    if out.is_empty() {
+        "untitled".into()
+    } else {
+        out
+    }
+}
+
+/// `value` with path separators and characters Windows rejects replaced.
+fn sanitize(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '-' } else { c })
+        .collect();
+    match cleaned.trim() {
+        "" | "." | ".." => "_".into(),
+        s => s.to_string(),
+    }
+}
+
+enum Placement {
+    Free(PathBuf),
+    /// Where the file goes instead, `None` to skip it.
+    Collided(Option<PathBuf>, CollisionPolicy),
+}
+
+fn place(dest: &Path, rel: &str, policy: CollisionPolicy, taken: &mut HashSet<PathBuf>) -> Placement {
+    let wanted = dest.join(rel);
+    let in_batch = taken.contains(&wanted);
+    if !in_batch && !wanted.exists() {
+        taken.insert(wanted.clone());
+        return Placement::Free(wanted);
+    }
+    let resolution = match policy {
+        CollisionPolicy::Overwrite if in_batch => CollisionPolicy::Rename,
+        policy => policy,
+    };
+    let path = match resolution {
+        CollisionPolicy::Skip => None,
+        CollisionPolicy::Overwrite => Some(wanted),
+        CollisionPolicy::Rename => {
+            let stem = wanted.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let ext = wanted.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            (2..)
+                .map(|i| wanted.with_file_name(format!("{stem}-{i}{ext}")))
+                .find(|p| !taken.contains(p) && !p.exists())
+        }
+    };
+    if let Some(path) = &path {
+        taken.insert(path.clone());
+    }
+    Placement::Collided(path, resolution)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{asset_row, setup};
+
+    fn asset(lib: &AssetLibrary, id: &str, prompt: &str) -> AssetRow {
+        let path = lib.project_dir("p1").join("assets").join(format!("{id}.png"));
+        std::fs::write(&path, id).unwrap();
+        let asset = AssetRow {
+            prompt: Some(prompt.into()),
+            model: Some("seedream".into()),
+            width: Some(64),
+            height: Some(64),
+            file_size: Some(id.len() as i64),
+            created_at: "2026-05-04T10:20:30+00:00".into(),
+            ..asset_row(id, &path)
+        };
+        lib.db().lock().unwrap().insert_asset(&asset).unwrap();
+        asset
+    }
+
+    #[test]
+    fn renders_templates_resolves_collisions_and_writes_sidecars() {
+        let (root, lib) = setup();
+        std::fs::write(lib.project_dir("p1").join("manifest.json"), r#"{"name":"Spring / Launch"}"#).unwrap();
+        asset(&lib, "a", "A red Fox, at dawn!");
+        asset(&lib, "b", "A red fox at dawn");
+
+        assert!(validate_template("{project}_{nope}.{ext}").is_err());
+        assert!(validate_template("../{n}.{ext}").is_err());
+        assert!(validate_template("{project}/{date}_{n}.{ext}").is_ok());
+
+        let dest = root.join("out");
+        let options = ExportOptions {
+            asset_ids: Some(vec!["a".into(), "b".into(), "ghost".into()]),
+            template: Some("{project}_{date}_{prompt_slug}.{ext}".into()),
+            sidecars: true,
+            ..Default::default()
+        };
+        let mut ticks = Vec::new();
+        let report = export_assets(&lib, &dest, &options, |p| ticks.push((p.done, p.total))).unwrap();
+        assert_eq!(ticks, vec![(1, 2), (2, 2)]);
+        assert_eq!(report.not_found, vec!["ghost".to_string()]);
+
+        let first = dest.join("Spring - Launch_2026-05-04_a-red-fox-at-dawn.png");
+        let second = dest.join("Spring - Launch_2026-05-04_a-red-fox-at-dawn-2.png");
+        assert_eq!(std::fs::read(&first).unwrap(), b"a");
+        assert_eq!(std::fs::read(&second).unwrap(), b"b");
+        assert_eq!(report.collisions.len(), 1);
+        assert_eq!(report.collisions[0].resolution, CollisionPolicy::Rename);
+        let sidecar: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(format!("{}.json", first.display())).unwrap()).unwrap();
+        assert_eq!(sidecar["prompt"], "A red Fox, at dawn!");
+        assert_eq!(sidecar["model"], "seedream");
+
+        // Running again with skip leaves both existing files alone
+        let skip = ExportOptions { on_collision: CollisionPolicy::Skip, sidecars: false, ..options };
+        let again = export_assets(&lib, &dest, &skip, |_| {}).unwrap();
+        assert!(again.exported.is_empty());
+        assert_eq!(again.collisions.len(), 2);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
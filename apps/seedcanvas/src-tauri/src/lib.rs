@@ -1,19 +1,40 @@
 pub mod ark;
+pub mod bundle;
+pub mod datadir;
 pub mod db;
+pub mod export;
+pub mod library;
 pub mod mcp;
+pub mod media;
+pub mod pricing;
+pub mod reconcile;
+pub mod search;
+pub mod similar;
+pub mod storage;
 pub mod tasks;
+pub mod trash;
+
+#[cfg(test)]
+pub(crate) mod test_support;
 
 #[cfg(unix)]
 mod mcp_bridge;
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
+use tauri_plugin_fs::FsExt;
 use tracing::info;
 
 use ark::ArkClient;
 use db::{Db, SharedDb};
+use library::AssetLibrary;
+use pricing::PriceTable;
+use storage::StorageOptions;
+use tasks::poller::PollPolicy;
 use tasks::{ImageParams, TaskQueue, UserDefaults, VideoParams};
 
 // ---------------------------------------------------------------------------
@@ -22,11 +43,17 @@ use tasks::{ImageParams, TaskQueue, UserDefaults, VideoParams};
 
 struct AppState {
     task_queue: Arc<TaskQueue>,
+    library: AssetLibrary,
     db: SharedDb,
+    /// Where the library lives; see [`datadir`].
+    data_dir: PathBuf,
+    /// seedcanvas-mcp processes connected to the bridge socket.
+    mcp_clients: Arc<AtomicUsize>,
 }
 
 // ---------------------------------------------------------------------------
-// Settings — read from {appDataDir}/settings.json
+// Settings — read from {appDataDir}/settings.json, which stays there when the
+// library moves (see datadir)
 // ---------------------------------------------------------------------------
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,12 +70,38 @@ struct Settings {
     default_image_model: Option<String>,
     #[serde(default)]
     default_video_model: Option<String>,
+    /// Public URL ARK should POST video callbacks to — a tunnel or reverse proxy
+    /// forwarding to `video_callback_listen_addr`. Unset means polling only.
+    #[serde(default)]
+    video_callback_url: Option<String>,
+    #[serde(default = "default_callback_listen_addr")]
+    video_callback_listen_addr: String,
+    /// Per-model video polling overrides, keyed by model ID.
+    #[serde(default)]
+    video_poll_policies: HashMap<String, PollPolicy>,
+    #[serde(default)]
+    asset_storage: StorageOptions,
+    /// Per-model prices for spend tracking; see [`pricing`].
+    #[serde(default)]
+    pricing: PriceTable,
+    /// Days deleted projects and assets stay in the trash; 0 deletes them
+    /// for good right away.
+    #[serde(default = "default_trash_retention_days")]
+    trash_retention_days: u32,
 }
 
 fn default_base_url() -> String {
     "https://ark.cn-beijing.volces.com/api/v3".to_string()
 }
 
+fn default_trash_retention_days() -> u32 {
+    trash::DEFAULT_RETENTION_DAYS
+}
+
+fn default_callback_listen_addr() -> String {
+    ark::callback::DEFAULT_LISTEN_ADDR.to_string()
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -57,12 +110,18 @@ impl Default for Settings {
             model: String::new(),
             default_image_model: None,
             default_video_model: None,
+            video_callback_url: None,
+            video_callback_listen_addr: default_callback_listen_addr(),
+            video_poll_policies: HashMap::new(),
+            asset_storage: StorageOptions::default(),
+            pricing: PriceTable::default(),
+            trash_retention_days: default_trash_retention_days(),
         }
     }
 }
 
-fn load_settings(data_dir: &PathBuf) -> Settings {
-    let path = data_dir.join("settings.json");
+fn load_settings(app_dir: &Path) -> Settings {
+    let path = app_dir.join("settings.json");
     match std::fs::read_to_string(&path) {
         Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
         Err(_) => Settings::default(),
@@ -74,6 +133,7 @@ fn load_settings(data_dir: &PathBuf) -> Settings {
 // ---------------------------------------------------------------------------
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn generate_image(
     state: tauri::State<'_, AppState>,
     project_id: String,
@@ -81,6 +141,9 @@ async fn generate_image(
     model: Option<String>,
     node_id: Option<String>,
     size: Option<String>,
+    reference_asset_ids: Option<Vec<String>>,
+    variant_of: Option<String>,
+    retry_of: Option<String>,
 ) -> Result<serde_json::Value, String> {
     let params = ImageParams {
         project_id,
@@ -88,6 +151,9 @@ async fn generate_image(
         model,
         node_id,
         size,
+        reference_asset_ids: reference_asset_ids.unwrap_or_default(),
+        variant_of,
+        retry_of,
     };
 
     let task_id = state
@@ -102,6 +168,7 @@ async fn generate_image(
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn generate_video(
     state: tauri::State<'_, AppState>,
     project_id: String,
@@ -111,6 +178,9 @@ async fn generate_video(
     resolution: Option<String>,
     ratio: Option<String>,
     duration: Option<i32>,
+    first_frame_asset_id: Option<String>,
+    variant_of: Option<String>,
+    retry_of: Option<String>,
 ) -> Result<serde_json::Value, String> {
     let params = VideoParams {
         project_id,
@@ -120,6 +190,9 @@ async fn generate_video(
         resolution,
         ratio,
         duration,
+        first_frame_asset_id,
+        variant_of,
+        retry_of,
     };
 
     let task_id = state
@@ -166,23 +239,204 @@ async fn task_status(
 // ---------------------------------------------------------------------------
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn list_assets(
     state: tauri::State<'_, AppState>,
     project_id: Option<String>,
     asset_type: Option<String>,
     query: Option<String>,
+    tags: Option<Vec<String>>,
+    collection_id: Option<String>,
+    favorite: Option<bool>,
+    min_rating: Option<i32>,
+    color: Option<String>,
+    color_distance: Option<f64>,
     limit: Option<usize>,
     offset: Option<usize>,
 ) -> Result<Vec<db::AssetRow>, String> {
+    let filter = db::AssetFilter {
+        project_id,
+        asset_type,
+        query,
+        tags: tags.unwrap_or_default(),
+        collection_id,
+        favorite,
+        min_rating,
+        color,
+        color_distance,
+    };
     let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
-    db.list_assets(
-        project_id.as_deref(),
-        asset_type.as_deref(),
-        query.as_deref(),
-        limit.unwrap_or(50),
-        offset.unwrap_or(0),
-    )
-    .map_err(|e| format!("{e:#}"))
+    db.list_assets(&filter, limit.unwrap_or(50), offset.unwrap_or(0))
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Ranked full-text search over prompts, notes, tags and file names, with
+/// highlighted snippets. Supports `"phrases"`, prefixes and `-exclusions`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn search_assets(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    project_id: Option<String>,
+    asset_type: Option<String>,
+    tags: Option<Vec<String>>,
+    collection_id: Option<String>,
+    favorite: Option<bool>,
+    min_rating: Option<i32>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<db::SearchHit>, String> {
+    let filter = db::AssetFilter {
+        project_id,
+        asset_type,
+        query: None,
+        tags: tags.unwrap_or_default(),
+        collection_id,
+        favorite,
+        min_rating,
+        color: None,
+        color_distance: None,
+    };
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.search_assets(&query, &filter, limit.unwrap_or(50), offset.unwrap_or(0))
+        .map_err(|e| format!("{e:#}"))
+}
+
+// ---------------------------------------------------------------------------
+// Asset organization — favorites, ratings, tags, notes, collections
+// ---------------------------------------------------------------------------
+
+/// Empty or null notes clear them.
+#[tauri::command]
+async fn set_asset_notes(
+    state: tauri::State<'_, AppState>,
+    asset_id: String,
+    notes: Option<String>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.set_asset_notes(&asset_id, notes.as_deref()).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn set_asset_favorite(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+    favorite: bool,
+) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.set_asset_favorite(&asset_ids, favorite).map_err(|e| format!("{e:#}"))
+}
+
+/// `rating` 1–5, or null to clear.
+#[tauri::command]
+async fn set_asset_rating(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+    rating: Option<i32>,
+) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.set_asset_rating(&asset_ids, rating).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn add_asset_tags(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+    tags: Vec<String>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.add_tags(&asset_ids, &tags).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn remove_asset_tags(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+    tags: Vec<String>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.remove_tags(&asset_ids, &tags).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn list_tags(state: tauri::State<'_, AppState>) -> Result<Vec<db::Tag>, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.list_tags().map_err(|e| format!("{e:#}"))
+}
+
+/// Renaming onto an existing tag merges the two.
+#[tauri::command]
+async fn rename_tag(
+    state: tauri::State<'_, AppState>,
+    from: String,
+    to: String,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.rename_tag(&from, &to).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn delete_tag(state: tauri::State<'_, AppState>, name: String) -> Result<bool, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.delete_tag(&name).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn list_collections(state: tauri::State<'_, AppState>) -> Result<Vec<db::Collection>, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.list_collections().map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn create_collection(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    description: Option<String>,
+) -> Result<db::Collection, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.create_collection(&name, description.as_deref()).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn update_collection(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+    name: Option<String>,
+    description: Option<String>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.update_collection(&collection_id, name.as_deref(), description.as_deref())
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Deletes the collection only; its assets are untouched.
+#[tauri::command]
+async fn delete_collection(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+) -> Result<bool, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.delete_collection(&collection_id).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn add_to_collection(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+    asset_ids: Vec<String>,
+) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.add_to_collection(&collection_id, &asset_ids).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn remove_from_collection(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+    asset_ids: Vec<String>,
+) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.remove_from_collection(&collection_id, &asset_ids).map_err(|e| format!("{e:#}"))
 }
 
 #[tauri::command]
@@ -193,37 +447,180 @@ async fn get_asset_stats(
     db.get_asset_stats().map_err(|e| format!("{e:#}"))
 }
 
+/// Import a file into a project's `assets/` directory and record it. The
+/// type is detected from the file content; `asset_type` is only checked
+/// against it. Files already in `assets/` are registered where they are.
 #[tauri::command]
 async fn register_imported_asset(
     state: tauri::State<'_, AppState>,
     project_id: String,
     file_path: String,
-    file_name: String,
-    asset_type: String,
-) -> Result<serde_json::Value, String> {
-    let file_size = std::fs::metadata(&file_path).ok().map(|m| m.len() as i64);
-    let now = chrono::Utc::now().to_rfc3339();
+    asset_type: Option<String>,
+    mode: Option<library::ImportMode>,
+) -> Result<db::AssetRow, String> {
+    let library = state.library.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        library.import_file(&project_id, Path::new(&file_path), mode.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| format!("{e}"))?
+    .map_err(|e| format!("{e:#}"))?;
+
+    let asset = result.asset.ok_or("import produced no asset")?;
+    if let Some(claimed) = asset_type.filter(|t| *t != asset.asset_type) {
+        tracing::warn!(path = %asset.file_path, "imported file claimed to be {claimed}, is {}", asset.asset_type);
+    }
+    Ok(asset)
+}
 
-    let asset = db::AssetRow {
-        id: uuid::Uuid::new_v4().to_string(),
-        project_id,
-        task_id: None,
-        asset_type,
-        file_path,
-        file_name,
-        prompt: None,
-        model: None,
-        width: None,
-        height: None,
-        file_size,
-        source: "imported".to_string(),
-        created_at: now,
-    };
+/// Generation details embedded in a media file, e.g. before importing it.
+#[tauri::command]
+async fn read_file_provenance(path: String) -> Result<Option<media::provenance::Provenance>, String> {
+    tokio::task::spawn_blocking(move || media::provenance::read_file(Path::new(&path)))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
 
+/// Import every supported file in a folder into a project, reporting the
+/// outcome per file.
+#[tauri::command]
+async fn import_folder(
+    state: tauri::State<'_, AppState>,
+    project_id: String,
+    folder_path: String,
+    recursive: Option<bool>,
+    mode: Option<library::ImportMode>,
+) -> Result<library::FolderImportReport, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || {
+        library.import_folder(
+            &project_id,
+            Path::new(&folder_path),
+            recursive.unwrap_or(false),
+            mode.unwrap_or_default(),
+        )
+    })
+    .await
+    .map_err(|e| format!("{e}"))?
+    .map_err(|e| format!("{e:#}"))
+}
+
+/// Re-hash asset files (all, or one project's) and report missing, truncated
+/// or modified files. Assets recorded before hashing get their hash stored.
+#[tauri::command]
+async fn verify_assets(
+    state: tauri::State<'_, AppState>,
+    project_id: Option<String>,
+) -> Result<storage::VerifyReport, String> {
+    let db = Arc::clone(&state.db);
+    tokio::task::spawn_blocking(move || storage::verify_assets(&db, project_id.as_deref()))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Ancestors and descendants of an asset; `None` if it does not exist.
+#[tauri::command]
+async fn get_asset_lineage(
+    state: tauri::State<'_, AppState>,
+    asset_id: String,
+) -> Result<Option<db::AssetLineage>, String> {
     let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
-    db.insert_asset(&asset).map_err(|e| format!("{e:#}"))?;
+    db.get_asset_lineage(&asset_id).map_err(|e| format!("{e:#}"))
+}
 
-    Ok(serde_json::json!({ "id": asset.id }))
+/// Image assets that look like `asset_id` (perceptual hash within
+/// `threshold`, default 10), closest first. Limited to `project_id` if given.
+#[tauri::command]
+async fn find_similar_assets(
+    state: tauri::State<'_, AppState>,
+    asset_id: String,
+    threshold: Option<u32>,
+    project_id: Option<String>,
+) -> Result<Vec<similar::SimilarAsset>, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || similar::find_similar(&library, &asset_id, threshold, project_id.as_deref()))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Groups of near-duplicate images with a suggested keeper for each.
+#[tauri::command]
+async fn cluster_duplicate_assets(
+    state: tauri::State<'_, AppState>,
+    project_id: Option<String>,
+    threshold: Option<u32>,
+) -> Result<similar::DuplicateReport, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || similar::cluster_duplicates(&library, project_id.as_deref(), threshold))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Delete assets with their files and previews — into the trash unless the
+/// retention period is 0. If a saved canvas still
+/// references any of them nothing is deleted and the references are returned;
+/// call again with `confirm` to delete anyway.
+#[tauri::command]
+async fn delete_assets(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+    confirm: Option<bool>,
+) -> Result<library::DeleteOutcome, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || library.delete_assets(&asset_ids, confirm.unwrap_or(false)))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Move assets (rows and files) into another project. Like `delete_assets`,
+/// a move that would break canvas references needs `confirm`.
+#[tauri::command]
+async fn move_assets(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+    project_id: String,
+    confirm: Option<bool>,
+) -> Result<library::TransferOutcome, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || library.move_assets(&asset_ids, &project_id, confirm.unwrap_or(false)))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Copy assets into another project as new rows with the same metadata.
+#[tauri::command]
+async fn copy_assets(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+    project_id: String,
+) -> Result<library::TransferOutcome, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || library.copy_assets(&asset_ids, &project_id))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Compare asset rows, done tasks and the asset directories. Without
+/// `resolutions` this is a dry run that only reports; with them, the listed
+/// issues are resolved (re-register, re-download or purge).
+#[tauri::command]
+async fn reconcile_assets(
+    state: tauri::State<'_, AppState>,
+    project_id: Option<String>,
+    resolutions: Option<Vec<reconcile::Resolution>>,
+) -> Result<reconcile::ReconcileReport, String> {
+    let result = match resolutions.filter(|r| !r.is_empty()) {
+        Some(resolutions) => reconcile::apply(&state.library, project_id.as_deref(), &resolutions).await,
+        None => reconcile::scan(&state.library, project_id.as_deref(), true).await,
+    };
+    result.map_err(|e| format!("{e:#}"))
 }
 
 #[tauri::command]
@@ -234,15 +631,59 @@ async fn get_usage_stats(
     db.get_usage_stats().map_err(|e| format!("{e:#}"))
 }
 
+/// Task outcomes and asset totals for `range`, grouped by project, model or type.
+#[tauri::command]
+async fn get_stats_breakdown(
+    state: tauri::State<'_, AppState>,
+    range: Option<db::StatsRange>,
+    group_by: db::StatsGroupBy,
+) -> Result<db::StatsBreakdown, String> {
+    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+    db.get_stats_breakdown(&range.unwrap_or_default(), group_by)
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Write every priced task created within `range` to `path` as CSV.
+/// Returns the number of rows written.
+#[tauri::command]
+async fn export_costs_csv(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    range: Option<db::StatsRange>,
+) -> Result<usize, String> {
+    let rows = {
+        let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
+        db.list_task_costs(&range.unwrap_or_default()).map_err(|e| format!("{e:#}"))?
+    };
+    tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&path)?);
+        pricing::write_csv(&rows, &mut out)?;
+        std::io::Write::flush(&mut out)?;
+        Ok(rows.len())
+    })
+    .await
+    .map_err(|e| format!("{e}"))?
+    .map_err(|e| format!("{e:#}"))
+}
+
+/// Bytes on disk per project, walked from the projects directory.
+#[tauri::command]
+async fn get_project_disk_usage(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<storage::ProjectDiskUsage>, String> {
+    let projects_dir = state.library.projects_dir().to_path_buf();
+    tokio::task::spawn_blocking(move || storage::project_disk_usage(&projects_dir))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
 #[tauri::command]
 async fn get_data_dir_info(
-    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
 ) -> Result<serde_json::Value, String> {
-    let data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("failed to resolve data dir: {e}"))?;
-    let db_path = data_dir.join("seedcanvas.db");
+    let data_dir = state.data_dir.clone();
+    let db_path = data_dir.join(datadir::DB_FILE);
     let db_size = std::fs::metadata(&db_path).ok().map(|m| m.len()).unwrap_or(0);
 
     Ok(serde_json::json!({
@@ -251,28 +692,139 @@ async fn get_data_dir_info(
     }))
 }
 
-/// Delete SQLite data associated with a project.
-/// When `keep_assets` is true, only tasks are deleted (asset records remain to track files on disk).
+/// Write a project — files plus its task, asset and lineage rows — to a zip bundle.
+#[tauri::command]
+async fn export_project(
+    state: tauri::State<'_, AppState>,
+    project_id: String,
+    path: String,
+) -> Result<bundle::ExportReport, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || bundle::export_project(&library, &project_id, Path::new(&path)))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Restore a project from a bundle. The caller adds the returned project to
+/// the recent-projects index.
+#[tauri::command]
+async fn import_project(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    options: Option<bundle::ImportOptions>,
+) -> Result<bundle::ImportReport, String> {
+    let library = state.library.clone();
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || bundle::import_project(&library, Path::new(&path), &options))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Copy assets into `dest` under names rendered from a template, optionally
+/// with JSON sidecars. Emits `export:progress` after each asset.
+#[tauri::command]
+async fn export_assets(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    dest: String,
+    options: export::ExportOptions,
+) -> Result<export::AssetExportReport, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || {
+        export::export_assets(&library, Path::new(&dest), &options, |progress| {
+            let _ = app.emit("export:progress", progress);
+        })
+    })
+    .await
+    .map_err(|e| format!("{e}"))?
+    .map_err(|e| format!("{e:#}"))
+}
+
+/// Move SQLite data associated with a project to the trash; the retention
+/// sweep deletes it later. When `keep_assets` is true, only tasks are trashed
+/// (asset records remain to track files on disk).
 #[tauri::command]
 async fn delete_project_data(
     state: tauri::State<'_, AppState>,
     project_id: String,
     keep_assets: Option<bool>,
 ) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| format!("db lock: {e}"))?;
-    if keep_assets.unwrap_or(false) {
-        db.delete_tasks_by_project(&project_id).map_err(|e| format!("{e:#}"))
-    } else {
-        db.delete_all_project_data(&project_id).map_err(|e| format!("{e:#}"))
-    }
+    let library = state.library.clone();
+    let tasks_only = keep_assets.unwrap_or(false);
+    tokio::task::spawn_blocking(move || trash::trash_project_records(&library, &project_id, tasks_only))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
 }
 
+// ---------------------------------------------------------------------------
+// Trash
+// ---------------------------------------------------------------------------
+
+/// Move a project directory and its data to the trash. The caller removes it
+/// from projects.json.
 #[tauri::command]
-async fn reveal_data_dir(app: tauri::AppHandle) -> Result<(), String> {
-    let data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("failed to resolve data dir: {e}"))?;
+async fn trash_project(
+    state: tauri::State<'_, AppState>,
+    project_id: String,
+) -> Result<db::TrashedProject, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || trash::trash_project(&library, &project_id))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn list_trash(state: tauri::State<'_, AppState>) -> Result<trash::TrashListing, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || trash::list_trash(&library))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn restore_assets(
+    state: tauri::State<'_, AppState>,
+    asset_ids: Vec<String>,
+) -> Result<trash::RestoreOutcome, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || trash::restore_assets(&library, &asset_ids))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Move a trashed project back into projects/. The caller re-adds it to
+/// projects.json.
+#[tauri::command]
+async fn restore_project(
+    state: tauri::State<'_, AppState>,
+    project_id: String,
+) -> Result<db::TrashedProject, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || trash::restore_project(&library, &project_id))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// Permanently delete everything in the trash.
+#[tauri::command]
+async fn empty_trash(state: tauri::State<'_, AppState>) -> Result<trash::PurgeReport, String> {
+    let library = state.library.clone();
+    tokio::task::spawn_blocking(move || trash::purge(&library, None))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+async fn reveal_data_dir(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let data_dir = state.data_dir.clone();
 
     #[cfg(target_os = "macos")]
     {
@@ -299,6 +851,33 @@ async fn reveal_data_dir(app: tauri::AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+/// Move the library — database, projects and trash — to `new_path`, then
+/// restart the app so everything follows. Refused while a seedcanvas-mcp
+/// process is connected: it would keep writing to the old location.
+#[tauri::command]
+async fn relocate_data_dir(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    new_path: String,
+) -> Result<datadir::RelocateReport, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("failed to resolve data dir: {e}"))?;
+    let clients = state.mcp_clients.load(Ordering::SeqCst);
+    if clients > 0 {
+        return Err(format!("{clients} MCP clients are connected; quit them before moving the library"));
+    }
+    let db = state.db.clone();
+    let report = tokio::task::spawn_blocking(move || datadir::relocate(&app_dir, &db, Path::new(&new_path)))
+        .await
+        .map_err(|e| format!("{e}"))?
+        .map_err(|e| format!("{e:#}"))?;
+    // The asset library and task queue still hold the old paths
+    app.request_restart();
+    Ok(report)
+}
+
 // ---------------------------------------------------------------------------
 // Orphan project cleanup
 // ---------------------------------------------------------------------------
@@ -316,13 +895,16 @@ struct OrphanProject {
 
 /// Scan projects/ directory for subdirectories not tracked in projects.json.
 #[tauri::command]
-async fn scan_orphan_projects(app: tauri::AppHandle) -> Result<Vec<OrphanProject>, String> {
-    let data_dir = app.path().app_data_dir()
+async fn scan_orphan_projects(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<OrphanProject>, String> {
+    let app_dir = app.path().app_data_dir()
         .map_err(|e| format!("failed to resolve data dir: {e}"))?;
-    let projects_dir = data_dir.join("projects");
+    let projects_dir = state.library.projects_dir().to_path_buf();
 
     // Read projects.json to find tracked project IDs
-    let index_path = data_dir.join("projects.json");
+    let index_path = app_dir.join("projects.json");
     let tracked_ids: std::collections::HashSet<String> = match std::fs::read_to_string(&index_path) {
         Ok(contents) => {
             serde_json::from_str::<Vec<serde_json::Value>>(&contents)
@@ -347,7 +929,8 @@ async fn scan_orphan_projects(app: tauri::AppHandle) -> Result<Vec<OrphanProject
             None => continue,
         };
 
-        if tracked_ids.contains(&dir_name) {
+        // Hidden: a bundle import's staging or backup directory
+        if dir_name.starts_with('.') || tracked_ids.contains(&dir_name) {
             continue;
         }
 
@@ -368,37 +951,27 @@ async fn scan_orphan_projects(app: tauri::AppHandle) -> Result<Vec<OrphanProject
     Ok(orphans)
 }
 
-/// Delete specified orphan project directories and their SQLite data.
+/// Move specified orphan project directories and their SQLite data to the
+/// trash.
 #[tauri::command]
 async fn cleanup_orphan_projects(
-    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     project_ids: Vec<String>,
 ) -> Result<serde_json::Value, String> {
-    let data_dir = app.path().app_data_dir()
-        .map_err(|e| format!("failed to resolve data dir: {e}"))?;
-    let projects_dir = data_dir.join("projects");
-
-    let mut deleted = 0u32;
-    let mut errors = Vec::new();
-
-    for id in &project_ids {
-        // Delete SQLite data (tasks + assets)
-        if let Ok(db) = state.db.lock() {
-            let _ = db.delete_all_project_data(id);
-        }
-
-        // Delete the directory on disk
-        let dir = projects_dir.join(id);
-        if dir.is_dir() {
-            match std::fs::remove_dir_all(&dir) {
-                Ok(()) => deleted += 1,
-                Err(e) => errors.push(format!("{id}: {e}")),
+    let library = state.library.clone();
+    let (deleted, errors) = tokio::task::spawn_blocking(move || {
+        let mut deleted = 0u32;
+        let mut errors = Vec::new();
+        for id in &project_ids {
+            match trash::trash_project(&library, id) {
+                Ok(_) => deleted += 1,
+                Err(e) => errors.push(format!("{id}: {e:#}")),
             }
-        } else {
-            deleted += 1; // Already gone
         }
-    }
+        (deleted, errors)
+    })
+    .await
+    .map_err(|e| format!("{e}"))?;
 
     Ok(serde_json::json!({ "deleted": deleted, "errors": errors }))
 }
@@ -546,18 +1119,25 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
-            let data_dir = app
+            let app_dir = app
                 .path()
                 .app_data_dir()
                 .expect("failed to resolve app data dir");
-            std::fs::create_dir_all(&data_dir)?;
+            std::fs::create_dir_all(&app_dir)?;
 
             // Load settings
-            let settings = load_settings(&data_dir);
+            let settings = load_settings(&app_dir);
             info!(base_url = %settings.base_url, "loaded settings");
 
+            // The library may live elsewhere; the WebView needs to reach it
+            let data_dir = datadir::resolve(&app_dir)?;
+            if data_dir != app_dir {
+                info!(data_dir = %data_dir.display(), "using relocated data directory");
+                app.fs_scope().allow_directory(&data_dir, true)?;
+            }
+
             // Open SQLite database (shared handle)
-            let db_path = data_dir.join("seedcanvas.db");
+            let db_path = data_dir.join(datadir::DB_FILE);
             let db = Db::open(&db_path).expect("failed to open database");
             let shared_db: SharedDb = Arc::new(std::sync::Mutex::new(db));
 
@@ -574,8 +1154,9 @@ pub fn run() {
             // Create ARK client
             let ark = ArkClient::new(settings.base_url, settings.api_key);
 
-            // Projects directory (same as frontend uses via Tauri fs plugin)
-            let projects_dir = data_dir.join("projects");
+            // Projects directory (the frontend reaches it through
+            // {appDataDir}/projects, a link once the library is relocated)
+            let projects_dir = data_dir.join(datadir::PROJECTS_DIR);
             std::fs::create_dir_all(&projects_dir)?;
 
             // Build user defaults from settings
@@ -584,30 +1165,96 @@ pub fn run() {
                 default_video_model: settings.default_video_model,
             };
 
-            // Create task queue with shared DB and resume any interrupted tasks
-            let task_queue = TaskQueue::new_with_shared(
+            // Create task queue with shared DB
+            let mut library = AssetLibrary::new(Arc::clone(&shared_db), projects_dir.clone());
+            library.set_storage_options(settings.asset_storage.clone());
+            library.set_trash_retention_days(settings.trash_retention_days);
+            let mut task_queue = TaskQueue::new_with_shared(
                 Arc::clone(&shared_db),
                 ark,
-                app.handle().clone(),
                 projects_dir,
                 user_defaults,
             );
+            task_queue.set_poll_policies(settings.video_poll_policies.clone());
+            task_queue.set_storage_options(settings.asset_storage.clone());
+            task_queue.set_price_table(settings.pricing.clone());
+
+            // Optional ARK callback receiver — video tasks complete on callback,
+            // with slow polling kept as a fallback
+            if let Some(public_url) = settings.video_callback_url.as_deref().filter(|u| !u.trim().is_empty()) {
+                match ark::callback::bind(&settings.video_callback_listen_addr, public_url) {
+                    Ok((callbacks, server)) => {
+                        task_queue.set_callbacks(callbacks);
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = server.run().await {
+                                tracing::error!("ARK callback receiver failed: {e:#}");
+                            }
+                        });
+                    }
+                    Err(e) => tracing::warn!("ARK callbacks disabled, polling instead: {e:#}"),
+                }
+            }
+
+            // Forward task lifecycle events to the WebView
+            let events_rx = task_queue.subscribe();
+            let emit_handle = app.handle().clone();
+            tauri::async_runtime::spawn(tasks::events::for_each_event(events_rx, move |event| {
+                let _ = emit_handle.emit(event.name(), event.payload());
+            }));
+
+            // Resume any interrupted tasks (after subscribing so their events reach the UI)
             if let Err(e) = task_queue.resume_running_tasks() {
                 tracing::error!("failed to resume running tasks: {e:#}");
             }
 
+            // Take over tasks orphaned by a crashed seedcanvas-mcp process
+            let task_queue = Arc::new(task_queue);
+            tauri::async_runtime::spawn(Arc::clone(&task_queue).sweep_stale_leases());
+
+            // Report drift between the DB and the asset directories; fixing
+            // it is left to the user via reconcile_assets
+            let reconcile_library = library.clone();
+            tauri::async_runtime::spawn(async move {
+                match reconcile::scan(&reconcile_library, None, false).await {
+                    Ok(report) if report.issues.is_empty() => {}
+                    Ok(report) => tracing::warn!(
+                        issues = report.issues.len(),
+                        "asset library out of sync with disk; run reconcile_assets to review"
+                    ),
+                    Err(e) => tracing::error!("asset reconcile scan failed: {e:#}"),
+                }
+            });
+
+            // Palettes for images stored before palettes existed
+            let palette_library = library.clone();
+            tauri::async_runtime::spawn(async move {
+                match tokio::task::spawn_blocking(move || palette_library.backfill_palettes()).await {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(n)) => tracing::info!(assets = n, "extracted missing color palettes"),
+                    Ok(Err(e)) => tracing::error!("palette backfill failed: {e:#}"),
+                    Err(e) => tracing::error!("palette backfill panicked: {e}"),
+                }
+            });
+
+            // Purge trash past its retention period
+            tauri::async_runtime::spawn(trash::sweep(library.clone()));
+
+            let mcp_clients = Arc::new(AtomicUsize::new(0));
             app.manage(AppState {
-                task_queue: Arc::new(task_queue),
+                task_queue,
+                library,
                 db: shared_db,
+                data_dir,
+                mcp_clients: Arc::clone(&mcp_clients),
             });
 
             // Start the Unix socket bridge for MCP binary communication
             #[cfg(unix)]
             {
-                let bridge_data_dir = data_dir.clone();
+                let bridge_app_dir = app_dir.clone();
                 let bridge_handle = app.handle().clone();
                 tauri::async_runtime::spawn(async move {
-                    if let Err(e) = mcp_bridge::start(bridge_data_dir, bridge_handle).await {
+                    if let Err(e) = mcp_bridge::start(bridge_app_dir, bridge_handle, mcp_clients).await {
                         tracing::error!("MCP bridge failed: {e:#}");
                     }
                 });
@@ -621,13 +1268,50 @@ pub fn run() {
             task_status,
             list_assets,
             get_asset_stats,
+            search_assets,
+            set_asset_notes,
+            set_asset_favorite,
+            set_asset_rating,
+            add_asset_tags,
+            remove_asset_tags,
+            list_tags,
+            rename_tag,
+            delete_tag,
+            list_collections,
+            create_collection,
+            update_collection,
+            delete_collection,
+            add_to_collection,
+            remove_from_collection,
             register_imported_asset,
+            import_folder,
+            read_file_provenance,
+            verify_assets,
+            get_asset_lineage,
+            find_similar_assets,
+            cluster_duplicate_assets,
+            delete_assets,
+            move_assets,
+            copy_assets,
+            reconcile_assets,
             get_usage_stats,
+            get_stats_breakdown,
+            get_project_disk_usage,
+            export_costs_csv,
             get_data_dir_info,
+            export_project,
+            import_project,
+            export_assets,
             delete_project_data,
             reveal_data_dir,
+            relocate_data_dir,
             scan_orphan_projects,
             cleanup_orphan_projects,
+            trash_project,
+            list_trash,
+            restore_assets,
+            restore_project,
+            empty_trash,
             resolve_mcp_binary_path,
             check_mcp_config,
             inject_mcp_config,
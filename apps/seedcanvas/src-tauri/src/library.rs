@@ -0,0 +1,870 @@
+//! Asset library operations shared by the Tauri commands and the MCP tools.
+//!
+//! The DB holds asset rows; files live under `{projects_dir}/{project}/`
+//! (`assets/` plus `thumbnails/`). Operations here keep the two in step.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::db::{AssetRow, SharedDb};
+use crate::media::palette;
+use crate::media::probe::{self, MediaFormat};
+use crate::media::provenance::{self, Provenance};
+use crate::media::thumbnail;
+use crate::storage::{self, AssetStore, StorageOptions};
+use crate::trash;
+
+#[derive(Clone)]
+pub struct AssetLibrary {
+    db: SharedDb,
+    store: AssetStore,
+    trash_retention_days: u32,
+}
+
+impl AssetLibrary {
+    pub fn new(db: SharedDb, projects_dir: PathBuf) -> Self {
+        Self { db, store: AssetStore::new(projects_dir), trash_retention_days: trash::DEFAULT_RETENTION_DAYS }
+    }
+
+    /// Days deleted assets and projects stay in the trash. 0 deletes them
+    /// for good right away.
+    pub fn set_trash_retention_days(&mut self, days: u32) {
+        self.trash_retention_days = days;
+    }
+
+    pub fn trash_retention_days(&self) -> u32 {
+        self.trash_retention_days
+    }
+
+    /// Storage options apply to imported files as they do to generated ones.
+    pub fn set_storage_options(&mut self, options: StorageOptions) {
+        self.store.set_options(options);
+    }
+
+    pub fn db(&self) -> &SharedDb {
+        &self.db
+    }
+
+    pub fn projects_dir(&self) -> &Path {
+        self.store.projects_dir()
+    }
+
+    pub fn project_dir(&self, project_id: &str) -> PathBuf {
+        self.store.project_dir(project_id)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, crate::db::Db>> {
+        self.db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// File metadata
+// ---------------------------------------------------------------------------
+
+impl AssetLibrary {
+    /// Fill in size, media details, hash and — for images — a library
+    /// thumbnail and color palette from the file at `asset.file_path`.
+    /// Blocking. Failures are
+    /// logged and leave the fields empty; a file the probe cannot read is
+    /// still a valid asset.
+    pub fn fill_file_metadata(&self, asset: &mut AssetRow) {
+        let path = PathBuf::from(&asset.file_path);
+        match probe::probe_file(&path) {
+            Ok(info) => asset.set_media_info(&info),
+            Err(e) => warn!(path = %path.display(), "asset not probed: {e:#}"),
+        }
+        if asset.sha256.is_none() {
+            match storage::sha256_file(&path) {
+                Ok((sha256, size)) => {
+                    asset.sha256 = Some(sha256);
+                    asset.file_size = Some(size as i64);
+                }
+                Err(e) => warn!(path = %path.display(), "asset not hashed: {e:#}"),
+            }
+        }
+        // Videos only get a thumbnail from a poster frame, which a bare file lacks
+        if asset.asset_type == "image" {
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    warn!(path = %path.display(), "asset not readable: {e}");
+                    return;
+                }
+            };
+            let project_dir = self.project_dir(&asset.project_id);
+            match thumbnail::create_thumbnail(&project_dir, &asset.file_name, &bytes) {
+                Ok(thumb) => asset.thumbnail_path = Some(thumb.to_string_lossy().to_string()),
+                Err(e) => warn!(path = %path.display(), "asset thumbnail failed: {e:#}"),
+            }
+            match palette::extract(&bytes) {
+                Ok(colors) => asset.palette = colors,
+                Err(e) => warn!(path = %path.display(), "asset palette failed: {e:#}"),
+            }
+        }
+    }
+
+    /// Extract palettes for image assets stored before palettes existed.
+    /// Blocking. Returns how many were filled; unreadable files are logged
+    /// and marked so the next launch skips them.
+    pub fn backfill_palettes(&self) -> Result<usize> {
+        let pending = self.lock()?.list_images_without_palette()?;
+        let mut filled = 0;
+        for (id, path) in pending {
+            match std::fs::read(&path).map_err(anyhow::Error::from).and_then(|b| palette::extract(&b)) {
+                Ok(colors) => {
+                    self.lock()?.set_asset_palette(&id, &colors)?;
+                    filled += 1;
+                }
+                Err(e) => {
+                    warn!(asset_id = %id, path = %path, "asset palette failed: {e:#}");
+                    self.lock()?.mark_asset_palette_checked(&id)?;
+                }
+            }
+        }
+        Ok(filled)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Import
+//
+// The file's magic bytes decide its type — the extension and the caller's
+// claim are not trusted — and anything but PNG, JPEG, WebP or MP4 is
+// rejected before it reaches `assets/`.
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Leave the original where it is.
+    #[default]
+    Copy,
+    /// Remove the original once it is in `assets/`.
+    Move,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub source: String,
+    pub asset: Option<AssetRow>,
+    /// The content matched a file already in the project (content-addressed
+    /// storage only); the new row shares it.
+    pub deduplicated: bool,
+    /// Generation details embedded in the file (see `media::provenance`).
+    pub provenance: Option<Provenance>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderImportReport {
+    pub imported: usize,
+    pub failed: usize,
+    pub results: Vec<ImportResult>,
+}
+
+impl AssetLibrary {
+    /// Import one file into a project: detect its format, copy or move it
+    /// into `assets/`, probe, hash and thumbnail it, and record the row.
+    /// A file already inside the project's `assets/` is registered in place.
+    /// Blocking.
+    pub fn import_file(&self, project_id: &str, src: &Path, mode: ImportMode) -> Result<ImportResult> {
+        let project_dir = self.project_dir(project_id);
+        if !project_dir.join("manifest.json").exists() {
+            bail!("project \"{project_id}\" does not exist");
+        }
+        let format = MediaFormat::sniff_file(src)?.with_context(|| {
+            format!("{}: unsupported format (expected PNG, JPEG, WebP or MP4)", src.display())
+        })?;
+
+        let asset_dir = self.store.asset_dir(project_id);
+        let in_place = src.parent().is_some_and(|dir| same_dir(dir, &asset_dir));
+        let (path, file_name, sha256, size, deduplicated) = if in_place {
+            let (sha256, size) = storage::sha256_file(src)?;
+            let name = src.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            (src.to_path_buf(), name, sha256, size, false)
+        } else {
+            let stored = self.store.import_file(project_id, src, format.extension(), mode == ImportMode::Move)?;
+            (stored.path, stored.file_name, stored.sha256, stored.size, stored.deduplicated)
+        };
+
+        let mut asset = AssetRow {
+            id: uuid::Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            asset_type: format.asset_type().to_string(),
+            file_path: path.to_string_lossy().to_string(),
+            file_name,
+            file_size: Some(size as i64),
+            source: "imported".to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            sha256: Some(sha256),
+            ..Default::default()
+        };
+        self.fill_file_metadata(&mut asset);
+        // A file generated here (or by another tool writing XMP) brings its prompt back
+        let provenance = provenance::read_file(&path)
+            .map_err(|e| warn!(path = %path.display(), "failed to read provenance: {e:#}"))
+            .ok()
+            .flatten();
+        if let Some(p) = &provenance {
+            asset.prompt = p.prompt.clone();
+            asset.model = p.model.clone();
+        }
+        self.lock()?.insert_asset(&asset)?;
+
+        Ok(ImportResult {
+            source: src.to_string_lossy().to_string(),
+            asset: Some(asset),
+            deduplicated,
+            provenance,
+            error: None,
+        })
+    }
+
+    /// Import every file in `folder` (and its subfolders with `recursive`).
+    /// Each file succeeds or fails on its own; hidden files are skipped.
+    /// Blocking.
+    pub fn import_folder(
+        &self,
+        project_id: &str,
+        folder: &Path,
+        recursive: bool,
+        mode: ImportMode,
+    ) -> Result<FolderImportReport> {
+        if !folder.is_dir() {
+            bail!("{} is not a directory", folder.display());
+        }
+        let mut files = Vec::new();
+        collect_files(folder, recursive, &mut files)?;
+        files.sort();
+
+        let mut report = FolderImportReport::default();
+        for file in files {
+            let result = self.import_file(project_id, &file, mode).unwrap_or_else(|e| ImportResult {
+                source: file.to_string_lossy().to_string(),
+                asset: None,
+                deduplicated: false,
+                provenance: None,
+                error: Some(format!("{e:#}")),
+            });
+            if result.error.is_some() {
+                report.failed += 1;
+            } else {
+                report.imported += 1;
+            }
+            report.results.push(result);
+        }
+        Ok(report)
+    }
+}
+
+fn collect_files(dir: &Path, recursive: bool, out: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            if recursive {
+                collect_files(&path, recursive, out)?;
+            }
+        } else if path.is_file() {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Canvas references
+//
+// Canvas nodes store media as `convertFileSrc` URLs (percent-encoded absolute
+// paths) or, when placed via MCP, as raw paths. The saved `canvas.json` is
+// authoritative: the bridge's canvas_read deliberately omits media URLs.
+// ---------------------------------------------------------------------------
+
+/// A canvas node whose content points at an asset.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasReference {
+    pub asset_id: String,
+    pub project_id: String,
+    pub node_id: String,
+    pub node_title: Option<String>,
+}
+
+impl AssetLibrary {
+    /// Nodes in each asset's project canvas that reference the asset's file.
+    pub fn find_canvas_references(&self, assets: &[AssetRow]) -> Vec<CanvasReference> {
+        let mut canvases: HashMap<&str, Option<serde_json::Value>> = HashMap::new();
+        let mut refs = Vec::new();
+        for asset in assets {
+            let canvas = canvases
+                .entry(asset.project_id.as_str())
+                .or_insert_with(|| self.read_canvas(&asset.project_id));
+            let Some(canvas) = canvas else { continue };
+            for node in canvas["nodes"].as_array().into_iter().flatten() {
+                if references_path(node, &asset.file_path) {
+                    refs.push(CanvasReference {
+                        asset_id: asset.id.clone(),
+                        project_id: asset.project_id.clone(),
+                        node_id: node["id"].as_str().unwrap_or_default().to_string(),
+                        node_title: node["data"]["uiInfo"]["title"].as_str().map(String::from),
+                    });
+                }
+            }
+        }
+        refs
+    }
+
+    fn read_canvas(&self, project_id: &str) -> Option<serde_json::Value> {
+        let path = self.project_dir(project_id).join("canvas.json");
+        let text = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!(path = %path.display(), "unreadable canvas file: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Whether any string inside `value` names exactly `file_path`, either as a
+/// raw path or as the path of a (percent-encoded) URL.
+fn references_path(value: &serde_json::Value, file_path: &str) -> bool {
+    let needle = normalize_path(file_path);
+    let needle = needle.trim_start_matches('/');
+    let mut stack = vec![value];
+    while let Some(v) = stack.pop() {
+        match v {
+            serde_json::Value::String(s) if referenced_path(s).trim_start_matches('/') == needle => {
+                return true;
+            }
+            serde_json::Value::Array(items) => stack.extend(items),
+            serde_json::Value::Object(map) => stack.extend(map.values()),
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The filesystem path a canvas string points at: the decoded path part of
+/// `asset://localhost/…`-style URLs, or the string itself.
+fn referenced_path(s: &str) -> String {
+    let path = match s.split_once("://") {
+        Some((_, rest)) => {
+            let rest = rest.split(['?', '#']).next().unwrap_or_default();
+            rest.split_once('/').map_or("", |(_, path)| path)
+        }
+        None => s,
+    };
+    normalize_path(&percent_decode(path))
+}
+
+fn normalize_path(s: &str) -> String {
+    s.replace('\\', "/")
+}
+
+fn percent_decode(s: &str) -> String {
+    if !s.contains('%') {
+        return s.to_string();
+    }
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = s.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// ---------------------------------------------------------------------------
+// Deletion
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteOutcome {
+    /// Asset rows removed. Empty when confirmation is required.
+    pub deleted: Vec<String>,
+    /// Requested IDs with no asset row.
+    pub not_found: Vec<String>,
+    /// Canvas nodes that reference the requested assets.
+    pub references: Vec<CanvasReference>,
+    /// Nothing was deleted because `references` is non-empty and the call
+    /// was not confirmed. Repeat with `confirm = true` to proceed.
+    pub requires_confirmation: bool,
+    /// The assets went to the trash rather than being deleted for good.
+    pub trashed: bool,
+    /// Files (assets, thumbnails, posters) removed from their place on
+    /// disk — moved into the trash when `trashed` is set.
+    pub removed_files: Vec<String>,
+    /// Files kept because another live asset row still points at them.
+    pub shared_files: Vec<String>,
+    /// Non-fatal errors, e.g. a file that could not be removed.
+    pub warnings: Vec<String>,
+}
+
+impl AssetLibrary {
+    /// Delete assets with their files — into the trash unless the retention
+    /// period is 0. If any canvas node references one of the assets, nothing
+    /// is deleted unless `confirm` is set.
+    pub fn delete_assets(&self, ids: &[String], confirm: bool) -> Result<DeleteOutcome> {
+        let assets = self.lock()?.get_assets_by_ids(ids)?;
+        let mut outcome = DeleteOutcome {
+            not_found: ids
+                .iter()
+                .filter(|id| !assets.iter().any(|a| &a.id == *id))
+                .cloned()
+                .collect(),
+            references: self.find_canvas_references(&assets),
+            ..Default::default()
+        };
+        if !outcome.references.is_empty() && !confirm {
+            outcome.requires_confirmation = true;
+            return Ok(outcome);
+        }
+
+        // Remove rows first, then only files no remaining row points at
+        // (content-addressed storage shares one file between identical assets).
+        outcome.trashed = self.trash_retention_days > 0;
+        let mut orphaned = Vec::new();
+        {
+            let db = self.lock()?;
+            let found: Vec<String> = assets.iter().map(|a| a.id.clone()).collect();
+            if outcome.trashed {
+                let deleted_at = chrono::Utc::now().to_rfc3339();
+                db.trash_asset_rows(&found, &deleted_at).context("failed to trash asset rows")?;
+            } else {
+                db.delete_asset_rows(&found).context("failed to delete asset rows")?;
+            }
+            for asset in &assets {
+                // A trashed row may still need the file to be restored
+                let refs = if outcome.trashed {
+                    db.count_live_asset_file_refs(&asset.file_path)?
+                } else {
+                    db.count_asset_file_refs(&asset.file_path)?
+                };
+                if refs == 0 {
+                    orphaned.push(asset);
+                } else {
+                    outcome.shared_files.push(asset.file_path.clone());
+                }
+            }
+            outcome.deleted = found;
+        }
+
+        for asset in orphaned {
+            if outcome.trashed {
+                let moved = trash::stash_asset_files(self, asset, &mut outcome.warnings);
+                outcome.removed_files.extend(moved);
+                continue;
+            }
+            let files = std::iter::once(asset.file_path.as_str())
+                .chain(asset.thumbnail_path.as_deref())
+                .chain(asset.poster_path.as_deref());
+            for file in files {
+                match std::fs::remove_file(file) {
+                    Ok(()) => outcome.removed_files.push(file.to_string()),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => outcome.warnings.push(format!("failed to remove {file}: {e}")),
+                }
+            }
+        }
+        outcome.shared_files.dedup();
+        Ok(outcome)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Moving and copying between projects
+//
+// A moved asset keeps its row — ID, tags, collections and lineage links stay
+// attached — and only its location changes. A copy is a new row with the
+// same generation metadata, tags and sources. A file another row still uses
+// (content-addressed storage) is copied, never moved out from under it.
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferOutcome {
+    /// The moved rows, or the new rows created by a copy, in request order.
+    pub assets: Vec<AssetRow>,
+    /// Requested IDs with no asset row.
+    pub not_found: Vec<String>,
+    /// Requested assets already in the target project; left alone.
+    pub skipped: Vec<String>,
+    /// Canvas nodes whose media would stop resolving after a move.
+    pub references: Vec<CanvasReference>,
+    /// Nothing was moved because `references` is non-empty and the call was
+    /// not confirmed. Repeat with `confirm = true` to proceed.
+    pub requires_confirmation: bool,
+    /// Assets that failed on their own; the rest were still transferred.
+    pub warnings: Vec<String>,
+}
+
+impl AssetLibrary {
+    /// Move assets and their files into `target`. If a canvas node references
+    /// one of them, nothing is moved unless `confirm` is set. Blocking.
+    pub fn move_assets(&self, ids: &[String], target: &str, confirm: bool) -> Result<TransferOutcome> {
+        self.transfer_assets(ids, target, ImportMode::Move, confirm)
+    }
+
+    /// Duplicate assets and their files into `target`. Blocking.
+    pub fn copy_assets(&self, ids: &[String], target: &str) -> Result<TransferOutcome> {
+        self.transfer_assets(ids, target, ImportMode::Copy, true)
+    }
+
+    fn transfer_assets(&self, ids: &[String], target: &str, mode: ImportMode, confirm: bool) -> Result<TransferOutcome> {
+        if !self.project_dir(target).join("manifest.json").exists() {
+            bail!("project \"{target}\" does not exist");
+        }
+        let (skipped, assets): (Vec<AssetRow>, Vec<AssetRow>) = self
+            .lock()?
+            .get_assets_by_ids(ids)?
+            .into_iter()
+            .partition(|a| a.project_id == target);
+        let mut outcome = TransferOutcome {
+            not_found: ids
+                .iter()
+                .filter(|id| !assets.iter().chain(&skipped).any(|a| &a.id == *id))
+                .cloned()
+                .collect(),
+            skipped: skipped.into_iter().map(|a| a.id).collect(),
+            ..Default::default()
+        };
+        if mode == ImportMode::Move {
+            outcome.references = self.find_canvas_references(&assets);
+            if !outcome.references.is_empty() && !confirm {
+                outcome.requires_confirmation = true;
+                return Ok(outcome);
+            }
+        }
+
+        for asset in &assets {
+            match self.transfer_asset(asset, target, mode) {
+                Ok(row) => outcome.assets.push(row),
+                Err(e) => outcome.warnings.push(format!("{}: {e:#}", asset.id)),
+            }
+        }
+        Ok(outcome)
+    }
+
+    fn transfer_asset(&self, asset: &AssetRow, target: &str, mode: ImportMode) -> Result<AssetRow> {
+        let src = Path::new(&asset.file_path);
+        let ext = match src.extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext.to_string(),
+            None => MediaFormat::sniff_file(src)?
+                .map(|f| f.extension().to_string())
+                .with_context(|| format!("{}: unsupported format", src.display()))?,
+        };
+        let relocate = mode == ImportMode::Move && self.lock()?.count_asset_file_refs(&asset.file_path)? <= 1;
+        let stored = self.store.import_file(target, src, &ext, relocate)?;
+
+        let target_dir = self.project_dir(target);
+        let mut row = asset.clone();
+        row.project_id = target.to_string();
+        row.file_path = stored.path.to_string_lossy().to_string();
+        row.file_name = stored.file_name.clone();
+        row.file_size = Some(stored.size as i64);
+        row.sha256 = Some(stored.sha256.clone());
+        let mut carried = Vec::new();
+        let thumb_dest = thumbnail::thumbnail_path(&target_dir, &row.file_name);
+        row.thumbnail_path = asset.thumbnail_path.as_deref().and_then(|old| {
+            carry_preview(Path::new(old), &thumb_dest, relocate, &mut carried)
+        });
+        row.poster_path = asset.poster_path.as_deref().and_then(|old| {
+            let ext = Path::new(old).extension().and_then(|e| e.to_str()).unwrap_or("png");
+            let dest = thumbnail::poster_path(&target_dir, &row.file_name, ext);
+            carry_preview(Path::new(old), &dest, relocate, &mut carried)
+        });
+
+        let saved = self.lock().and_then(|db| match mode {
+            ImportMode::Move => db.update_asset_location(&row),
+            ImportMode::Copy => {
+                row.id = uuid::Uuid::new_v4().to_string();
+                db.insert_asset(&row)?;
+                db.copy_asset_sources(&asset.id, &row.id)?;
+                Ok(())
+            }
+        });
+        if let Err(e) = saved {
+            // Put files back where the unchanged row expects them
+            let files = std::iter::once((src.to_path_buf(), stored.path.clone(), stored.deduplicated)).chain(carried);
+            for (old, new, shared) in files {
+                if relocate && !old.exists() {
+                    if let Err(e) = std::fs::copy(&new, &old) {
+                        warn!(path = %old.display(), "failed to restore moved file: {e}");
+                    }
+                }
+                if !shared {
+                    let _ = std::fs::remove_file(&new);
+                }
+            }
+            return Err(e);
+        }
+        Ok(row)
+    }
+}
+
+/// Bring a thumbnail or poster along to `dest`, recording `(src, dest,
+/// false)` in `carried` for rollback. A missing preview is not an error — it
+/// is simply dropped from the row.
+fn carry_preview(src: &Path, dest: &Path, relocate: bool, carried: &mut Vec<(PathBuf, PathBuf, bool)>) -> Option<String> {
+    let result = dest
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            if relocate && std::fs::rename(src, dest).is_ok() {
+                return Ok(());
+            }
+            std::fs::copy(src, dest).map(|_| ())
+        });
+    match result {
+        Ok(()) => {
+            if relocate && src.exists() {
+                let _ = std::fs::remove_file(src);
+            }
+            carried.push((src.to_path_buf(), dest.to_path_buf(), false));
+            Some(dest.to_string_lossy().to_string())
+        }
+        Err(e) => {
+            warn!(path = %src.display(), "failed to carry preview: {e}");
+            None
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{TaskRow, TaskStatus, TaskType};
+    use crate::test_support::{add_asset, setup};
+
+    #[test]
+    fn referenced_assets_need_confirmation_before_deletion() {
+        let (root, lib) = setup();
+        let used = add_asset(&lib, "used", "a.png", b"data");
+        let free = add_asset(&lib, "free", "b.png", b"data");
+
+        // The canvas stores a convertFileSrc URL with the path percent-encoded
+        let encoded: String = used
+            .file_path
+            .bytes()
+            .map(|b| if b.is_ascii_alphanumeric() || b == b'.' { (b as char).to_string() } else { format!("%{b:02X}") })
+            .collect();
+        let canvas = serde_json::json!({
+            "nodes": [{
+                "id": "n1",
+                "data": {
+                    "uiInfo": { "title": "Hero" },
+                    "historys": [{ "result": { "type": "image", "url": format!("asset://localhost/{encoded}") } }]
+                }
+            }, {
+                // Only a prefix of `free`'s path: not a reference
+                "id": "n2",
+                "data": { "historys": [{ "result": { "url": format!("{}.thumb.jpg", free.file_path) } }] }
+            }],
+            "edges": []
+        });
+        std::fs::write(lib.project_dir("p1").join("canvas.json"), canvas.to_string()).unwrap();
+
+        let ids = vec!["used".to_string(), "free".to_string(), "ghost".to_string()];
+        let first = lib.delete_assets(&ids, false).unwrap();
+        assert!(first.requires_confirmation);
+        assert!(first.deleted.is_empty());
+        assert_eq!(first.not_found, vec!["ghost".to_string()]);
+        assert_eq!(first.references.len(), 1);
+        assert_eq!(first.references[0].node_id, "n1");
+        assert!(Path::new(&used.file_path).exists());
+
+        let second = lib.delete_assets(&ids, true).unwrap();
+        assert_eq!(second.deleted.len(), 2);
+        assert!(!Path::new(&used.file_path).exists());
+        assert!(!Path::new(&free.file_path).exists());
+        assert!(lib.db().lock().unwrap().get_assets_by_ids(&ids).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn shared_files_survive_until_last_row_is_deleted() {
+        let (root, lib) = setup();
+        let a = add_asset(&lib, "a", "same.png", b"data");
+        add_asset(&lib, "b", "same.png", b"data");
+
+        let first = lib.delete_assets(&["a".to_string()], false).unwrap();
+        assert_eq!(first.shared_files, vec![a.file_path.clone()]);
+        assert!(Path::new(&a.file_path).exists());
+
+        let second = lib.delete_assets(&["b".to_string()], false).unwrap();
+        assert_eq!(second.removed_files, vec![a.file_path.clone()]);
+        assert!(!Path::new(&a.file_path).exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn permanently_deleted_assets_are_not_backfilled_from_their_task() {
+        let (root, mut lib) = setup();
+        lib.set_trash_retention_days(0);
+        let kept = add_asset(&lib, "kept", "same.png", b"data");
+        {
+            let db = lib.db().lock().unwrap();
+            let now = chrono::Utc::now().to_rfc3339();
+            db.insert_task(&TaskRow {
+                id: "t1".into(),
+                project_id: "p1".into(),
+                task_type: TaskType::Image,
+                status: TaskStatus::Done,
+                input: r#"{"prompt":"harbour"}"#.into(),
+                output: Some(serde_json::json!({ "assetPath": kept.file_path }).to_string()),
+                ark_task_id: None,
+                error: None,
+                created_at: now.clone(),
+                updated_at: now,
+            })
+            .unwrap();
+            let generated = AssetRow { id: "gen".into(), task_id: Some("t1".into()), ..kept.clone() };
+            db.insert_asset(&generated).unwrap();
+        }
+
+        // The file is shared with `kept`, so it stays on disk
+        let outcome = lib.delete_assets(&["gen".to_string()], false).unwrap();
+        assert!(!outcome.trashed);
+        assert!(Path::new(&kept.file_path).exists());
+        let db = lib.db().lock().unwrap();
+        assert_eq!(db.backfill_assets_from_tasks().unwrap(), 0);
+        let ids: Vec<String> = db.list_all_assets(Some("p1")).unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec!["kept".to_string()]);
+        drop(db);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn folder_import_detects_formats_and_reports_each_file() {
+        let (root, lib) = setup();
+        let src = root.join("incoming");
+        std::fs::create_dir_all(src.join("nested")).unwrap();
+        let mut png = Vec::new();
+        image::RgbImage::new(40, 30)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        // Misnamed: the magic bytes decide the type and extension
+        std::fs::write(src.join("photo.jpg"), &png).unwrap();
+        std::fs::write(src.join("notes.png"), b"not an image").unwrap();
+        std::fs::write(src.join(".DS_Store"), b"junk").unwrap();
+        std::fs::write(src.join("nested/deep.png"), &png).unwrap();
+
+        let flat = lib.import_folder("p1", &src, false, ImportMode::Copy).unwrap();
+        assert_eq!((flat.imported, flat.failed), (1, 1));
+        let asset = flat.results[1].asset.as_ref().unwrap();
+        assert!(asset.file_name.ends_with(".png"));
+        assert_eq!((asset.asset_type.as_str(), asset.width, asset.height), ("image", Some(40), Some(30)));
+        assert!(asset.file_path.starts_with(&*lib.project_dir("p1").join("assets").to_string_lossy()));
+        assert!(flat.results[0].error.as_deref().unwrap().contains("unsupported format"));
+        assert!(src.join("photo.jpg").exists());
+
+        let moved = lib.import_folder("p1", &src.join("nested"), true, ImportMode::Move).unwrap();
+        assert_eq!(moved.imported, 1);
+        assert!(!src.join("nested/deep.png").exists());
+
+        assert!(lib.import_file("missing", &src.join("photo.jpg"), ImportMode::Copy).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn moved_and_copied_assets_keep_metadata_and_lineage() {
+        let (root, lib) = setup();
+        std::fs::create_dir_all(root.join("projects/p2")).unwrap();
+        std::fs::write(root.join("projects/p2/manifest.json"), "{}").unwrap();
+        let source = add_asset(&lib, "src", "ref.png", b"data");
+        let mut hero = add_asset(&lib, "hero", "hero.png", b"data");
+        let thumb = thumbnail::thumbnail_path(&lib.project_dir("p1"), "hero.png");
+        std::fs::create_dir_all(thumb.parent().unwrap()).unwrap();
+        std::fs::write(&thumb, b"thumb").unwrap();
+        hero.thumbnail_path = Some(thumb.to_string_lossy().to_string());
+        hero.prompt = Some("a lighthouse".into());
+        hero.task_id = Some("t1".into());
+        {
+            let db = lib.db().lock().unwrap();
+            let now = chrono::Utc::now().to_rfc3339();
+            db.insert_task(&crate::db::TaskRow {
+                id: "t1".into(),
+                project_id: "p1".into(),
+                task_type: crate::db::TaskType::Image,
+                status: crate::db::TaskStatus::Done,
+                input: "{}".into(),
+                output: Some(serde_json::json!({ "assetPath": hero.file_path }).to_string()),
+                ark_task_id: None,
+                error: None,
+                created_at: now.clone(),
+                updated_at: now,
+            })
+            .unwrap();
+            db.delete_asset_rows(&["hero".to_string()]).unwrap();
+            db.insert_asset(&hero).unwrap();
+            db.add_tags(&["hero".to_string()], &["keep".to_string()]).unwrap();
+            db.add_asset_link("src", "hero", crate::db::LinkRelation::ReferenceOf, None).unwrap();
+        }
+
+        let copied = lib.copy_assets(&["hero".to_string(), "ghost".to_string()], "p2").unwrap();
+        assert_eq!(copied.not_found, vec!["ghost".to_string()]);
+        let copy = &copied.assets[0];
+        assert_ne!(copy.id, "hero");
+        assert_eq!((copy.project_id.as_str(), copy.prompt.as_deref()), ("p2", Some("a lighthouse")));
+        assert_eq!(copy.tags, vec!["keep".to_string()]);
+        assert!(Path::new(&copy.file_path).starts_with(lib.project_dir("p2")));
+        assert!(Path::new(copy.thumbnail_path.as_deref().unwrap()).exists());
+        assert!(Path::new(&hero.file_path).exists() && thumb.exists());
+        let lineage = lib.db().lock().unwrap().get_asset_lineage(&copy.id).unwrap().unwrap();
+        assert_eq!(lineage.ancestors[0].id, source.id);
+
+        let moved = lib.move_assets(&["hero".to_string()], "p2", false).unwrap();
+        let row = &moved.assets[0];
+        assert_eq!((row.id.as_str(), row.project_id.as_str()), ("hero", "p2"));
+        assert!(Path::new(&row.file_path).exists());
+        assert!(!Path::new(&hero.file_path).exists() && !thumb.exists());
+        let db = lib.db().lock().unwrap();
+        assert_eq!(db.get_assets_by_ids(&["hero".to_string()]).unwrap()[0].file_path, row.file_path);
+        assert_eq!(db.get_asset_lineage("hero").unwrap().unwrap().ancestors.len(), 1);
+        // The generating task follows its output
+        let task = db.get_task("t1").unwrap().unwrap();
+        assert_eq!(task.project_id, "p2");
+        let output: serde_json::Value = serde_json::from_str(task.output.as_deref().unwrap()).unwrap();
+        assert_eq!(output["assetPath"], row.file_path.as_str());
+        drop(db);
+
+        let again = lib.move_assets(&["hero".to_string()], "p2", false).unwrap();
+        assert_eq!(again.skipped, vec!["hero".to_string()]);
+        assert!(lib.copy_assets(&["src".to_string()], "missing").is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -8,6 +8,9 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 
+use crate::db::AssetFilter;
+use crate::library::AssetLibrary;
+use crate::similar;
 use crate::tasks::{ImageParams, TaskQueue, VideoParams};
 
 // ---------------------------------------------------------------------------
@@ -144,6 +147,15 @@ pub struct GenerateImageParams {
     /// Image size (e.g. "2K", "2048x2048"). Defaults to "2K".
     #[serde(default)]
     pub size: Option<String>,
+    /// Library image asset IDs to use as references (up to 14).
+    #[serde(default)]
+    pub reference_asset_ids: Vec<String>,
+    /// Asset ID this image is a variation of (recorded as lineage).
+    #[serde(default)]
+    pub variant_of: Option<String>,
+    /// Asset ID this image retries (recorded as lineage).
+    #[serde(default)]
+    pub retry_of: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -167,6 +179,15 @@ pub struct GenerateVideoParams {
     /// Duration in seconds (2-12). Defaults to 5.
     #[serde(default)]
     pub duration: Option<i32>,
+    /// Library image asset ID to use as the first frame.
+    #[serde(default)]
+    pub first_frame_asset_id: Option<String>,
+    /// Asset ID this video is a variation of (recorded as lineage).
+    #[serde(default)]
+    pub variant_of: Option<String>,
+    /// Asset ID this video retries (recorded as lineage).
+    #[serde(default)]
+    pub retry_of: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -175,6 +196,136 @@ pub struct TaskStatusParams {
     pub task_id: String,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListAssetsParams {
+    /// Only assets of this project.
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// "image" or "video".
+    #[serde(default)]
+    pub asset_type: Option<String>,
+    /// Full-text filter over prompt, notes, tags and file name.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Only assets carrying all of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Only assets in this collection.
+    #[serde(default)]
+    pub collection_id: Option<String>,
+    /// Only favorites (true) or non-favorites (false).
+    #[serde(default)]
+    pub favorite: Option<bool>,
+    /// Only assets rated at least this (1-5).
+    #[serde(default)]
+    pub min_rating: Option<i32>,
+    /// Only images with a dominant color near this one, as "#rrggbb".
+    #[serde(default)]
+    pub color: Option<String>,
+    /// How near `color` must be, as ΔE in Lab space: ~10 is the same color, ~25 the same hue family. Defaults to 20.
+    #[serde(default)]
+    pub color_distance: Option<f64>,
+    /// Max results. Defaults to 50.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchAssetsParams {
+    /// Words match as prefixes; "quoted phrases" match exactly; -word excludes.
+    /// Chinese and Japanese text is matched character by character, in order.
+    pub query: String,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// "image" or "video".
+    #[serde(default)]
+    pub asset_type: Option<String>,
+    /// Only assets carrying all of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Max results. Defaults to 20.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TagAssetsParams {
+    pub asset_ids: Vec<String>,
+    /// Tag names, e.g. "hero-shot-candidates". Missing tags are created.
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RateAssetsParams {
+    pub asset_ids: Vec<String>,
+    /// 1-5 stars, or 0 to clear the rating. Omit to leave unchanged.
+    #[serde(default)]
+    pub rating: Option<i32>,
+    /// Mark or unmark as favorite. Omit to leave unchanged.
+    #[serde(default)]
+    pub favorite: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateCollectionParams {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CollectionAssetsParams {
+    pub collection_id: String,
+    pub asset_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AssetLineageParams {
+    pub asset_id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FindSimilarAssetsParams {
+    /// Image asset to compare against.
+    pub asset_id: String,
+    /// Maximum Hamming distance between perceptual hashes (0-64). Defaults to 10.
+    #[serde(default)]
+    pub threshold: Option<u32>,
+    /// Only search this project. Defaults to the whole library.
+    #[serde(default)]
+    pub project_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ClusterDuplicatesParams {
+    /// Only cluster this project. Defaults to the whole library.
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// Maximum Hamming distance between perceptual hashes (0-64). Defaults to 10.
+    #[serde(default)]
+    pub threshold: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DeleteAssetsParams {
+    /// Asset IDs to delete.
+    pub asset_ids: Vec<String>,
+    /// Delete even if canvas nodes still reference the assets. Defaults to false.
+    #[serde(default)]
+    pub confirm: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TransferAssetsParams {
+    /// Asset IDs to move or copy.
+    pub asset_ids: Vec<String>,
+    /// Target project ID.
+    pub project_id: String,
+    /// Move even if canvas nodes still reference the assets (move_assets only). Defaults to false.
+    #[serde(default)]
+    pub confirm: Option<bool>,
+}
+
 // ---------------------------------------------------------------------------
 // MCP Server
 // ---------------------------------------------------------------------------
@@ -182,6 +333,7 @@ pub struct TaskStatusParams {
 #[derive(Clone)]
 pub struct SeedCanvasMcp {
     task_queue: Arc<TaskQueue>,
+    library: AssetLibrary,
     canvas_tx: Option<mpsc::Sender<CanvasIpcRequest>>,
     tool_router: ToolRouter<Self>,
 }
@@ -189,15 +341,52 @@ pub struct SeedCanvasMcp {
 impl SeedCanvasMcp {
     pub fn new(
         task_queue: Arc<TaskQueue>,
+        library: AssetLibrary,
         canvas_tx: Option<mpsc::Sender<CanvasIpcRequest>>,
     ) -> Self {
         Self {
             task_queue,
+            library,
             canvas_tx,
             tool_router: Self::tool_router(),
         }
     }
 
+    /// Run a DB call and return its result as JSON tool output.
+    fn db_tool<T: Serialize>(&self, f: impl FnOnce(&crate::db::Db) -> anyhow::Result<T>) -> CallToolResult {
+        let result = self
+            .library
+            .db()
+            .lock()
+            .map_err(|e| anyhow::anyhow!("db lock: {e}"))
+            .and_then(|db| f(&db));
+        match result {
+            Ok(value) => CallToolResult::success(vec![Content::text(
+                serde_json::to_string(&value).unwrap_or_default(),
+            )]),
+            Err(e) => CallToolResult::error(vec![Content::text(format!("{e:#}"))]),
+        }
+    }
+
+    /// Run a blocking library call off the async runtime and return its
+    /// result as JSON tool output. `action` completes "Failed to …" in errors.
+    async fn library_tool<T: Serialize + Send + 'static>(
+        &self,
+        action: &str,
+        f: impl FnOnce(&AssetLibrary) -> anyhow::Result<T> + Send + 'static,
+    ) -> Result<CallToolResult, ErrorData> {
+        let library = self.library.clone();
+        let result = tokio::task::spawn_blocking(move || f(&library))
+            .await
+            .map_err(|e| ErrorData::internal_error(format!("{action} task failed: {e}"), None))?;
+        Ok(match result {
+            Ok(value) => CallToolResult::success(vec![Content::text(
+                serde_json::to_string(&value).unwrap_or_default(),
+            )]),
+            Err(e) => CallToolResult::error(vec![Content::text(format!("Failed to {action}: {e:#}"))]),
+        })
+    }
+
     /// Return a reference to the canvas IPC sender, or an MCP error if the app isn't running.
     fn require_canvas_tx(&self) -> Result<&mpsc::Sender<CanvasIpcRequest>, ErrorData> {
         self.canvas_tx.as_ref().ok_or_else(|| {
@@ -288,6 +477,7 @@ impl SeedCanvasMcp {
         Models: doubao-seedream-5-0-260128 (default), doubao-seedream-5-0-lite-260128, \
         doubao-seedream-4-5-251128, doubao-seedream-4-0-250828. \
         Sizes: 1K, 2K (default), 3K, 4K, or pixel dimensions like 2048x2048. \
+        Pass reference_asset_ids to generate from library images. \
         Requires the SeedCanvas app to be running.")]
     async fn generate_image(
         &self,
@@ -301,6 +491,9 @@ impl SeedCanvasMcp {
             model: params.model,
             node_id: params.node_id,
             size: params.size,
+            reference_asset_ids: params.reference_asset_ids,
+            variant_of: params.variant_of,
+            retry_of: params.retry_of,
         };
 
         match self.task_queue.submit_image(image_params) {
@@ -325,6 +518,7 @@ impl SeedCanvasMcp {
         Follow the Video Prompt Craft guidelines in server instructions. \
         Models: doubao-seedance-1-5-pro-251215 (default), doubao-seedance-1-0-pro-250528. \
         Resolutions: 480p, 720p (default), 1080p. Ratios: 16:9 (default), 9:16, 4:3, 1:1. Duration: 2-12s. \
+        Pass first_frame_asset_id to animate a library image. \
         Requires the SeedCanvas app to be running.")]
     async fn generate_video(
         &self,
@@ -340,6 +534,9 @@ impl SeedCanvasMcp {
             resolution: params.resolution,
             ratio: params.ratio,
             duration: params.duration,
+            first_frame_asset_id: params.first_frame_asset_id,
+            variant_of: params.variant_of,
+            retry_of: params.retry_of,
         };
 
         match self.task_queue.submit_video(video_params) {
@@ -360,7 +557,7 @@ impl SeedCanvasMcp {
     }
 
     #[tool(description = "Check the status of a generation task (image or video). \
-        Returns status (pending/running/done/failed), output details on completion, \
+        Returns status (pending/running/queued_remote/downloading/done/failed/cancelled), output details on completion, \
         or error message on failure. \
         Requires the SeedCanvas app to be running.")]
     async fn task_status(
@@ -395,6 +592,188 @@ impl SeedCanvasMcp {
             ))])),
         }
     }
+
+    #[tool(description = "List assets in the SeedCanvas library, newest first, with their tags, rating, favorite flag \
+        and dominant color palette. Filter by project, type, prompt text, tags (all must match), collection, favorite, \
+        minimum rating or a color (\"#rrggbb\") the image's palette should contain. \
+        Does not require the SeedCanvas app to be running.")]
+    async fn list_assets(
+        &self,
+        Parameters(params): Parameters<ListAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let filter = AssetFilter {
+            project_id: params.project_id,
+            asset_type: params.asset_type,
+            query: params.query,
+            tags: params.tags,
+            collection_id: params.collection_id,
+            favorite: params.favorite,
+            min_rating: params.min_rating,
+            color: params.color,
+            color_distance: params.color_distance,
+        };
+        Ok(self.db_tool(|db| db.list_assets(&filter, params.limit.unwrap_or(50), 0)))
+    }
+
+    #[tool(description = "Search the asset library by prompt, notes, tags and file name, best match first. \
+        Each hit has the asset, a relevance score and a snippet with matches in <mark></mark>. \
+        Use it to find earlier generations to reuse or reference. \
+        Does not require the SeedCanvas app to be running.")]
+    async fn search_assets(
+        &self,
+        Parameters(params): Parameters<SearchAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let filter = AssetFilter {
+            project_id: params.project_id,
+            asset_type: params.asset_type,
+            tags: params.tags,
+            ..Default::default()
+        };
+        Ok(self.db_tool(|db| db.search_assets(&params.query, &filter, params.limit.unwrap_or(20), 0)))
+    }
+
+    #[tool(description = "Tag assets, e.g. mark generated images as \"hero-shot-candidates\" for later review. \
+        Tags are created on first use and matched case-insensitively.")]
+    async fn tag_assets(
+        &self,
+        Parameters(params): Parameters<TagAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.add_tags(&params.asset_ids, &params.tags).map(|_| "ok")))
+    }
+
+    #[tool(description = "Remove tags from assets.")]
+    async fn untag_assets(
+        &self,
+        Parameters(params): Parameters<TagAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.remove_tags(&params.asset_ids, &params.tags).map(|_| "ok")))
+    }
+
+    #[tool(description = "List all asset tags with how many assets carry each.")]
+    async fn list_tags(&self) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.list_tags()))
+    }
+
+    #[tool(description = "Set the star rating (1-5, 0 clears) and/or favorite flag of assets.")]
+    async fn rate_assets(
+        &self,
+        Parameters(params): Parameters<RateAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| {
+            if let Some(rating) = params.rating {
+                db.set_asset_rating(&params.asset_ids, Some(rating).filter(|r| *r != 0))?;
+            }
+            if let Some(favorite) = params.favorite {
+                db.set_asset_favorite(&params.asset_ids, favorite)?;
+            }
+            Ok("ok")
+        }))
+    }
+
+    #[tool(description = "List asset collections (id, name, description, asset count).")]
+    async fn list_collections(&self) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.list_collections()))
+    }
+
+    #[tool(description = "Create an asset collection. Returns its id for add_to_collection.")]
+    async fn create_collection(
+        &self,
+        Parameters(params): Parameters<CreateCollectionParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.create_collection(&params.name, params.description.as_deref())))
+    }
+
+    #[tool(description = "Add assets to a collection. Returns how many were newly added.")]
+    async fn add_to_collection(
+        &self,
+        Parameters(params): Parameters<CollectionAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.add_to_collection(&params.collection_id, &params.asset_ids)))
+    }
+
+    #[tool(description = "Remove assets from a collection (the assets themselves are kept).")]
+    async fn remove_from_collection(
+        &self,
+        Parameters(params): Parameters<CollectionAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.remove_from_collection(&params.collection_id, &params.asset_ids)))
+    }
+
+    #[tool(description = "Trace where an asset came from and what was made from it. \
+        Returns ancestors and descendants (nearest first) and the links between them, each with a relation: \
+        reference_of / first_frame_of (source was an input of the derived asset), variant_of / retry_of. \
+        Returns null if the asset does not exist.")]
+    async fn get_asset_lineage(
+        &self,
+        Parameters(params): Parameters<AssetLineageParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        Ok(self.db_tool(|db| db.get_asset_lineage(&params.asset_id)))
+    }
+
+    #[tool(description = "Find images that look like a given image asset (near duplicates by perceptual hash), \
+        closest first, each with its Hamming distance (0 = visually identical). Check this before re-generating \
+        a variation to avoid piling up near-identical results.")]
+    async fn find_similar_assets(
+        &self,
+        Parameters(params): Parameters<FindSimilarAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        self.library_tool("find similar assets", move |library| {
+            similar::find_similar(library, &params.asset_id, params.threshold, params.project_id.as_deref())
+        })
+        .await
+    }
+
+    #[tool(description = "Group near-duplicate images in a project or the whole library into clusters. \
+        Each cluster names a suggested asset to keep (favorite, then highest rated, then largest, then oldest) \
+        and lists every member with its distance to it.")]
+    async fn cluster_duplicate_assets(
+        &self,
+        Parameters(params): Parameters<ClusterDuplicatesParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        self.library_tool("cluster duplicates", move |library| {
+            similar::cluster_duplicates(library, params.project_id.as_deref(), params.threshold)
+        })
+        .await
+    }
+
+    #[tool(description = "Delete assets from the library: their records, files and thumbnails go to the trash, \
+        where they can be restored until the retention period ends — unless trash retention is set to 0 days, \
+        in which case they are deleted permanently. The result's `trashed` field says which happened. \
+        If any saved canvas node still uses one of the assets, nothing is deleted and the referencing nodes are returned \
+        with requiresConfirmation=true — ask the user, then call again with confirm=true. \
+        Does not require the SeedCanvas app to be running.")]
+    async fn delete_assets(
+        &self,
+        Parameters(params): Parameters<DeleteAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let confirm = params.confirm.unwrap_or(false);
+        self.library_tool("delete assets", move |library| library.delete_assets(&params.asset_ids, confirm)).await
+    }
+
+    #[tool(description = "Move assets into another project: their files, thumbnails and records move, \
+        keeping IDs, prompts, tags, collections and lineage. If a saved canvas node still uses one of them, \
+        nothing is moved and the referencing nodes are returned with requiresConfirmation=true — \
+        ask the user, then call again with confirm=true.")]
+    async fn move_assets(
+        &self,
+        Parameters(params): Parameters<TransferAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let confirm = params.confirm.unwrap_or(false);
+        self.library_tool("move assets", move |library| {
+            library.move_assets(&params.asset_ids, &params.project_id, confirm)
+        })
+        .await
+    }
+
+    #[tool(description = "Copy assets into another project as new assets with the same prompt, model, tags \
+        and lineage sources. Returns the new asset records (with new IDs).")]
+    async fn copy_assets(
+        &self,
+        Parameters(params): Parameters<TransferAssetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        self.library_tool("copy assets", move |library| library.copy_assets(&params.asset_ids, &params.project_id))
+            .await
+    }
 }
 
 #[tool_handler]
@@ -10,6 +10,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, Listener};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
@@ -73,8 +74,10 @@ struct McpResponseEvent {
 // Public entry point — called from lib.rs setup()
 // ---------------------------------------------------------------------------
 
-pub async fn start(data_dir: PathBuf, app_handle: AppHandle) -> Result<()> {
-    let sock_path = data_dir.join("mcp.sock");
+/// `clients` tracks how many connections are open, so the app can refuse
+/// operations a running seedcanvas-mcp would not survive.
+pub async fn start(app_dir: PathBuf, app_handle: AppHandle, clients: Arc<AtomicUsize>) -> Result<()> {
+    let sock_path = app_dir.join("mcp.sock");
 
     // Clean up stale socket
     if sock_path.exists() {
@@ -127,11 +130,14 @@ pub async fn start(data_dir: PathBuf, app_handle: AppHandle) -> Result<()> {
             Ok((stream, _addr)) => {
                 let app = app_handle.clone();
                 let pending = Arc::clone(&pending);
+                let clients = Arc::clone(&clients);
 
+                clients.fetch_add(1, Ordering::SeqCst);
                 tokio::spawn(async move {
                     if let Err(e) = handle_connection(stream, app, pending).await {
                         warn!("MCP bridge connection ended: {e:#}");
                     }
+                    clients.fetch_sub(1, Ordering::SeqCst);
                 });
             }
             Err(e) => {
@@ -0,0 +1,8 @@
+//! Media file inspection shared by generated and imported assets.
+
+pub mod palette;
+pub mod perceptual;
+pub mod probe;
+pub mod provenance;
+pub mod thumbnail;
+pub mod transcode;
@@ -0,0 +1,224 @@
+//! Dominant-color palettes for searching the library by color.
+//!
+//! The image is shrunk to at most [`SAMPLE_EDGE`] pixels a side, converted
+//! to CIE Lab (D65) and clustered with k-means into up to
+//! [`PALETTE_SIZE`] colors. Lab makes Euclidean distance roughly match
+//! perceived difference (ΔE76), so both the clustering and the color filter
+//! on `list_assets` work in it: ΔE below ~10 reads as the same color at a
+//! glance, ~25 as the same hue family.
+//!
+//! Initialisation is deterministic (farthest point from the mean outward),
+//! so the same image always yields the same palette. Decoding is CPU-bound;
+//! async callers should run [`extract`] under `spawn_blocking`.
+
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+
+/// Colors kept per image.
+pub const PALETTE_SIZE: usize = 5;
+/// Longest edge the image is shrunk to before clustering.
+pub const SAMPLE_EDGE: u32 = 64;
+/// ΔE within which a palette color matches a searched color by default.
+pub const DEFAULT_COLOR_DISTANCE: f64 = 20.0;
+const ITERATIONS: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteColor {
+    /// `#rrggbb`.
+    pub hex: String,
+    /// Share of the image's pixels, 0–1.
+    pub weight: f64,
+}
+
+impl PaletteColor {
+    pub fn lab(&self) -> Option<[f64; 3]> {
+        parse_hex(&self.hex).map(rgb_to_lab)
+    }
+}
+
+/// Palette of an encoded image (PNG, JPEG or WebP), heaviest color first.
+/// Fully transparent pixels are ignored.
+pub fn extract(image_bytes: &[u8]) -> Result<Vec<PaletteColor>> {
+    let img = image::load_from_memory(image_bytes).context("failed to decode image")?;
+    let small = img.resize(SAMPLE_EDGE, SAMPLE_EDGE, FilterType::Triangle).to_rgba8();
+    let pixels: Vec<[f64; 3]> = small
+        .pixels()
+        .filter(|p| p[3] > 0)
+        .map(|p| rgb_to_lab([p[0], p[1], p[2]]))
+        .collect();
+    Ok(kmeans(&pixels, PALETTE_SIZE)
+        .into_iter()
+        .map(|(lab, count)| PaletteColor {
+            hex: to_hex(lab_to_rgb(lab)),
+            weight: count as f64 / pixels.len() as f64,
+        })
+        .collect())
+}
+
+/// Cluster centres with their sizes, largest first. Empty clusters are
+/// dropped, so a flat image yields fewer than `k`.
+fn kmeans(points: &[[f64; 3]], k: usize) -> Vec<([f64; 3], usize)> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+    let mut centres = vec![mean(points.iter())];
+    while centres.len() < k {
+        let farthest = points
+            .iter()
+            .map(|p| (p, centres.iter().map(|c| distance_sq(p, c)).fold(f64::MAX, f64::min)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match farthest {
+            Some((p, d)) if d > 0.0 => centres.push(*p),
+            _ => break,
+        }
+    }
+
+    let mut assignment = vec![0usize; points.len()];
+    for _ in 0..ITERATIONS {
+        let mut changed = false;
+        for (i, p) in points.iter().enumerate() {
+            let nearest = nearest(p, &centres);
+            if nearest != assignment[i] {
+                assignment[i] = nearest;
+                changed = true;
+            }
+        }
+        for (c, centre) in centres.iter_mut().enumerate() {
+            let members = points.iter().zip(&assignment).filter(|(_, a)| **a == c).map(|(p, _)| p);
+            let m = mean(members);
+            // An emptied cluster keeps its centre
+            if m.iter().all(|v| v.is_finite()) {
+                *centre = m;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut clusters: Vec<([f64; 3], usize)> = centres
+        .iter()
+        .enumerate()
+        .map(|(c, centre)| (*centre, assignment.iter().filter(|a| **a == c).count()))
+        .filter(|(_, n)| *n > 0)
+        .collect();
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.1));
+    clusters
+}
+
+fn nearest(p: &[f64; 3], centres: &[[f64; 3]]) -> usize {
+    centres
+        .iter()
+        .enumerate()
+        .min_by(|a, b| distance_sq(p, a.1).total_cmp(&distance_sq(p, b.1)))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// NaN components when `points` is empty.
+fn mean<'a>(points: impl Iterator<Item = &'a [f64; 3]>) -> [f64; 3] {
+    let (mut sum, mut n) = ([0.0; 3], 0.0);
+    for p in points {
+        for i in 0..3 {
+            sum[i] += p[i];
+        }
+        n += 1.0;
+    }
+    sum.map(|s| s / n)
+}
+
+fn distance_sq(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum()
+}
+
+/// ΔE76 between two Lab colors.
+pub fn delta_e(a: [f64; 3], b: [f64; 3]) -> f64 {
+    distance_sq(&a, &b).sqrt()
+}
+
+// ---------------------------------------------------------------------------
+// Color conversion
+// ---------------------------------------------------------------------------
+
+/// `#rrggbb` or `rrggbb`, case-insensitive.
+pub fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+// D65 reference white
+const WHITE: [f64; 3] = [0.950_47, 1.0, 1.088_83];
+
+pub fn rgb_to_lab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|c| {
+        let c = f64::from(c) / 255.0;
+        if c <= 0.040_45 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    });
+    let xyz = [
+        0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+        0.212_672_9 * r + 0.715_152_2 * g + 0.072_175_0 * b,
+        0.019_333_9 * r + 0.119_192_0 * g + 0.950_304_1 * b,
+    ];
+    let f = |t: f64| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+    let [fx, fy, fz] = [f(xyz[0] / WHITE[0]), f(xyz[1] / WHITE[1]), f(xyz[2] / WHITE[2])];
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn lab_to_rgb(lab: [f64; 3]) -> [u8; 3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let (fx, fz) = (fy + lab[1] / 500.0, fy - lab[2] / 200.0);
+    let inv = |t: f64| if t.powi(3) > 216.0 / 24389.0 { t.powi(3) } else { (116.0 * t - 16.0) * 27.0 / 24389.0 };
+    let [x, y, z] = [inv(fx) * WHITE[0], inv(fy) * WHITE[1], inv(fz) * WHITE[2]];
+    let linear = [
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266_0 * x + 1.876_010_8 * y + 0.041_556_0 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    ];
+    linear.map(|c| {
+        let c = if c <= 0.003_130_8 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (c.clamp(0.0, 1.0) * 255.0).round() as u8
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    #[test]
+    fn finds_dominant_colors_by_share() {
+        // Three quarters red, one quarter blue
+        let img = RgbImage::from_fn(80, 40, |x, _| if x < 60 { image::Rgb([220, 30, 30]) } else { image::Rgb([20, 40, 200]) });
+        let mut bytes = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(img).write_to(&mut bytes, ImageFormat::Png).unwrap();
+
+        let palette = extract(bytes.get_ref()).unwrap();
+        assert!(palette.len() >= 2);
+        let red = palette[0].lab().unwrap();
+        assert!(delta_e(red, rgb_to_lab([220, 30, 30])) < 5.0);
+        assert!((palette[0].weight - 0.75).abs() < 0.1);
+        assert!(palette.iter().any(|c| delta_e(c.lab().unwrap(), rgb_to_lab([20, 40, 200])) < 5.0));
+        let total: f64 = palette.iter().map(|c| c.weight).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        assert_eq!(parse_hex("#FF8000"), Some([255, 128, 0]));
+        assert_eq!(parse_hex("ff8000"), Some([255, 128, 0]));
+        assert_eq!(parse_hex("#ff80"), None);
+        assert_eq!(lab_to_rgb(rgb_to_lab([12, 200, 99])), [12, 200, 99]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Perceptual hashes for spotting near-duplicate images.
+//!
+//! A difference hash (dHash) shrinks the image to 9×8 grayscale and records,
+//! for each of the 64 horizontally adjacent pairs, whether brightness falls
+//! to the right. Re-encoding, resizing and small edits flip few bits, so the
+//! Hamming distance between two hashes says how alike the images look:
+//! 0 is the same picture, up to about [`DEFAULT_THRESHOLD`] is a near
+//! duplicate, and unrelated images land around 32.
+//!
+//! Decoding is CPU-bound; async callers should run [`dhash`] under
+//! `spawn_blocking`.
+
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+
+/// Hamming distance up to which two images count as near duplicates.
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// dHash of an encoded image (PNG, JPEG or WebP).
+pub fn dhash(image_bytes: &[u8]) -> Result<u64> {
+    let img = image::load_from_memory(image_bytes).context("failed to decode image")?;
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(bit);
+        }
+    }
+    Ok(hash)
+}
+
+/// Number of differing bits.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn encode(img: RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(img).write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    fn gradient(w: u32, h: u32) -> RgbImage {
+        RgbImage::from_fn(w, h, |x, y| {
+            let v = ((x * 255 / w) as u8).wrapping_add((y * 40 / h) as u8);
+            image::Rgb([v, v / 2, 255 - v])
+        })
+    }
+
+    #[test]
+    fn near_duplicates_hash_close_and_different_images_far() {
+        let original = dhash(&encode(gradient(256, 192), ImageFormat::Png)).unwrap();
+        // Same picture at another size and in a lossy container
+        let resized = dhash(&encode(gradient(128, 96), ImageFormat::Jpeg)).unwrap();
+        assert!(distance(original, resized) <= DEFAULT_THRESHOLD);
+
+        let checker = RgbImage::from_fn(256, 192, |x, y| {
+            if (x / 16 + y / 16) % 2 == 0 { image::Rgb([255, 255, 255]) } else { image::Rgb([0, 0, 0]) }
+        });
+        let other = dhash(&encode(checker, ImageFormat::Png)).unwrap();
+        assert!(distance(original, other) > DEFAULT_THRESHOLD);
+
+        assert!(dhash(b"not an image").is_err());
+    }
+}
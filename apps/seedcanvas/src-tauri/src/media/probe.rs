@@ -0,0 +1,531 @@
+//! Media metadata from file headers — nothing is decoded.
+//!
+//! - PNG: `IHDR` dimensions
+//! - JPEG: first `SOFn` segment dimensions
+//! - WebP: `VP8 ` / `VP8L` / `VP8X` dimensions
+//! - MP4/MOV: the `moov` box — `mvhd` duration, and from the first video
+//!   track the `stsd` sample entry (codec, coded size, falling back to the
+//!   `tkhd` display size) and `mdhd` + `stts` for the frame rate.
+//!
+//! MP4 files are walked box by box with seeks, so a `moov` placed after a
+//! large `mdat` costs a few reads rather than loading the whole file.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// `moov` boxes larger than this are rejected rather than buffered.
+const MAX_MOOV_BYTES: u64 = 64 * 1024 * 1024;
+
+// ---------------------------------------------------------------------------
+// Format detection
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Mp4,
+}
+
+impl MediaFormat {
+    /// Identify the container from its magic bytes (the first 12 are enough).
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(MediaFormat::Png)
+        } else if head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(MediaFormat::Jpeg)
+        } else if head.len() >= 12 && &head[0..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+            Some(MediaFormat::Webp)
+        } else if head.len() >= 8 && matches!(&head[4..8], b"ftyp" | b"moov" | b"mdat" | b"wide" | b"free") {
+            Some(MediaFormat::Mp4)
+        } else {
+            None
+        }
+    }
+
+    /// [`sniff`](Self::sniff) the first bytes of a file.
+    pub fn sniff_file(path: &Path) -> Result<Option<Self>> {
+        let mut head = [0u8; 32];
+        let mut file = std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mut n = 0;
+        while n < head.len() {
+            match file.read(&mut head[n..])? {
+                0 => break,
+                read => n += read,
+            }
+        }
+        Ok(Self::sniff(&head[..n]))
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            MediaFormat::Png => "image/png",
+            MediaFormat::Jpeg => "image/jpeg",
+            MediaFormat::Webp => "image/webp",
+            MediaFormat::Mp4 => "video/mp4",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            MediaFormat::Png => "png",
+            MediaFormat::Jpeg => "jpg",
+            MediaFormat::Webp => "webp",
+            MediaFormat::Mp4 => "mp4",
+        }
+    }
+
+    /// Asset type as stored in `assets.type`.
+    pub fn asset_type(self) -> &'static str {
+        match self {
+            MediaFormat::Mp4 => "video",
+            _ => "image",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Probe results
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfo {
+    pub format: MediaFormat,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Seconds (video only).
+    pub duration: Option<f64>,
+    /// Frames per second (video only).
+    pub frame_rate: Option<f64>,
+    /// Short codec name, e.g. "h264", "hevc", "av1" (video only).
+    pub codec: Option<String>,
+}
+
+impl MediaInfo {
+    fn image(format: MediaFormat, width: u32, height: u32) -> Self {
+        Self {
+            format,
+            width: Some(width),
+            height: Some(height),
+            duration: None,
+            frame_rate: None,
+            codec: None,
+        }
+    }
+}
+
+/// Probe an in-memory file (e.g. freshly downloaded bytes).
+pub fn probe_bytes(bytes: &[u8]) -> Result<MediaInfo> {
+    probe_reader(&mut Cursor::new(bytes))
+}
+
+/// Probe a file on disk, reading only the headers.
+pub fn probe_file(path: &Path) -> Result<MediaInfo> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    probe_reader(&mut file).with_context(|| format!("failed to probe {}", path.display()))
+}
+
+pub fn probe_reader<R: Read + Seek>(r: &mut R) -> Result<MediaInfo> {
+    let mut head = [0u8; 12];
+    let n = read_up_to(r, &mut head)?;
+    let Some(format) = MediaFormat::sniff(&head[..n]) else {
+        bail!("unrecognized media format");
+    };
+    r.seek(SeekFrom::Start(0))?;
+    match format {
+        MediaFormat::Png => probe_png(r),
+        MediaFormat::Jpeg => probe_jpeg(r),
+        MediaFormat::Webp => probe_webp(r),
+        MediaFormat::Mp4 => probe_mp4(r),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Images
+// ---------------------------------------------------------------------------
+
+fn probe_png<R: Read>(r: &mut R) -> Result<MediaInfo> {
+    // 8-byte signature, then the IHDR chunk: length(4) "IHDR"(4) width(4) height(4)
+    let mut buf = [0u8; 24];
+    r.read_exact(&mut buf).context("truncated PNG header")?;
+    if &buf[12..16] != b"IHDR" {
+        bail!("PNG does not start with IHDR");
+    }
+    Ok(MediaInfo::image(MediaFormat::Png, be32(&buf[16..]), be32(&buf[20..])))
+}
+
+fn probe_jpeg<R: Read + Seek>(r: &mut R) -> Result<MediaInfo> {
+    r.seek(SeekFrom::Start(2))?;
+    loop {
+        // Markers are 0xFF followed by a non-0xFF code; extra 0xFF bytes are fill.
+        let mut byte = [0u8; 1];
+        r.read_exact(&mut byte).context("truncated JPEG")?;
+        if byte[0] != 0xFF {
+            continue;
+        }
+        let mut marker = 0xFF;
+        while marker == 0xFF {
+            r.read_exact(&mut byte).context("truncated JPEG")?;
+            marker = byte[0];
+        }
+        match marker {
+            // Standalone markers carry no length
+            0x00 | 0x01 | 0xD0..=0xD8 => continue,
+            0xD9 | 0xDA => bail!("JPEG has no frame header before scan data"),
+            _ => {}
+        }
+        let mut len = [0u8; 2];
+        r.read_exact(&mut len).context("truncated JPEG segment")?;
+        let len = u16::from_be_bytes(len) as i64;
+        let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_sof {
+            // precision(1) height(2) width(2)
+            let mut sof = [0u8; 5];
+            r.read_exact(&mut sof).context("truncated JPEG frame header")?;
+            let height = u16::from_be_bytes([sof[1], sof[2]]) as u32;
+            let width = u16::from_be_bytes([sof[3], sof[4]]) as u32;
+            return Ok(MediaInfo::image(MediaFormat::Jpeg, width, height));
+        }
+        r.seek(SeekFrom::Current(len - 2))?;
+    }
+}
+
+fn probe_webp<R: Read>(r: &mut R) -> Result<MediaInfo> {
+    // "RIFF" size "WEBP", then the first chunk header and up to 10 payload bytes
+    let mut buf = [0u8; 30];
+    r.read_exact(&mut buf).context("truncated WebP header")?;
+    let (width, height) = match &buf[12..16] {
+        b"VP8 " => {
+            // frame tag(3) start code 9d 01 2a(3) width(2) height(2), 14 bits each
+            if buf[23..26] != [0x9D, 0x01, 0x2A] {
+                bail!("invalid VP8 start code");
+            }
+            (le16(&buf[26..]) & 0x3FFF, le16(&buf[28..]) & 0x3FFF)
+        }
+        b"VP8L" => {
+            // signature 0x2f, then 14-bit width-1 and height-1 packed LSB first
+            if buf[20] != 0x2F {
+                bail!("invalid VP8L signature");
+            }
+            let bits = u32::from_le_bytes([buf[21], buf[22], buf[23], buf[24]]);
+            ((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1)
+        }
+        b"VP8X" => {
+            // flags(4) canvas width-1(3) canvas height-1(3)
+            (le24(&buf[24..]) + 1, le24(&buf[27..]) + 1)
+        }
+        other => bail!("unsupported WebP chunk {:?}", String::from_utf8_lossy(other)),
+    };
+    Ok(MediaInfo::image(MediaFormat::Webp, width, height))
+}
+
+// ---------------------------------------------------------------------------
+// MP4 / QuickTime
+// ---------------------------------------------------------------------------
+
+fn probe_mp4<R: Read + Seek>(r: &mut R) -> Result<MediaInfo> {
+    let end = r.seek(SeekFrom::End(0))?;
+    let mut pos = 0u64;
+    while pos + 8 <= end {
+        r.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 16];
+        r.read_exact(&mut header[..8])?;
+        let kind: [u8; 4] = header[4..8].try_into().unwrap();
+        let (header_len, size) = match be32(&header) {
+            0 => (8, end - pos),
+            1 => {
+                r.read_exact(&mut header[8..16])?;
+                (16, u64::from_be_bytes(header[8..16].try_into().unwrap()))
+            }
+            n => (8, n as u64),
+        };
+        let Some(next) = pos.checked_add(size).filter(|&next| next <= end) else {
+            bail!("MP4 box overruns the file");
+        };
+        if size < header_len {
+            bail!("malformed MP4 box");
+        }
+        if &kind == b"moov" {
+            let len = size - header_len;
+            if len > MAX_MOOV_BYTES {
+                bail!("moov box too large ({len} bytes)");
+            }
+            let mut moov = vec![0u8; len as usize];
+            r.read_exact(&mut moov).context("truncated moov box")?;
+            return Ok(parse_moov(&moov));
+        }
+        pos = next;
+    }
+    bail!("no moov box found")
+}
+
+fn parse_moov(moov: &[u8]) -> MediaInfo {
+    let mut info = MediaInfo {
+        format: MediaFormat::Mp4,
+        width: None,
+        height: None,
+        duration: None,
+        frame_rate: None,
+        codec: None,
+    };
+
+    if let Some((timescale, duration)) = child(moov, b"mvhd").and_then(parse_time_header) {
+        info.duration = seconds(duration, timescale);
+    }
+
+    let Some(track) = boxes(moov)
+        .filter(|(kind, _)| kind == b"trak")
+        .map(|(_, trak)| trak)
+        .find(|trak| handler_type(trak) == Some(*b"vide"))
+    else {
+        return info;
+    };
+
+    let mdia = child(track, b"mdia");
+    let stbl = mdia
+        .and_then(|m| child(m, b"minf"))
+        .and_then(|m| child(m, b"stbl"));
+
+    if let Some((codec, w, h)) = stbl.and_then(|s| child(s, b"stsd")).and_then(parse_stsd) {
+        info.codec = Some(codec_name(&codec));
+        if w > 0 && h > 0 {
+            info.width = Some(w);
+            info.height = Some(h);
+        }
+    }
+    if info.width.is_none() {
+        if let Some((w, h)) = child(track, b"tkhd").and_then(parse_tkhd) {
+            info.width = Some(w);
+            info.height = Some(h);
+        }
+    }
+
+    let media_time = mdia.and_then(|m| child(m, b"mdhd")).and_then(parse_time_header);
+    if let Some((timescale, duration)) = media_time {
+        if info.duration.is_none() {
+            info.duration = seconds(duration, timescale);
+        }
+        let samples = stbl.and_then(|s| child(s, b"stts")).and_then(stts_sample_count);
+        if let (Some(samples), Some(secs)) = (samples, seconds(duration, timescale)) {
+            if secs > 0.0 {
+                info.frame_rate = Some((samples as f64 / secs * 1000.0).round() / 1000.0);
+            }
+        }
+    }
+
+    info
+}
+
+/// Iterate the boxes directly inside `data` as (type, payload).
+fn boxes(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.len() < 8 {
+            return None;
+        }
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let (header_len, size) = match be32(rest) {
+            0 => (8, rest.len()),
+            1 if rest.len() >= 16 => (16, u64::from_be_bytes(rest[8..16].try_into().unwrap()) as usize),
+            1 => return None,
+            n => (8, n as usize),
+        };
+        if size < header_len || size > rest.len() {
+            return None;
+        }
+        let payload = &rest[header_len..size];
+        rest = &rest[size..];
+        Some((kind, payload))
+    })
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).find(|(k, _)| k == kind).map(|(_, payload)| payload)
+}
+
+fn handler_type(trak: &[u8]) -> Option<[u8; 4]> {
+    // hdlr: version/flags(4) pre_defined(4) handler_type(4)
+    let hdlr = child(child(trak, b"mdia")?, b"hdlr")?;
+    hdlr.get(8..12)?.try_into().ok()
+}
+
+/// `mvhd` / `mdhd` → (timescale, duration).
+fn parse_time_header(b: &[u8]) -> Option<(u32, u64)> {
+    match *b.first()? {
+        // version(1) flags(3) creation(8) modification(8) timescale(4) duration(8)
+        1 => Some((be32(b.get(20..24)?), u64::from_be_bytes(b.get(24..32)?.try_into().ok()?))),
+        // version(1) flags(3) creation(4) modification(4) timescale(4) duration(4)
+        _ => Some((be32(b.get(12..16)?), be32(b.get(16..20)?) as u64)),
+    }
+}
+
+/// `tkhd` → display (width, height), stored as 16.16 fixed point at the end.
+fn parse_tkhd(b: &[u8]) -> Option<(u32, u32)> {
+    let len = if *b.first()? == 1 { 96 } else { 84 };
+    let w = be32(b.get(len - 8..len - 4)?) >> 16;
+    let h = be32(b.get(len - 4..len)?) >> 16;
+    (w > 0 && h > 0).then_some((w, h))
+}
+
+/// `stsd` → (codec fourcc, coded width, coded height) of the first sample entry.
+fn parse_stsd(b: &[u8]) -> Option<([u8; 4], u32, u32)> {
+    // version/flags(4) entry_count(4), then sample entry boxes
+    let (codec, entry) = boxes(b.get(8..)?).next()?;
+    // reserved(6) data_ref_index(2) pre_defined/reserved(16) width(2) height(2)
+    let w = entry.get(24..26).map(|s| u16::from_be_bytes([s[0], s[1]]) as u32).unwrap_or(0);
+    let h = entry.get(26..28).map(|s| u16::from_be_bytes([s[0], s[1]]) as u32).unwrap_or(0);
+    Some((codec, w, h))
+}
+
+/// Total sample (frame) count from the `stts` time-to-sample table.
+fn stts_sample_count(b: &[u8]) -> Option<u64> {
+    // version/flags(4) entry_count(4), then (sample_count(4), sample_delta(4)) pairs
+    let count = be32(b.get(4..8)?) as usize;
+    let entries = b.get(8..8 + count.checked_mul(8)?)?;
+    Some(entries.chunks_exact(8).map(|e| be32(e) as u64).sum())
+}
+
+fn codec_name(fourcc: &[u8; 4]) -> String {
+    match fourcc {
+        b"avc1" | b"avc3" => "h264".into(),
+        b"hvc1" | b"hev1" => "hevc".into(),
+        b"av01" => "av1".into(),
+        b"vp09" => "vp9".into(),
+        b"mp4v" => "mpeg4".into(),
+        other => String::from_utf8_lossy(other).trim().to_string(),
+    }
+}
+
+fn seconds(duration: u64, timescale: u32) -> Option<f64> {
+    (timescale > 0 && duration > 0).then(|| duration as f64 / timescale as f64)
+}
+
+// ---------------------------------------------------------------------------
+// Byte helpers
+// ---------------------------------------------------------------------------
+
+fn read_up_to<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn le16(b: &[u8]) -> u32 {
+    u16::from_le_bytes([b[0], b[1]]) as u32
+}
+
+fn le24(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], 0])
+}
+
+// ---------------------------------------------------------------------------
+// Tests — synthetic headers for each supported container
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn full_box(kind: &[u8; 4], fields: &[u32]) -> Vec<u8> {
+        let payload: Vec<u8> = fields.iter().flat_map(|f| f.to_be_bytes()).collect();
+        mp4_box(kind, &payload)
+    }
+
+    fn sample_mp4(moov_after_mdat: bool) -> Vec<u8> {
+        // 5 s at 24 fps, 1920x1080 H.264
+        let mvhd = full_box(b"mvhd", &[0, 0, 0, 1000, 5000]);
+        let mut tkhd = vec![0u8; 84];
+        tkhd[76..80].copy_from_slice(&(1920u32 << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(1080u32 << 16).to_be_bytes());
+        let mdhd = full_box(b"mdhd", &[0, 0, 0, 12288, 61440]);
+        let hdlr = full_box(b"hdlr", &[0, 0, u32::from_be_bytes(*b"vide"), 0, 0, 0]);
+        let mut avc1 = vec![0u8; 78];
+        avc1[24..26].copy_from_slice(&1920u16.to_be_bytes());
+        avc1[26..28].copy_from_slice(&1080u16.to_be_bytes());
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"avc1", &avc1));
+        let stts = full_box(b"stts", &[0, 1, 120, 512]);
+        let stbl = mp4_box(b"stbl", &[mp4_box(b"stsd", &stsd), stts].concat());
+        let minf = mp4_box(b"minf", &stbl);
+        let mdia = mp4_box(b"mdia", &[mdhd, hdlr, minf].concat());
+        let trak = mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), mdia].concat());
+        let moov = mp4_box(b"moov", &[mvhd, trak].concat());
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\x02\0isomiso2avc1mp41");
+        let mdat = mp4_box(b"mdat", &[0u8; 4096]);
+        if moov_after_mdat {
+            [ftyp, mdat, moov].concat()
+        } else {
+            [ftyp, moov, mdat].concat()
+        }
+    }
+
+    #[test]
+    fn probes_image_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0]);
+        let info = probe_bytes(&png).unwrap();
+        assert_eq!((info.format, info.width, info.height), (MediaFormat::Png, Some(640), Some(480)));
+
+        // SOI, APP0 (skipped by length), SOF0 with 1024x768
+        let jpeg = [
+            &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x06, b'J', b'F', b'I', b'F'][..],
+            &[0xFF, 0xC0, 0x00, 0x11, 0x08, 0x03, 0x00, 0x04, 0x00, 0x03],
+        ]
+        .concat();
+        let info = probe_bytes(&jpeg).unwrap();
+        assert_eq!((info.format, info.width, info.height), (MediaFormat::Jpeg, Some(1024), Some(768)));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        webp.extend([0xFF, 0x07, 0x00, 0x37, 0x04, 0x00]); // 2048 x 1080
+        let info = probe_bytes(&webp).unwrap();
+        assert_eq!((info.format, info.width, info.height), (MediaFormat::Webp, Some(2048), Some(1080)));
+
+        assert!(probe_bytes(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn probes_mp4_moov_wherever_it_is() {
+        for moov_after_mdat in [false, true] {
+            let info = probe_bytes(&sample_mp4(moov_after_mdat)).unwrap();
+            assert_eq!(info.format, MediaFormat::Mp4);
+            assert_eq!((info.width, info.height), (Some(1920), Some(1080)));
+            assert_eq!(info.duration, Some(5.0));
+            assert_eq!(info.frame_rate, Some(24.0));
+            assert_eq!(info.codec.as_deref(), Some("h264"));
+        }
+    }
+
+    #[test]
+    fn rejects_mp4_boxes_that_overrun_the_file() {
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\x02\0isomiso2avc1mp41");
+        // 64-bit largesize pointing far past the end of the file
+        let mut huge = 1u32.to_be_bytes().to_vec();
+        huge.extend_from_slice(b"mdat");
+        huge.extend(u64::MAX.to_be_bytes());
+        assert!(probe_bytes(&[ftyp, huge].concat()).is_err());
+    }
+}
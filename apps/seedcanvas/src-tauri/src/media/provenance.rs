@@ -0,0 +1,751 @@
+//! Generation provenance embedded in the asset files themselves, so the
+//! prompt and settings travel with a file that leaves SeedCanvas.
+//!
+//! The record is an XMP packet (prompt as `dc:description`, the rest under
+//! the `seedcanvas:` namespace), stored where each format keeps XMP:
+//!
+//! - PNG: an `iTXt` chunk with keyword `XML:com.adobe.xmp`, plus plain
+//!   `Description` and `Software` `iTXt` chunks for viewers that ignore XMP
+//! - JPEG: an `APP1` segment with the XMP namespace header
+//! - WebP: an `XMP ` chunk, flagged in the `VP8X` header (one is added to
+//!   simple lossy/lossless files, which have none)
+//! - MP4: `moov/udta/XMP_`, plus `©cmt` and `©too` text atoms
+//!
+//! Chunks are inserted without re-encoding. Growing `moov` in front of `mdat`
+//! shifts the media data, so the `stco`/`co64` chunk offsets are rebased.
+//! [`read`] parses the packet back out; it also accepts a bare
+//! `dc:description` written by other tools.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::probe::MediaFormat;
+
+/// Value of `xmp:CreatorTool` and the `Software` / `©too` tags.
+pub const CREATOR_TOOL: &str = "SeedCanvas";
+
+const NS_SEEDCANVAS: &str = "urn:seedcanvas:provenance:1.0#";
+const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// `VP8X` header flag bits.
+const WEBP_FLAG_XMP: u8 = 0x04;
+const WEBP_FLAG_ALPHA: u8 = 0x10;
+/// Same cap as the probe: larger `moov` boxes are not buffered.
+const MAX_MOOV_BYTES: u64 = 64 * 1024 * 1024;
+/// Boxes this module owns inside `moov/udta`; replaced on every write.
+const UDTA_OWNED: [&[u8; 4]; 3] = [b"XMP_", b"\xA9cmt", b"\xA9too"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    pub prompt: Option<String>,
+    pub model: Option<String>,
+    pub seed: Option<i64>,
+    /// Output size, e.g. `"2048x2048"`.
+    pub size: Option<String>,
+    pub task_id: Option<String>,
+    /// RFC 3339.
+    pub created_at: Option<String>,
+    pub creator_tool: Option<String>,
+}
+
+/// `bytes` with `provenance` embedded, or `None` for formats without a writer.
+pub fn embed(bytes: &[u8], provenance: &Provenance) -> Result<Option<Vec<u8>>> {
+    let xmp = to_xmp(provenance);
+    match MediaFormat::sniff(bytes) {
+        Some(MediaFormat::Png) => embed_png(bytes, provenance, &xmp).map(Some),
+        Some(MediaFormat::Jpeg) => embed_jpeg(bytes, &xmp).map(Some),
+        Some(MediaFormat::Webp) => embed_webp(bytes, &xmp).map(Some),
+        Some(MediaFormat::Mp4) => embed_mp4(bytes, provenance, &xmp).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Provenance embedded in `bytes`, if any.
+pub fn read(bytes: &[u8]) -> Option<Provenance> {
+    let xmp = match MediaFormat::sniff(bytes)? {
+        MediaFormat::Png => read_png_xmp(bytes),
+        MediaFormat::Jpeg => read_jpeg_xmp(bytes),
+        MediaFormat::Webp => read_webp_xmp(bytes),
+        MediaFormat::Mp4 => read_mp4_xmp(bytes),
+    }?;
+    from_xmp(&String::from_utf8_lossy(xmp))
+}
+
+/// [`read`] a file. For MP4 only the `moov` box is loaded.
+pub fn read_file(path: &Path) -> Result<Option<Provenance>> {
+    if MediaFormat::sniff_file(path)? != Some(MediaFormat::Mp4) {
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        return Ok(read(&bytes));
+    }
+    let mut file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let len = file.metadata()?.len();
+    let mut pos = 0u64;
+    while pos + 8 <= len {
+        let mut header = [0u8; 16];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header[..8])?;
+        let size = match u32::from_be_bytes(header[0..4].try_into().unwrap()) {
+            0 => len - pos,
+            1 => {
+                file.read_exact(&mut header[8..16])?;
+                u64::from_be_bytes(header[8..16].try_into().unwrap())
+            }
+            n => u64::from(n),
+        };
+        let Some(end) = pos.checked_add(size).filter(|&end| size >= 8 && end <= len) else {
+            bail!("malformed MP4 box {:?} at byte {pos}", String::from_utf8_lossy(&header[4..8]));
+        };
+        if &header[4..8] == b"moov" {
+            if size > MAX_MOOV_BYTES {
+                bail!("moov box of {size} bytes is too large");
+            }
+            let mut moov = vec![0u8; size as usize];
+            file.seek(SeekFrom::Start(pos))?;
+            file.read_exact(&mut moov)?;
+            return Ok(read_mp4_xmp(&moov).and_then(|xmp| from_xmp(&String::from_utf8_lossy(xmp))));
+        }
+        pos = end;
+    }
+    Ok(None)
+}
+
+// ---------------------------------------------------------------------------
+// XMP
+// ---------------------------------------------------------------------------
+
+fn to_xmp(p: &Provenance) -> String {
+    let mut attrs = format!("\n    xmp:CreatorTool=\"{}\"", escape(p.creator_tool.as_deref().unwrap_or(CREATOR_TOOL)));
+    let fields = [
+        ("xmp:CreateDate", p.created_at.clone()),
+        ("seedcanvas:model", p.model.clone()),
+        ("seedcanvas:seed", p.seed.map(|s| s.to_string())),
+        ("seedcanvas:size", p.size.clone()),
+        ("seedcanvas:taskId", p.task_id.clone()),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            attrs.push_str(&format!("\n    {name}=\"{}\"", escape(&value)));
+        }
+    }
+    let description = p
+        .prompt
+        .as_deref()
+        .map(|prompt| {
+            format!(
+                "\n   <dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n  ",
+                escape(prompt)
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+         <rdf:Description rdf:about=\"\"\n    \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n    \
+         xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n    \
+         xmlns:seedcanvas=\"{NS_SEEDCANVAS}\"{attrs}>{description}</rdf:Description>\n \
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>"
+    )
+}
+
+fn from_xmp(xmp: &str) -> Option<Provenance> {
+    let p = Provenance {
+        prompt: description(xmp),
+        model: attribute(xmp, "seedcanvas:model"),
+        seed: attribute(xmp, "seedcanvas:seed").and_then(|s| s.parse().ok()),
+        size: attribute(xmp, "seedcanvas:size"),
+        task_id: attribute(xmp, "seedcanvas:taskId"),
+        created_at: attribute(xmp, "xmp:CreateDate"),
+        creator_tool: attribute(xmp, "xmp:CreatorTool"),
+    };
+    (p.prompt.is_some() || p.model.is_some()).then_some(p)
+}
+
+/// Value of `name="…"` anywhere in the packet.
+fn attribute(xmp: &str, name: &str) -> Option<String> {
+    let pattern = format!("{name}=\"");
+    let start = xmp.find(&pattern)? + pattern.len();
+    let end = start + xmp[start..].find('"')?;
+    Some(unescape(&xmp[start..end]))
+}
+
+/// The first `rdf:li` of `dc:description`.
+fn description(xmp: &str) -> Option<String> {
+    let block = &xmp[xmp.find("<dc:description")?..];
+    let li = &block[block.find("<rdf:li")?..];
+    let start = li.find('>')? + 1;
+    let end = li.find("</rdf:li>")?;
+    let text = unescape(li.get(start..end)?);
+    (!text.is_empty()).then_some(text)
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' => out.push_str("&#9;"),
+            // Not allowed in XML 1.0 at all
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|h| u32::from_str_radix(h, 16))
+                .or_else(|| entity.strip_prefix('#').map(|d| d.parse()))
+                .and_then(|n| n.ok())
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// ---------------------------------------------------------------------------
+// PNG
+// ---------------------------------------------------------------------------
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// `(type, data, whole chunk)`.
+type PngChunk<'a> = ([u8; 4], &'a [u8], &'a [u8]);
+
+/// Every chunk after the signature, up to `IEND`.
+fn png_chunks(bytes: &[u8]) -> Result<Vec<PngChunk<'_>>> {
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < bytes.len() {
+        let header = bytes.get(pos..pos + 8).context("truncated PNG chunk header")?;
+        let len = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let end = pos + 12 + len;
+        let chunk = bytes.get(pos..end).context("truncated PNG chunk")?;
+        let kind: [u8; 4] = header[4..8].try_into().unwrap();
+        chunks.push((kind, &chunk[8..8 + len], chunk));
+        pos = end;
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+    Ok(chunks)
+}
+
+fn itxt_keyword(data: &[u8]) -> &[u8] {
+    data.split(|&b| b == 0).next().unwrap_or_default()
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 12);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+    out
+}
+
+/// Uncompressed `iTXt` with no language tag.
+fn itxt(keyword: &str, text: &str) -> Vec<u8> {
+    let mut data = keyword.as_bytes().to_vec();
+    data.extend_from_slice(&[0, 0, 0, 0, 0]);
+    data.extend_from_slice(text.as_bytes());
+    png_chunk(b"iTXt", &data)
+}
+
+fn embed_png(bytes: &[u8], p: &Provenance, xmp: &str) -> Result<Vec<u8>> {
+    let mut inserted = vec![itxt(PNG_XMP_KEYWORD, xmp), itxt("Software", CREATOR_TOOL)];
+    if let Some(prompt) = &p.prompt {
+        inserted.push(itxt("Description", prompt));
+    }
+    let owned: [&[u8]; 3] = [PNG_XMP_KEYWORD.as_bytes(), b"Software", b"Description"];
+
+    let mut out = Vec::with_capacity(bytes.len() + inserted.iter().map(Vec::len).sum::<usize>());
+    out.extend_from_slice(PNG_SIGNATURE);
+    for (kind, data, chunk) in png_chunks(bytes)? {
+        if &kind == b"iTXt" && owned.contains(&itxt_keyword(data)) {
+            continue;
+        }
+        out.extend_from_slice(chunk);
+        // Right after the header, ahead of the image data
+        if &kind == b"IHDR" {
+            inserted.iter().for_each(|c| out.extend_from_slice(c));
+        }
+    }
+    Ok(out)
+}
+
+fn read_png_xmp(bytes: &[u8]) -> Option<&[u8]> {
+    png_chunks(bytes).ok()?.into_iter().find_map(|(kind, data, _)| {
+        if &kind != b"iTXt" || itxt_keyword(data) != PNG_XMP_KEYWORD.as_bytes() {
+            return None;
+        }
+        // keyword\0 flag method language\0 translated\0 text
+        let rest = &data[PNG_XMP_KEYWORD.len() + 1..];
+        if rest.first() != Some(&0) {
+            return None; // compressed XMP is allowed but never written by us
+        }
+        let rest = rest.get(2..)?;
+        let rest = &rest[rest.iter().position(|&b| b == 0)? + 1..];
+        Some(&rest[rest.iter().position(|&b| b == 0)? + 1..])
+    })
+}
+
+// ---------------------------------------------------------------------------
+// JPEG
+// ---------------------------------------------------------------------------
+
+/// `(marker, segment including marker and length)` up to the start of scan.
+fn jpeg_segments(bytes: &[u8]) -> Result<Vec<(u8, std::ops::Range<usize>)>> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    loop {
+        let header = bytes.get(pos..pos + 4).context("truncated JPEG segment")?;
+        if header[0] != 0xFF {
+            bail!("malformed JPEG segment at byte {pos}");
+        }
+        let marker = header[1];
+        if marker == 0xDA {
+            return Ok(segments);
+        }
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        segments.push((marker, pos..pos + 2 + len));
+        pos += 2 + len;
+    }
+}
+
+fn embed_jpeg(bytes: &[u8], xmp: &str) -> Result<Vec<u8>> {
+    let payload_len = JPEG_XMP_HEADER.len() + xmp.len();
+    if payload_len + 2 > u16::MAX as usize {
+        bail!("provenance too large for a JPEG APP1 segment");
+    }
+    let mut app1 = vec![0xFF, 0xE1];
+    app1.extend_from_slice(&((payload_len + 2) as u16).to_be_bytes());
+    app1.extend_from_slice(JPEG_XMP_HEADER);
+    app1.extend_from_slice(xmp.as_bytes());
+
+    let segments = jpeg_segments(bytes)?;
+    let mut out = Vec::with_capacity(bytes.len() + app1.len());
+    out.extend_from_slice(&bytes[..2]);
+    // JFIF/EXIF must stay first; the XMP segment follows them
+    let mut pos = 2;
+    let mut placed = false;
+    for (marker, range) in segments {
+        let is_xmp = marker == 0xE1 && bytes[range.start + 4..range.end].starts_with(JPEG_XMP_HEADER);
+        if !placed && marker != 0xE0 && (marker != 0xE1 || is_xmp) {
+            out.extend_from_slice(&app1);
+            placed = true;
+        }
+        if !is_xmp {
+            out.extend_from_slice(&bytes[range.clone()]);
+        }
+        pos = range.end;
+    }
+    if !placed {
+        out.extend_from_slice(&app1);
+    }
+    out.extend_from_slice(&bytes[pos..]);
+    Ok(out)
+}
+
+fn read_jpeg_xmp(bytes: &[u8]) -> Option<&[u8]> {
+    jpeg_segments(bytes).ok()?.into_iter().find_map(|(marker, range)| {
+        let payload = &bytes[range.start + 4..range.end];
+        (marker == 0xE1).then(|| payload.strip_prefix(JPEG_XMP_HEADER)).flatten()
+    })
+}
+
+// ---------------------------------------------------------------------------
+// WebP
+// ---------------------------------------------------------------------------
+
+/// `(fourcc, payload, whole chunk including padding)` after the RIFF header.
+type WebpChunk = ([u8; 4], std::ops::Range<usize>, std::ops::Range<usize>);
+
+fn webp_chunks(bytes: &[u8]) -> Result<Vec<WebpChunk>> {
+    let riff_end = bytes
+        .get(4..8)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        .and_then(|size| size.checked_add(8))
+        .filter(|&end| end <= bytes.len())
+        .context("truncated WebP file")?;
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= riff_end {
+        let kind: [u8; 4] = bytes[pos..pos + 4].try_into().unwrap();
+        let len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let Some(end) = (pos + 8).checked_add(len).filter(|&end| end <= riff_end) else {
+            bail!("malformed WebP chunk {:?} at byte {pos}", String::from_utf8_lossy(&kind));
+        };
+        let padded = (end + (len & 1)).min(riff_end);
+        chunks.push((kind, pos + 8..end, pos..padded));
+        pos = padded;
+    }
+    Ok(chunks)
+}
+
+fn webp_chunk(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 9);
+    out.extend_from_slice(kind);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+    out
+}
+
+fn embed_webp(bytes: &[u8], xmp: &str) -> Result<Vec<u8>> {
+    let chunks = webp_chunks(bytes)?;
+    let mut body = Vec::with_capacity(bytes.len() + xmp.len() + 32);
+    let (kind, payload, _) = chunks.first().context("WebP file has no image data")?;
+    if kind == b"VP8X" {
+        let mut header = bytes[payload.clone()].to_vec();
+        *header.first_mut().context("truncated VP8X chunk")? |= WEBP_FLAG_XMP;
+        body.extend(webp_chunk(b"VP8X", &header));
+    } else {
+        // Simple format: metadata needs the extended header
+        let info = super::probe::probe_bytes(bytes)?;
+        let (width, height) = (info.width.unwrap_or(1), info.height.unwrap_or(1));
+        let lossless_alpha = kind == b"VP8L"
+            && bytes.get(payload.start + 1..payload.start + 5).is_some_and(|b| b[3] & 0x10 != 0);
+        let mut header = vec![WEBP_FLAG_XMP | if lossless_alpha { WEBP_FLAG_ALPHA } else { 0 }, 0, 0, 0];
+        header.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        header.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        body.extend(webp_chunk(b"VP8X", &header));
+    }
+    for (kind, _, chunk) in &chunks {
+        if kind != b"VP8X" && kind != b"XMP " {
+            body.extend_from_slice(&bytes[chunk.clone()]);
+        }
+    }
+    // XMP goes last, after the image data and any EXIF
+    body.extend(webp_chunk(b"XMP ", xmp.as_bytes()));
+
+    let riff_size = u32::try_from(body.len() + 4).context("WebP file too large")?;
+    let mut out = Vec::with_capacity(body.len() + 12);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&riff_size.to_le_bytes());
+    out.extend_from_slice(b"WEBP");
+    out.extend(body);
+    Ok(out)
+}
+
+fn read_webp_xmp(bytes: &[u8]) -> Option<&[u8]> {
+    webp_chunks(bytes)
+        .ok()?
+        .into_iter()
+        .find_map(|(kind, payload, _)| (&kind == b"XMP ").then(|| &bytes[payload]))
+}
+
+// ---------------------------------------------------------------------------
+// MP4
+// ---------------------------------------------------------------------------
+
+/// A box inside some buffer: where it starts, where its payload starts, where it ends.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    kind: [u8; 4],
+    start: usize,
+    body: usize,
+    end: usize,
+}
+
+fn spans(data: &[u8], base: usize) -> Result<Vec<Span>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let size32 = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap());
+        let kind: [u8; 4] = data[pos + 4..pos + 8].try_into().unwrap();
+        let (header, size) = match size32 {
+            0 => (8, data.len() - pos),
+            1 => {
+                let large = data.get(pos + 8..pos + 16).context("truncated MP4 box header")?;
+                (16, usize::try_from(u64::from_be_bytes(large.try_into().unwrap())).unwrap_or(usize::MAX))
+            }
+            n => (8, n as usize),
+        };
+        let Some(end) = pos.checked_add(size).filter(|&end| size >= header && end <= data.len()) else {
+            bail!("malformed MP4 box {:?} at byte {}", String::from_utf8_lossy(&kind), base + pos);
+        };
+        out.push(Span { kind, start: base + pos, body: base + pos + header, end: base + end });
+        pos = end;
+    }
+    Ok(out)
+}
+
+fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// QuickTime user-data text atom: length, language, UTF-8 text.
+fn text_atom(kind: &[u8; 4], text: &str) -> Option<Vec<u8>> {
+    let len = u16::try_from(text.len()).ok()?;
+    let mut payload = len.to_be_bytes().to_vec();
+    payload.extend_from_slice(&0x55C4u16.to_be_bytes()); // "und"
+    payload.extend_from_slice(text.as_bytes());
+    Some(mp4_box(kind, &payload))
+}
+
+fn embed_mp4(bytes: &[u8], p: &Provenance, xmp: &str) -> Result<Vec<u8>> {
+    let top = spans(bytes, 0)?;
+    let moov = *top.iter().find(|s| &s.kind == b"moov").context("MP4 has no moov box")?;
+    if moov.body - moov.start != 8 {
+        bail!("64-bit moov boxes are not supported");
+    }
+
+    // Rebuild moov with our udta entries replacing any earlier ones
+    let mut owned = vec![mp4_box(b"XMP_", xmp.as_bytes())];
+    owned.extend(text_atom(b"\xA9too", CREATOR_TOOL));
+    owned.extend(p.prompt.as_deref().and_then(|prompt| text_atom(b"\xA9cmt", prompt)));
+    let children = spans(&bytes[moov.body..moov.end], moov.body)?;
+    let mut moov_body = Vec::with_capacity(moov.end - moov.body + xmp.len() + 64);
+    let mut has_udta = false;
+    for child in &children {
+        if &child.kind == b"udta" {
+            has_udta = true;
+            let mut udta = Vec::new();
+            for entry in spans(&bytes[child.body..child.end], child.body)? {
+                if !UDTA_OWNED.contains(&&entry.kind) {
+                    udta.extend_from_slice(&bytes[entry.start..entry.end]);
+                }
+            }
+            owned.iter().for_each(|b| udta.extend_from_slice(b));
+            moov_body.extend(mp4_box(b"udta", &udta));
+        } else {
+            moov_body.extend_from_slice(&bytes[child.start..child.end]);
+        }
+    }
+    if !has_udta {
+        moov_body.extend(mp4_box(b"udta", &owned.concat()));
+    }
+    let mut new_moov = mp4_box(b"moov", &moov_body);
+
+    // Media data after moov moves by the size difference
+    let delta = new_moov.len() as i64 - (moov.end - moov.start) as i64;
+    if delta != 0 && top.iter().any(|s| &s.kind == b"mdat" && s.start > moov.start) {
+        rebase_chunk_offsets(&mut new_moov, moov.end as u64, delta)?;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() + delta.max(0) as usize);
+    out.extend_from_slice(&bytes[..moov.start]);
+    out.extend_from_slice(&new_moov);
+    out.extend_from_slice(&bytes[moov.end..]);
+    Ok(out)
+}
+
+/// Shift every `stco`/`co64` entry at or past `from` by `delta`.
+fn rebase_chunk_offsets(moov: &mut [u8], from: u64, delta: i64) -> Result<()> {
+    let mut stack = vec![(8usize, moov.len())];
+    while let Some((body, end)) = stack.pop() {
+        for span in spans(&moov[body..end], body)? {
+            match &span.kind {
+                b"trak" | b"mdia" | b"minf" | b"stbl" => stack.push((span.body, span.end)),
+                b"stco" | b"co64" => {
+                    let wide = &span.kind == b"co64";
+                    let width = if wide { 8 } else { 4 };
+                    let count = moov.get(span.body + 4..span.body + 8).context("truncated chunk offset box")?;
+                    let count = u32::from_be_bytes(count.try_into().unwrap()) as usize;
+                    let entries = span.body + 8;
+                    if entries + count * width > span.end {
+                        bail!("truncated chunk offset table");
+                    }
+                    for i in 0..count {
+                        let at = entries + i * width;
+                        let field = &mut moov[at..at + width];
+                        let offset = if wide {
+                            u64::from_be_bytes(field.try_into().unwrap())
+                        } else {
+                            u64::from(u32::from_be_bytes(field.try_into().unwrap()))
+                        };
+                        if offset < from {
+                            continue;
+                        }
+                        let moved = offset.checked_add_signed(delta).context("chunk offset out of range")?;
+                        if wide {
+                            field.copy_from_slice(&moved.to_be_bytes());
+                        } else {
+                            let moved = u32::try_from(moved).context("chunk offset no longer fits stco")?;
+                            field.copy_from_slice(&moved.to_be_bytes());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_mp4_xmp(bytes: &[u8]) -> Option<&[u8]> {
+    let top = spans(bytes, 0).ok()?;
+    let moov = top.iter().find(|s| &s.kind == b"moov")?;
+    let udta = spans(&bytes[moov.body..moov.end], moov.body).ok()?.into_iter().find(|s| &s.kind == b"udta")?;
+    let xmp = spans(&bytes[udta.body..udta.end], udta.body).ok()?.into_iter().find(|s| &s.kind == b"XMP_")?;
+    Some(&bytes[xmp.body..xmp.end])
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::probe;
+
+    fn provenance() -> Provenance {
+        Provenance {
+            prompt: Some("夜景 \"neon\" <rain> & fog\nsecond line".into()),
+            model: Some("doubao-seedance-1-5-pro-251215".into()),
+            seed: Some(58944),
+            size: Some("1920x1080".into()),
+            task_id: Some("t1".into()),
+            created_at: Some("2026-01-02T03:04:05Z".into()),
+            creator_tool: Some(CREATOR_TOOL.into()),
+        }
+    }
+
+    fn encoded_png() -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(4, 3, image::Rgb([200, 10, 10]));
+        let mut out = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn png_and_jpeg_round_trip_and_still_decode() {
+        let png = encoded_png();
+        let tagged = embed(&png, &provenance()).unwrap().unwrap();
+        assert_eq!(read(&tagged), Some(provenance()));
+        assert_eq!(image::load_from_memory(&tagged).unwrap().width(), 4);
+        // Writing again replaces rather than stacks
+        let retagged = embed(&tagged, &Provenance { seed: None, ..provenance() }).unwrap().unwrap();
+        assert_eq!(read(&retagged).unwrap().seed, None);
+        assert_eq!(retagged.len(), tagged.len() - "\n    seedcanvas:seed=\"58944\"".len());
+
+        let img = image::load_from_memory(&png).unwrap().to_rgb8();
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut jpeg, image::ImageFormat::Jpeg).unwrap();
+        let tagged = embed(jpeg.get_ref(), &provenance()).unwrap().unwrap();
+        assert_eq!(read(&tagged), Some(provenance()));
+        assert_eq!(probe::probe_bytes(&tagged).unwrap().width, Some(4));
+        assert!(read(&png).is_none());
+    }
+
+    #[test]
+    fn webp_gains_an_extended_header_and_xmp_chunk() {
+        let rgba = image::RgbaImage::from_pixel(5, 3, image::Rgba([10, 200, 10, 128]));
+        let mut lossless = Vec::new();
+        image::codecs::webp::WebPEncoder::new_lossless(&mut lossless)
+            .encode(rgba.as_raw(), 5, 3, image::ExtendedColorType::Rgba8)
+            .unwrap();
+        let lossy = webp::Encoder::from_rgba(rgba.as_raw(), 5, 3).encode(80.0).to_vec();
+
+        for file in [lossless, lossy] {
+            assert!(read(&file).is_none());
+            let tagged = embed(&file, &provenance()).unwrap().unwrap();
+            assert_eq!(read(&tagged), Some(provenance()));
+            assert_eq!(&tagged[12..16], b"VP8X");
+            assert_eq!(tagged[20] & WEBP_FLAG_XMP, WEBP_FLAG_XMP);
+            let info = probe::probe_bytes(&tagged).unwrap();
+            assert_eq!((info.width, info.height), (Some(5), Some(3)));
+            let decoded = image::load_from_memory(&tagged).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (5, 3));
+
+            let retagged = embed(&tagged, &Provenance { seed: None, ..provenance() }).unwrap().unwrap();
+            assert_eq!(read(&retagged).unwrap().seed, None);
+            assert_eq!(webp_chunks(&retagged).unwrap().iter().filter(|c| &c.0 == b"XMP ").count(), 1);
+        }
+    }
+
+    #[test]
+    fn mp4_gains_udta_and_keeps_chunk_offsets_pointing_at_media() {
+        let stco = |offset: u32| {
+            let mut payload = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            payload.extend_from_slice(&offset.to_be_bytes());
+            mp4_box(b"stco", &payload)
+        };
+        let build = |offset: u32| {
+            let stbl = mp4_box(b"stbl", &stco(offset));
+            let trak = mp4_box(b"trak", &mp4_box(b"mdia", &mp4_box(b"minf", &stbl)));
+            let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &[0; 20]), trak].concat());
+            let ftyp = mp4_box(b"ftyp", b"isom\0\0\x02\0isom");
+            (ftyp.len() + moov.len(), [ftyp, moov].concat())
+        };
+        // The chunk offset must point at the first media byte
+        let (header_len, _) = build(0);
+        let media_at = (header_len + 8) as u32;
+        let (_, head) = build(media_at);
+        let file = [head, mp4_box(b"mdat", b"FRAMEDATA")].concat();
+
+        let tagged = embed(&file, &provenance()).unwrap().unwrap();
+        assert_eq!(read(&tagged), Some(provenance()));
+        let top = spans(&tagged, 0).unwrap();
+        let mdat = top.iter().find(|s| &s.kind == b"mdat").unwrap();
+        let stco_at = tagged.windows(4).position(|w| w == b"stco").unwrap() + 4 + 8;
+        let offset = u32::from_be_bytes(tagged[stco_at..stco_at + 4].try_into().unwrap()) as usize;
+        assert_eq!(offset, mdat.body);
+        assert_eq!(&tagged[offset..offset + 9], b"FRAMEDATA");
+
+        // A 64-bit size running past the end is an error, not an overflow
+        let mut huge = 1u32.to_be_bytes().to_vec();
+        huge.extend_from_slice(b"free");
+        huge.extend(u64::MAX.to_be_bytes());
+        assert!(spans(&[file, huge].concat(), 0).is_err());
+    }
+
+    #[test]
+    fn foreign_xmp_description_is_read_as_prompt() {
+        let xmp = r#"<x:xmpmeta><rdf:RDF><rdf:Description xmp:CreatorTool="Other">
+            <dc:description><rdf:Alt><rdf:li xml:lang="x-default">a red fox &amp; hen</rdf:li></rdf:Alt></dc:description>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let p = from_xmp(xmp).unwrap();
+        assert_eq!(p.prompt.as_deref(), Some("a red fox & hen"));
+        assert_eq!(p.creator_tool.as_deref(), Some("Other"));
+        assert!(from_xmp("<x:xmpmeta/>").is_none());
+    }
+}
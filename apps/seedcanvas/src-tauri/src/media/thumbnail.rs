@@ -0,0 +1,110 @@
+//! Downscaled previews for the asset library grid.
+//!
+//! Previews live beside a project's `assets/` directory, never inside it:
+//!
+//! ```text
+//! {project}/thumbnails/{asset stem}.webp          — every image and video
+//! {project}/thumbnails/{asset stem}.poster.{ext}  — video last frame, full size
+//! ```
+//!
+//! Thumbnails are lossless WebP, at most [`THUMBNAIL_MAX_EDGE`] on the long
+//! side. Decoding and encoding are CPU-bound; async callers should run these
+//! functions under `spawn_blocking`.
+
+use anyhow::{Context, Result};
+use image::codecs::webp::WebPEncoder;
+use std::path::{Path, PathBuf};
+
+use super::probe::MediaFormat;
+
+/// Longest edge of a generated thumbnail, in pixels.
+pub const THUMBNAIL_MAX_EDGE: u32 = 512;
+
+pub fn thumbnail_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join("thumbnails")
+}
+
+/// Thumbnail location for the asset stored as `asset_file_name`.
+pub fn thumbnail_path(project_dir: &Path, asset_file_name: &str) -> PathBuf {
+    thumbnail_dir(project_dir).join(format!("{}.webp", file_stem(asset_file_name)))
+}
+
+/// Write a thumbnail of an encoded image (PNG, JPEG or WebP) and return its path.
+pub fn create_thumbnail(project_dir: &Path, asset_file_name: &str, image_bytes: &[u8]) -> Result<PathBuf> {
+    let dest = thumbnail_path(project_dir, asset_file_name);
+    write_thumbnail(image_bytes, &dest)?;
+    Ok(dest)
+}
+
+/// Poster frame location for the asset stored as `asset_file_name`.
+pub fn poster_path(project_dir: &Path, asset_file_name: &str, ext: &str) -> PathBuf {
+    thumbnail_dir(project_dir).join(format!("{}.poster.{ext}", file_stem(asset_file_name)))
+}
+
+/// Store a video's poster frame as-is, plus a thumbnail of it.
+/// Returns `(poster_path, thumbnail_path)`.
+pub fn store_poster(project_dir: &Path, asset_file_name: &str, frame_bytes: &[u8]) -> Result<(PathBuf, PathBuf)> {
+    let ext = MediaFormat::sniff(frame_bytes)
+        .map(MediaFormat::extension)
+        .context("poster frame is not a recognized image")?;
+    let dir = thumbnail_dir(project_dir);
+    std::fs::create_dir_all(&dir)?;
+    let poster = poster_path(project_dir, asset_file_name, ext);
+    std::fs::write(&poster, frame_bytes)
+        .with_context(|| format!("failed to write {}", poster.display()))?;
+
+    let thumb = create_thumbnail(project_dir, asset_file_name, frame_bytes)?;
+    Ok((poster, thumb))
+}
+
+fn write_thumbnail(image_bytes: &[u8], dest: &Path) -> Result<()> {
+    let img = image::load_from_memory(image_bytes).context("failed to decode image")?;
+    let thumb = img.thumbnail(THUMBNAIL_MAX_EDGE, THUMBNAIL_MAX_EDGE).to_rgba8();
+
+    if let Some(dir) = dest.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = std::fs::File::create(dest)
+        .with_context(|| format!("failed to create {}", dest.display()))?;
+    WebPEncoder::new_lossless(std::io::BufWriter::new(file))
+        .encode(thumb.as_raw(), thumb.width(), thumb.height(), image::ExtendedColorType::Rgba8)
+        .context("failed to encode WebP thumbnail")?;
+    Ok(())
+}
+
+fn file_stem(file_name: &str) -> &str {
+    Path::new(file_name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(file_name)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::probe;
+
+    #[test]
+    fn thumbnail_fits_max_edge_and_keeps_aspect_ratio() {
+        let project = std::env::temp_dir().join(format!("seedcanvas-thumb-{}", uuid::Uuid::new_v4()));
+        let src = image::RgbImage::from_pixel(2048, 1024, image::Rgb([200, 40, 40]));
+        let mut png = Vec::new();
+        src.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+
+        let path = create_thumbnail(&project, "abc.png", &png).unwrap();
+        assert_eq!(path, project.join("thumbnails").join("abc.webp"));
+        let info = probe::probe_file(&path).unwrap();
+        assert_eq!(info.format, MediaFormat::Webp);
+        assert_eq!((info.width, info.height), (Some(512), Some(256)));
+
+        let (poster, thumb) = store_poster(&project, "clip.mp4", &png).unwrap();
+        assert_eq!(poster.file_name().unwrap(), "clip.poster.png");
+        assert!(thumb.ends_with("thumbnails/clip.webp"));
+
+        let _ = std::fs::remove_dir_all(&project);
+    }
+}
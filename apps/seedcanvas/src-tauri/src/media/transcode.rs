@@ -0,0 +1,156 @@
+//! Re-encoding generated images into the configured storage format.
+//!
+//! ARK returns whatever its backend produced — usually PNG, sometimes JPEG or
+//! WebP. By default the bytes are stored untouched under the extension their
+//! magic bytes identify; `assetStorage.imageFormat` trades fidelity for disk:
+//!
+//! | `imageFormat`    | stored as                       |
+//! |------------------|---------------------------------|
+//! | `original`       | the provider's bytes            |
+//! | `png`            | PNG, best compression           |
+//! | `webpLossless`   | lossless WebP                   |
+//! | `jpeg`           | JPEG at `imageQuality`, no alpha|
+//! | `webp`           | lossy WebP at `imageQuality`    |
+//!
+//! An image already in the target container is never re-encoded, so a lossy
+//! source is not degraded twice. Encoding is CPU-bound; async callers should
+//! run [`transcode`] under `spawn_blocking`.
+
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageEncoder};
+use serde::{Deserialize, Serialize};
+
+use super::probe::MediaFormat;
+
+/// Quality used for `jpeg` and `webp` when `imageQuality` is unset.
+pub const DEFAULT_IMAGE_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImageStorageFormat {
+    #[default]
+    Original,
+    Png,
+    WebpLossless,
+    Jpeg,
+    Webp,
+}
+
+impl ImageStorageFormat {
+    /// Container the stored file ends up in, or `None` to keep the source's.
+    pub fn target(self) -> Option<MediaFormat> {
+        match self {
+            ImageStorageFormat::Original => None,
+            ImageStorageFormat::Png => Some(MediaFormat::Png),
+            ImageStorageFormat::WebpLossless | ImageStorageFormat::Webp => Some(MediaFormat::Webp),
+            ImageStorageFormat::Jpeg => Some(MediaFormat::Jpeg),
+        }
+    }
+}
+
+/// An image ready to be written, with the format its bytes are in.
+#[derive(Debug)]
+pub struct Encoded {
+    pub bytes: Vec<u8>,
+    pub format: MediaFormat,
+    /// The bytes were re-encoded rather than passed through.
+    pub transcoded: bool,
+}
+
+/// Convert `bytes` to `target`. `quality` (1–100) applies to the lossy
+/// formats only. Fails if `bytes` is not a PNG, JPEG or WebP image.
+pub fn transcode(bytes: Vec<u8>, target: ImageStorageFormat, quality: u8) -> Result<Encoded> {
+    let source = match MediaFormat::sniff(&bytes) {
+        Some(format) if format.asset_type() == "image" => format,
+        _ => anyhow::bail!("not a PNG, JPEG or WebP image"),
+    };
+    let format = match target.target() {
+        Some(format) if format != source => format,
+        _ => return Ok(Encoded { bytes, format: source, transcoded: false }),
+    };
+
+    let img = image::load_from_memory(&bytes).context("failed to decode image")?;
+    let quality = quality.clamp(1, 100);
+    let mut out = Vec::new();
+    match target {
+        ImageStorageFormat::Png => {
+            let rgba = img.to_rgba8();
+            PngEncoder::new_with_quality(&mut out, CompressionType::Best, FilterType::Adaptive)
+                .write_image(rgba.as_raw(), rgba.width(), rgba.height(), image::ExtendedColorType::Rgba8)
+                .context("failed to encode PNG")?;
+        }
+        ImageStorageFormat::WebpLossless => {
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_lossless(&mut out)
+                .encode(rgba.as_raw(), rgba.width(), rgba.height(), image::ExtendedColorType::Rgba8)
+                .context("failed to encode WebP")?;
+        }
+        ImageStorageFormat::Jpeg => {
+            // JPEG has no alpha channel: it is dropped, so transparent pixels
+            // show whatever color they carry underneath
+            let rgb = DynamicImage::ImageRgba8(img.to_rgba8()).to_rgb8();
+            JpegEncoder::new_with_quality(&mut out, quality)
+                .encode(rgb.as_raw(), rgb.width(), rgb.height(), image::ExtendedColorType::Rgb8)
+                .context("failed to encode JPEG")?;
+        }
+        ImageStorageFormat::Webp => {
+            let rgba = img.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height())
+                .encode_simple(false, quality as f32)
+                .map_err(|e| anyhow::anyhow!("failed to encode WebP: {e:?}"))?;
+            out = encoded.to_vec();
+        }
+        ImageStorageFormat::Original => unreachable!("handled above"),
+    }
+
+    Ok(Encoded { bytes: out, format, transcoded: true })
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_png() -> Vec<u8> {
+        let img = image::RgbaImage::from_fn(32, 24, |x, y| image::Rgba([x as u8 * 8, y as u8 * 10, 128, 255]));
+        let mut out = Vec::new();
+        PngEncoder::new(&mut out)
+            .write_image(img.as_raw(), 32, 24, image::ExtendedColorType::Rgba8)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn converts_to_each_storage_format() {
+        let png = sample_png();
+
+        let kept = transcode(png.clone(), ImageStorageFormat::Original, 85).unwrap();
+        assert_eq!(kept.format, MediaFormat::Png);
+        assert!(!kept.transcoded);
+        assert_eq!(kept.bytes, png);
+
+        let same = transcode(png.clone(), ImageStorageFormat::Png, 85).unwrap();
+        assert!(!same.transcoded);
+
+        for (target, format) in [
+            (ImageStorageFormat::WebpLossless, MediaFormat::Webp),
+            (ImageStorageFormat::Jpeg, MediaFormat::Jpeg),
+            (ImageStorageFormat::Webp, MediaFormat::Webp),
+        ] {
+            let encoded = transcode(png.clone(), target, 70).unwrap();
+            assert!(encoded.transcoded);
+            assert_eq!(encoded.format, format);
+            assert_eq!(MediaFormat::sniff(&encoded.bytes), Some(format));
+            let decoded = image::load_from_memory(&encoded.bytes).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (32, 24));
+        }
+
+        assert!(transcode(b"not an image".to_vec(), ImageStorageFormat::Png, 85).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+//! Cost estimates for generation tasks — `pricing` in settings.json.
+//!
+//! Prices are per model, with optional overrides per image size or video
+//! resolution. A rate can charge per output, per second of video and per
+//! million usage tokens; whichever components are set are added up:
+//!
+//! ```json
+//! "pricing": {
+//!   "currency": "CNY",
+//!   "models": {
+//!     "doubao-seedream-5-0-260128": { "perOutput": 0.22 },
+//!     "doubao-seedance-1-5-pro-251215": {
+//!       "perMillionTokens": 16.0,
+//!       "sizes": { "1080p": { "perMillionTokens": 18.0 } }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Every task gets an estimate at submission from its input; token-priced
+//! video models are estimated from the requested resolution, ratio and
+//! duration. When a task finishes, the cost is settled from the token count
+//! ARK reports if the rate has a token price, otherwise from the produced
+//! output. Unpriced models are simply not tracked.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::db::{TaskCostRow, TaskType};
+
+/// Nominal video length when a task does not name one.
+const FALLBACK_VIDEO_SECONDS: f64 = 5.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceTable {
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Keyed by model ID.
+    #[serde(default)]
+    pub models: HashMap<String, ModelPrice>,
+}
+
+fn default_currency() -> String {
+    "CNY".to_string()
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        Self { currency: default_currency(), models: HashMap::new() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rate {
+    #[serde(default)]
+    pub per_output: Option<f64>,
+    #[serde(default)]
+    pub per_second: Option<f64>,
+    #[serde(default)]
+    pub per_million_tokens: Option<f64>,
+}
+
+impl Rate {
+    /// `self`, with each component `other` sets taking precedence.
+    fn overlay(self, other: &Rate) -> Rate {
+        Rate {
+            per_output: other.per_output.or(self.per_output),
+            per_second: other.per_second.or(self.per_second),
+            per_million_tokens: other.per_million_tokens.or(self.per_million_tokens),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    #[serde(flatten)]
+    pub base: Rate,
+    /// Overrides keyed by image size ("2K", "2048x2048") or video resolution ("720p").
+    #[serde(default)]
+    pub sizes: HashMap<String, Rate>,
+}
+
+/// How a cost was arrived at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostBasis {
+    /// From the task input, before anything was generated.
+    Estimate,
+    /// From the generated output's count and duration.
+    Output,
+    /// From the token usage ARK reported.
+    Tokens,
+}
+
+impl CostBasis {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CostBasis::Estimate => "estimate",
+            CostBasis::Output => "output",
+            CostBasis::Tokens => "tokens",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cost {
+    pub amount: f64,
+    pub basis: CostBasis,
+}
+
+impl PriceTable {
+    /// Rate for `model`, with the first matching size override applied.
+    pub fn rate(&self, model: &str, sizes: &[impl AsRef<str>]) -> Option<Rate> {
+        let price = self.models.get(model)?;
+        let size = sizes.iter().find_map(|s| price.sizes.get(s.as_ref()));
+        Some(match size {
+            Some(over) => price.base.overlay(over),
+            None => price.base,
+        })
+    }
+
+    /// Cost of a task from its input JSON — at submission, before any usage
+    /// is known.
+    pub fn estimate(&self, task_type: TaskType, input: &serde_json::Value) -> Option<Cost> {
+        let model = input["model"].as_str()?;
+        let rate = self.rate(model, &size_keys(task_type, input, None))?;
+        let amount = match task_type {
+            TaskType::Image => output_cost(&rate, 0.0),
+            TaskType::Video => {
+                let seconds = input["duration"].as_f64().unwrap_or(FALLBACK_VIDEO_SECONDS);
+                match rate.per_million_tokens {
+                    Some(per_million) => {
+                        let tokens = estimated_video_tokens(input["resolution"].as_str(), input["ratio"].as_str(), seconds);
+                        Some(per_million * tokens / 1_000_000.0 + rate.per_output.unwrap_or(0.0))
+                    }
+                    None => output_cost(&rate, seconds),
+                }
+            }
+        };
+        amount.map(|amount| Cost { amount, basis: CostBasis::Estimate })
+    }
+
+    /// Cost of a finished task from its output JSON (`usageTokens`,
+    /// `duration`, `width` × `height`).
+    pub fn settle(&self, task_type: TaskType, input: &serde_json::Value, output: &serde_json::Value) -> Option<Cost> {
+        let model = input["model"].as_str()?;
+        let rate = self.rate(model, &size_keys(task_type, input, Some(output)))?;
+        if let (Some(per_million), Some(tokens)) = (rate.per_million_tokens, output["usageTokens"].as_i64()) {
+            let amount = per_million * tokens as f64 / 1_000_000.0 + rate.per_output.unwrap_or(0.0);
+            return Some(Cost { amount, basis: CostBasis::Tokens });
+        }
+        let seconds = match task_type {
+            TaskType::Image => 0.0,
+            TaskType::Video => output["duration"]
+                .as_f64()
+                .or_else(|| input["duration"].as_f64())
+                .unwrap_or(FALLBACK_VIDEO_SECONDS),
+        };
+        output_cost(&rate, seconds).map(|amount| Cost { amount, basis: CostBasis::Output })
+    }
+}
+
+/// Size override keys to try, most specific first.
+fn size_keys(task_type: TaskType, input: &serde_json::Value, output: Option<&serde_json::Value>) -> Vec<String> {
+    let mut keys = Vec::new();
+    if let (TaskType::Image, Some(output)) = (task_type, output) {
+        if let (Some(w), Some(h)) = (output["width"].as_i64(), output["height"].as_i64()) {
+            keys.push(format!("{w}x{h}"));
+        }
+    }
+    let field = match task_type {
+        TaskType::Image => "size",
+        TaskType::Video => "resolution",
+    };
+    keys.extend(input[field].as_str().map(String::from));
+    keys
+}
+
+/// Seedance bills `width × height × fps × seconds / 1024` tokens. The frame
+/// size follows from the resolution's short edge and the aspect ratio;
+/// `adaptive` is taken as 16:9.
+fn estimated_video_tokens(resolution: Option<&str>, ratio: Option<&str>, seconds: f64) -> f64 {
+    const FPS: f64 = 24.0;
+    let short_edge = resolution
+        .and_then(|r| r.strip_suffix('p'))
+        .and_then(|r| r.parse::<f64>().ok())
+        .unwrap_or(720.0);
+    let (w, h) = ratio
+        .and_then(|r| r.split_once(':'))
+        .and_then(|(w, h)| Some((w.parse::<f64>().ok()?, h.parse::<f64>().ok()?)))
+        .unwrap_or((16.0, 9.0));
+    let (long, short) = (w.max(h), w.min(h));
+    let frame = short_edge * short_edge * long / short;
+    frame * FPS * seconds / 1024.0
+}
+
+/// Per-output and per-second components; `None` when the rate has neither.
+fn output_cost(rate: &Rate, seconds: f64) -> Option<f64> {
+    if rate.per_output.is_none() && rate.per_second.is_none() {
+        return None;
+    }
+    Some(rate.per_output.unwrap_or(0.0) + rate.per_second.unwrap_or(0.0) * seconds)
+}
+
+// ---------------------------------------------------------------------------
+// CSV export
+// ---------------------------------------------------------------------------
+
+const CSV_HEADER: &str =
+    "task_id,project_id,type,status,model,created_at,currency,estimated_cost,actual_cost,usage_tokens,basis";
+
+/// Write one CSV line per task cost, with a header row.
+pub fn write_csv(rows: &[TaskCostRow], out: &mut impl Write) -> Result<()> {
+    writeln!(out, "{CSV_HEADER}")?;
+    for row in rows {
+        let fields = [
+            csv_field(&row.task_id),
+            csv_field(&row.project_id),
+            row.task_type.as_str().to_string(),
+            row.status.as_str().to_string(),
+            csv_field(row.model.as_deref().unwrap_or("")),
+            csv_field(&row.created_at),
+            csv_field(&row.currency),
+            row.estimated_cost.map(|c| c.to_string()).unwrap_or_default(),
+            row.actual_cost.map(|c| c.to_string()).unwrap_or_default(),
+            row.usage_tokens.map(|t| t.to_string()).unwrap_or_default(),
+            row.basis.as_deref().unwrap_or("").to_string(),
+        ];
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TaskStatus;
+    use serde_json::json;
+
+    fn table() -> PriceTable {
+        serde_json::from_value(json!({
+            "models": {
+                "img": { "perOutput": 0.2, "sizes": { "4K": { "perOutput": 0.5 } } },
+                "vid": { "perMillionTokens": 10.0, "sizes": { "1080p": { "perMillionTokens": 20.0 } } },
+                "clip": { "perSecond": 0.1 }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn estimates_and_settles_by_size_duration_and_tokens() {
+        let prices = table();
+        assert_eq!(prices.currency, "CNY");
+
+        let image = json!({ "model": "img", "size": "4K" });
+        assert_eq!(prices.estimate(TaskType::Image, &image).unwrap().amount, 0.5);
+        let settled = prices.settle(TaskType::Image, &json!({ "model": "img", "size": "2K" }), &json!({})).unwrap();
+        assert_eq!((settled.amount, settled.basis), (0.2, CostBasis::Output));
+        assert!(prices.estimate(TaskType::Image, &json!({ "model": "unpriced" })).is_none());
+
+        // 1280×720 × 24 fps × 5 s / 1024 = 108 000 tokens
+        let video = json!({ "model": "vid", "resolution": "720p", "ratio": "16:9", "duration": 5 });
+        let estimate = prices.estimate(TaskType::Video, &video).unwrap();
+        assert!((estimate.amount - 1.08).abs() < 1e-9);
+        let settled = prices.settle(TaskType::Video, &video, &json!({ "usageTokens": 200_000 })).unwrap();
+        assert_eq!(settled.basis, CostBasis::Tokens);
+        assert!((settled.amount - 2.0).abs() < 1e-9);
+
+        let clip = json!({ "model": "clip", "duration": 10 });
+        assert!((prices.estimate(TaskType::Video, &clip).unwrap().amount - 1.0).abs() < 1e-9);
+        let settled = prices.settle(TaskType::Video, &clip, &json!({ "duration": 4.0 })).unwrap();
+        assert!((settled.amount - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        let row = TaskCostRow {
+            task_id: "t1".into(),
+            project_id: "p,1".into(),
+            task_type: TaskType::Video,
+            status: TaskStatus::Done,
+            model: Some("vid".into()),
+            created_at: "2026-03-01T00:00:00+00:00".into(),
+            currency: "CNY".into(),
+            estimated_cost: Some(1.5),
+            actual_cost: None,
+            usage_tokens: Some(42),
+            basis: Some("estimate".into()),
+        };
+        let mut out = Vec::new();
+        write_csv(&[row], &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "t1,\"p,1\",video,done,vid,2026-03-01T00:00:00+00:00,CNY,1.5,,42,estimate");
+    }
+}
@@ -0,0 +1,673 @@
+//! Reconcile asset rows, task outputs and the files on disk.
+//!
+//! [`scan`] compares three sources and reports where they disagree:
+//!
+//! - `missing_file` — an asset row whose file is gone
+//! - `untracked_file` — a file in `{project}/assets/` with no asset row
+//! - `missing_output` — a done task whose output file is gone and that no
+//!   asset row accounts for
+//!
+//! Scanning changes nothing. Each issue lists the actions that can resolve
+//! it; [`apply`] re-scans and carries out the chosen ones, so a stale report
+//! can never act on an issue that has since disappeared. Previews in
+//! `thumbnails/` are derived data and not scanned.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::db::{AssetRow, TaskRow};
+use crate::library::AssetLibrary;
+use crate::media::probe::MediaFormat;
+use crate::storage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    MissingFile,
+    UntrackedFile,
+    MissingOutput,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileAction {
+    /// Missing file: point the row at the untracked file with the same hash.
+    /// Untracked file: create an asset row for it.
+    Reregister,
+    /// Fetch the file again from the task's remote URL.
+    Redownload,
+    /// Drop the row (missing file/output) or delete the file (untracked).
+    Purge,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileIssue {
+    /// Stable key to pass back in a [`Resolution`].
+    pub id: String,
+    pub kind: IssueKind,
+    pub project_id: String,
+    pub asset_id: Option<String>,
+    pub task_id: Option<String>,
+    pub path: String,
+    /// Remote URL recorded by the task, if any.
+    pub remote_url: Option<String>,
+    /// An untracked file with the same content as a missing one — the file
+    /// was most likely renamed.
+    pub relink_candidate: Option<String>,
+    /// Actions available for this issue.
+    pub actions: Vec<ReconcileAction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resolution {
+    pub issue_id: String,
+    pub action: ReconcileAction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedResolution {
+    pub issue_id: String,
+    pub action: ReconcileAction,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    /// True when nothing was changed.
+    pub dry_run: bool,
+    pub assets_checked: usize,
+    pub files_checked: usize,
+    pub tasks_checked: usize,
+    pub issues: Vec<ReconcileIssue>,
+    pub applied: Vec<AppliedResolution>,
+}
+
+// ---------------------------------------------------------------------------
+// Scan
+// ---------------------------------------------------------------------------
+
+/// Report disagreements without changing anything. With `check_remote`,
+/// recorded remote URLs are probed and `redownload` is only offered for
+/// those that still answer.
+pub async fn scan(library: &AssetLibrary, project_id: Option<&str>, check_remote: bool) -> Result<ReconcileReport> {
+    let lib = library.clone();
+    let project = project_id.map(String::from);
+    let mut report = tokio::task::spawn_blocking(move || scan_local(&lib, project.as_deref())).await??;
+
+    if check_remote {
+        let http = http_client()?;
+        for issue in &mut report.issues {
+            if let Some(url) = issue.remote_url.as_deref() {
+                if remote_available(&http, url).await {
+                    issue.actions.insert(0, ReconcileAction::Redownload);
+                }
+            }
+        }
+    }
+    report.dry_run = true;
+    Ok(report)
+}
+
+fn scan_local(library: &AssetLibrary, project_id: Option<&str>) -> Result<ReconcileReport> {
+    // A task's output may live on as an asset in another project (moved or
+    // copied there) or in the trash, so coverage looks at every asset row
+    let (assets, tasks, covered_tasks) = {
+        let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        (db.list_all_assets(project_id)?, db.list_done_tasks(project_id)?, db.asset_task_ids()?)
+    };
+    let project_ids = match project_id {
+        Some(id) => vec![id.to_string()],
+        None => list_project_ids(library.projects_dir()),
+    };
+
+    let tracked: HashSet<&str> = assets.iter().map(|a| a.file_path.as_str()).collect();
+    let task_by_id: HashMap<&str, &TaskRow> = tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+
+    let mut report = ReconcileReport {
+        assets_checked: assets.len(),
+        tasks_checked: tasks.len(),
+        ..Default::default()
+    };
+
+    // Untracked files first: they are relink candidates for missing rows
+    let mut untracked: Vec<(String, PathBuf)> = Vec::new();
+    for project in &project_ids {
+        let dir = library.project_dir(project).join("assets");
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            report.files_checked += 1;
+            if !tracked.contains(path.to_string_lossy().as_ref()) {
+                untracked.push((project.clone(), path));
+            }
+        }
+    }
+    let mut untracked_hashes: HashMap<PathBuf, String> = HashMap::new();
+    let mut relink_targets: HashSet<String> = HashSet::new();
+
+    for asset in &assets {
+        if Path::new(&asset.file_path).exists() {
+            continue;
+        }
+        let remote_url = asset
+            .task_id
+            .as_deref()
+            .and_then(|id| task_by_id.get(id))
+            .and_then(|t| remote_url(t));
+        let relink_candidate = find_same_content(asset, &untracked, &mut untracked_hashes)
+            .filter(|path| relink_targets.insert(path.clone()));
+        let mut actions = Vec::new();
+        if relink_candidate.is_some() {
+            actions.push(ReconcileAction::Reregister);
+        }
+        actions.push(ReconcileAction::Purge);
+        report.issues.push(ReconcileIssue {
+            id: format!("missing_file:{}", asset.id),
+            kind: IssueKind::MissingFile,
+            project_id: asset.project_id.clone(),
+            asset_id: Some(asset.id.clone()),
+            task_id: asset.task_id.clone(),
+            path: asset.file_path.clone(),
+            remote_url,
+            relink_candidate,
+            actions,
+        });
+    }
+
+    for (project, path) in &untracked {
+        let path_str = path.to_string_lossy().to_string();
+        if relink_targets.contains(&path_str) {
+            // Reported as the missing row's relink candidate instead
+            continue;
+        }
+        // Leftovers of an interrupted write can only be cleaned up
+        let mut actions = Vec::new();
+        if !is_partial(path) && sniff_file(path).is_some() {
+            actions.push(ReconcileAction::Reregister);
+        }
+        actions.push(ReconcileAction::Purge);
+        report.issues.push(ReconcileIssue {
+            id: format!("untracked_file:{path_str}"),
+            kind: IssueKind::UntrackedFile,
+            project_id: project.clone(),
+            asset_id: None,
+            task_id: task_for_path(&tasks, &path_str).map(|t| t.id.clone()),
+            path: path_str,
+            remote_url: None,
+            relink_candidate: None,
+            actions,
+        });
+    }
+
+    for task in &tasks {
+        if covered_tasks.contains(task.id.as_str()) {
+            continue;
+        }
+        let Some(path) = output_path(task) else { continue };
+        if Path::new(&path).exists() {
+            continue;
+        }
+        report.issues.push(ReconcileIssue {
+            id: format!("missing_output:{}", task.id),
+            kind: IssueKind::MissingOutput,
+            project_id: task.project_id.clone(),
+            asset_id: None,
+            task_id: Some(task.id.clone()),
+            path,
+            remote_url: remote_url(task),
+            relink_candidate: None,
+            actions: vec![ReconcileAction::Purge],
+        });
+    }
+
+    Ok(report)
+}
+
+/// Project directories, skipping hidden ones such as a bundle import's
+/// `.import-*` staging and `.replaced-*` backup directories.
+fn list_project_ids(projects_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(projects_dir) else { return Vec::new() };
+    let mut ids: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().to_str().map(String::from))
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// Same-project untracked file whose size and hash match the asset's record.
+fn find_same_content(
+    asset: &AssetRow,
+    untracked: &[(String, PathBuf)],
+    hashes: &mut HashMap<PathBuf, String>,
+) -> Option<String> {
+    let expected = asset.sha256.as_deref()?;
+    for (project, path) in untracked {
+        if *project != asset.project_id || is_partial(path) {
+            continue;
+        }
+        let size = std::fs::metadata(path).map(|m| m.len() as i64).ok();
+        if asset.file_size.is_some() && size != asset.file_size {
+            continue;
+        }
+        if !hashes.contains_key(path) {
+            match storage::sha256_file(path) {
+                Ok((sha, _)) => hashes.insert(path.clone(), sha),
+                Err(_) => continue,
+            };
+        }
+        if hashes.get(path).map(String::as_str) == Some(expected) {
+            return Some(path.to_string_lossy().to_string());
+        }
+    }
+    None
+}
+
+fn is_partial(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.') && n.ends_with(".partial"))
+}
+
+fn sniff_file(path: &Path) -> Option<MediaFormat> {
+    MediaFormat::sniff_file(path).ok().flatten()
+}
+
+fn output_json(task: &TaskRow) -> serde_json::Value {
+    task.output
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default()
+}
+
+fn output_path(task: &TaskRow) -> Option<String> {
+    output_json(task)["assetPath"].as_str().map(String::from)
+}
+
+fn remote_url(task: &TaskRow) -> Option<String> {
+    output_json(task)["remoteUrl"].as_str().map(String::from)
+}
+
+fn task_for_path<'a>(tasks: &'a [TaskRow], path: &str) -> Option<&'a TaskRow> {
+    tasks.iter().find(|t| output_path(t).as_deref() == Some(path))
+}
+
+// ---------------------------------------------------------------------------
+// Remote
+// ---------------------------------------------------------------------------
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()
+        .context("failed to build HTTP client")
+}
+
+/// ARK's signed URLs reject HEAD, so ask for the first byte instead.
+async fn remote_available(http: &reqwest::Client, url: &str) -> bool {
+    let resp = http
+        .get(url)
+        .header(reqwest::header::RANGE, "bytes=0-0")
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await;
+    matches!(resp, Ok(r) if r.status().is_success())
+}
+
+async fn download_to(http: &reqwest::Client, url: &str, dest: &Path) -> Result<Vec<u8>> {
+    let resp = http.get(url).send().await?;
+    if !resp.status().is_success() {
+        bail!("remote URL no longer valid (HTTP {})", resp.status());
+    }
+    let bytes = resp.bytes().await.context("failed to download")?.to_vec();
+    if let Some(dir) = dest.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let tmp = dest.with_file_name(format!(".{}.partial", file_name_of(&dest.to_string_lossy())));
+    tokio::fs::write(&tmp, &bytes)
+        .await
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, dest).await?;
+    Ok(bytes)
+}
+
+// ---------------------------------------------------------------------------
+// Apply
+// ---------------------------------------------------------------------------
+
+/// Re-scan, then carry out each resolution whose issue still exists and
+/// offers the requested action. Returns the report of the scan it acted on.
+pub async fn apply(
+    library: &AssetLibrary,
+    project_id: Option<&str>,
+    resolutions: &[Resolution],
+) -> Result<ReconcileReport> {
+    let wants_remote = resolutions.iter().any(|r| r.action == ReconcileAction::Redownload);
+    let mut report = scan(library, project_id, wants_remote).await?;
+    report.dry_run = false;
+
+    let http = http_client()?;
+    for res in resolutions {
+        let outcome = match report.issues.iter().find(|i| i.id == res.issue_id) {
+            None => Err(anyhow::anyhow!("no such issue (already resolved?)")),
+            Some(issue) if !issue.actions.contains(&res.action) => {
+                Err(anyhow::anyhow!("action {:?} is not available for this issue", res.action))
+            }
+            Some(issue) => resolve(library, &http, issue, res.action).await,
+        };
+        if let Err(e) = &outcome {
+            warn!(issue = %res.issue_id, "reconcile action failed: {e:#}");
+        }
+        report.applied.push(AppliedResolution {
+            issue_id: res.issue_id.clone(),
+            action: res.action,
+            ok: outcome.is_ok(),
+            error: outcome.err().map(|e| format!("{e:#}")),
+        });
+    }
+    Ok(report)
+}
+
+async fn resolve(
+    library: &AssetLibrary,
+    http: &reqwest::Client,
+    issue: &ReconcileIssue,
+    action: ReconcileAction,
+) -> Result<()> {
+    use IssueKind::*;
+    use ReconcileAction::*;
+
+    let lib = library.clone();
+    let issue_owned = issue.clone();
+    match (issue.kind, action) {
+        (MissingFile, Reregister) => blocking(move || relink(&lib, &issue_owned)).await,
+        (MissingFile, Purge) => {
+            let id = issue.asset_id.clone().unwrap_or_default();
+            blocking(move || lib.delete_assets(&[id], true).map(|_| ())).await
+        }
+        (UntrackedFile, Reregister) => blocking(move || register(&lib, &issue_owned)).await,
+        (UntrackedFile, Purge) => Ok(tokio::fs::remove_file(&issue.path).await?),
+        (MissingOutput, Purge) => {
+            let id = issue.task_id.clone().unwrap_or_default();
+            blocking(move || {
+                let db = lib.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+                db.delete_task(&id).map(|_| ())
+            })
+            .await
+        }
+        (MissingFile | MissingOutput, Redownload) => {
+            let url = issue.remote_url.as_deref().context("no remote URL recorded")?;
+            let bytes = download_to(http, url, Path::new(&issue.path)).await?;
+            blocking(move || after_redownload(&lib, &issue_owned, &bytes)).await
+        }
+        (kind, action) => bail!("{action:?} does not apply to {kind:?}"),
+    }
+}
+
+async fn blocking<F>(f: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await?
+}
+
+fn relink(library: &AssetLibrary, issue: &ReconcileIssue) -> Result<()> {
+    let asset_id = issue.asset_id.as_deref().context("issue has no asset")?;
+    let path = issue.relink_candidate.as_deref().context("no file to relink to")?;
+    let file_name = file_name_of(path);
+    let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+    db.relink_asset_file(asset_id, path, &file_name)
+}
+
+/// Create an asset row for an untracked file, taking prompt and model from
+/// the task that produced it when one is known.
+fn register(library: &AssetLibrary, issue: &ReconcileIssue) -> Result<()> {
+    let path = Path::new(&issue.path);
+    let format = sniff_file(path).context("not a recognized image or video")?;
+    let task = match issue.task_id.as_deref() {
+        Some(id) => library
+            .db()
+            .lock()
+            .map_err(|e| anyhow::anyhow!("db lock: {e}"))?
+            .get_task(id)?,
+        None => None,
+    };
+    let mut asset = new_asset_row(&issue.project_id, &issue.path, format.asset_type(), task.as_ref());
+    library.fill_file_metadata(&mut asset);
+    let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+    db.insert_asset(&asset)?;
+    Ok(())
+}
+
+fn after_redownload(library: &AssetLibrary, issue: &ReconcileIssue, bytes: &[u8]) -> Result<()> {
+    match issue.kind {
+        IssueKind::MissingFile => {
+            let asset_id = issue.asset_id.as_deref().unwrap_or_default();
+            let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+            let asset = db.get_assets_by_ids(&[asset_id.to_string()])?.pop();
+            let actual = storage::sha256_hex(bytes);
+            // The stored file carried embedded provenance; the remote one is
+            // the raw output, recorded separately
+            let remote_hash = match asset.as_ref().and_then(|a| a.task_id.as_deref()) {
+                Some(task_id) => db
+                    .get_task(task_id)?
+                    .and_then(|t| output_json(&t)["remoteSha256"].as_str().map(String::from)),
+                None => None,
+            };
+            match remote_hash.or_else(|| asset.and_then(|a| a.sha256)) {
+                Some(expected) if expected != actual => {
+                    let _ = std::fs::remove_file(&issue.path);
+                    bail!("downloaded file does not match the recorded hash");
+                }
+                _ => db.set_asset_sha256(asset_id, &actual),
+            }
+        }
+        _ => {
+            let task = {
+                let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+                db.get_task(issue.task_id.as_deref().unwrap_or_default())?
+            };
+            let asset_type = task.as_ref().map_or("video", |t| t.task_type.as_str());
+            let mut asset = new_asset_row(&issue.project_id, &issue.path, asset_type, task.as_ref());
+            library.fill_file_metadata(&mut asset);
+            let db = library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+            db.insert_asset(&asset)?;
+            Ok(())
+        }
+    }
+}
+
+fn new_asset_row(project_id: &str, path: &str, asset_type: &str, task: Option<&TaskRow>) -> AssetRow {
+    let input: serde_json::Value = task
+        .and_then(|t| serde_json::from_str(&t.input).ok())
+        .unwrap_or_default();
+    AssetRow {
+        id: uuid::Uuid::new_v4().to_string(),
+        project_id: project_id.to_string(),
+        task_id: task.map(|t| t.id.clone()),
+        asset_type: asset_type.to_string(),
+        file_path: path.to_string(),
+        file_name: file_name_of(path),
+        prompt: input["prompt"].as_str().map(String::from),
+        model: input["model"].as_str().map(String::from),
+        source: if task.is_some() { "generated" } else { "imported" }.to_string(),
+        created_at: task.map_or_else(|| chrono::Utc::now().to_rfc3339(), |t| t.created_at.clone()),
+        ..Default::default()
+    }
+}
+
+fn file_name_of(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{TaskStatus, TaskType};
+    use crate::test_support::setup;
+
+    const PNG_HEAD: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x10\0\0\0\x08\x08\x02\0\0\0";
+
+    fn asset_path(lib: &AssetLibrary, name: &str) -> String {
+        lib.project_dir("p1").join("assets").join(name).to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn scan_finds_each_kind_and_apply_resolves_them() {
+        let (root, lib) = setup();
+
+        // A row whose file was renamed on disk
+        let renamed = asset_path(&lib, "renamed.png");
+        std::fs::write(&renamed, PNG_HEAD).unwrap();
+        let mut moved = new_asset_row("p1", &asset_path(&lib, "original.png"), "image", None);
+        moved.sha256 = Some(storage::sha256_hex(PNG_HEAD));
+        moved.file_size = Some(PNG_HEAD.len() as i64);
+
+        // A file nobody recorded, and a done task whose output is gone
+        let stray = asset_path(&lib, "stray.bin");
+        std::fs::write(&stray, b"not media").unwrap();
+        let task = TaskRow {
+            id: "t1".into(),
+            project_id: "p1".into(),
+            task_type: TaskType::Video,
+            status: TaskStatus::Done,
+            input: r#"{"prompt":"waves"}"#.into(),
+            output: Some(serde_json::json!({ "assetPath": asset_path(&lib, "gone.mp4") }).to_string()),
+            ark_task_id: None,
+            error: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        {
+            let db = lib.db().lock().unwrap();
+            db.insert_asset(&moved).unwrap();
+            db.insert_task(&task).unwrap();
+        }
+
+        let report = scan(&lib, Some("p1"), false).await.unwrap();
+        assert!(report.dry_run);
+        let kinds: Vec<_> = report.issues.iter().map(|i| (i.kind, i.actions.clone())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (IssueKind::MissingFile, vec![ReconcileAction::Reregister, ReconcileAction::Purge]),
+                (IssueKind::UntrackedFile, vec![ReconcileAction::Purge]),
+                (IssueKind::MissingOutput, vec![ReconcileAction::Purge]),
+            ]
+        );
+        assert_eq!(report.issues[0].relink_candidate.as_deref(), Some(renamed.as_str()));
+        // Dry run touched nothing
+        assert!(Path::new(&stray).exists());
+
+        let resolutions: Vec<Resolution> = report
+            .issues
+            .iter()
+            .map(|i| Resolution { issue_id: i.id.clone(), action: i.actions[0] })
+            .collect();
+        let applied = apply(&lib, Some("p1"), &resolutions).await.unwrap();
+        assert!(applied.applied.iter().all(|a| a.ok), "{:?}", applied.applied);
+
+        assert!(!Path::new(&stray).exists());
+        {
+            let db = lib.db().lock().unwrap();
+            assert_eq!(db.get_assets_by_ids(&[moved.id.clone()]).unwrap()[0].file_path, renamed);
+            assert!(db.get_task("t1").unwrap().is_none());
+        }
+
+        assert!(scan(&lib, Some("p1"), false).await.unwrap().issues.is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn trashed_outputs_keep_their_task() {
+        let (root, lib) = setup();
+        let path = asset_path(&lib, "kept.png");
+        std::fs::write(&path, PNG_HEAD).unwrap();
+        let task = TaskRow {
+            id: "t3".into(),
+            project_id: "p1".into(),
+            task_type: TaskType::Image,
+            status: TaskStatus::Done,
+            input: r#"{"prompt":"fox"}"#.into(),
+            output: Some(serde_json::json!({ "assetPath": path }).to_string()),
+            ark_task_id: None,
+            error: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        {
+            let db = lib.db().lock().unwrap();
+            db.insert_task(&task).unwrap();
+            db.insert_asset(&new_asset_row("p1", &path, "image", Some(&task))).unwrap();
+        }
+        let id = lib.db().lock().unwrap().list_all_assets(Some("p1")).unwrap()[0].id.clone();
+
+        // The file goes to the trash with its row; the task is not missing output
+        assert!(lib.delete_assets(&[id], false).unwrap().trashed);
+        assert!(!Path::new(&path).exists());
+        assert!(scan(&lib, Some("p1"), false).await.unwrap().issues.is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn untracked_media_is_registered_with_its_task() {
+        let (root, lib) = setup();
+        let path = asset_path(&lib, "orphan.png");
+        std::fs::write(&path, PNG_HEAD).unwrap();
+        let task = TaskRow {
+            id: "t2".into(),
+            project_id: "p1".into(),
+            task_type: TaskType::Image,
+            status: TaskStatus::Done,
+            input: r#"{"prompt":"fox","model":"m"}"#.into(),
+            output: Some(serde_json::json!({ "assetPath": path }).to_string()),
+            ark_task_id: None,
+            error: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        lib.db().lock().unwrap().insert_task(&task).unwrap();
+        // Left behind by an interrupted bundle import; not a project
+        let staging = lib.projects_dir().join(".import-x/assets");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("a.png"), PNG_HEAD).unwrap();
+
+        let report = scan(&lib, None, false).await.unwrap();
+        assert_eq!(report.issues.len(), 1);
+        let issue = &report.issues[0];
+        assert_eq!((issue.kind, issue.task_id.as_deref()), (IssueKind::UntrackedFile, Some("t2")));
+
+        let res = [Resolution { issue_id: issue.id.clone(), action: ReconcileAction::Reregister }];
+        apply(&lib, None, &res).await.unwrap();
+
+        let assets = lib.db().lock().unwrap().list_all_assets(Some("p1")).unwrap();
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].task_id.as_deref(), Some("t2"));
+        assert_eq!(assets[0].prompt.as_deref(), Some("fox"));
+        assert_eq!((assets[0].width, assets[0].height), (Some(16), Some(8)));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
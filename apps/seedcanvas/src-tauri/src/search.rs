@@ -0,0 +1,142 @@
+//! Full-text search over the asset library.
+//!
+//! Prompt, notes, tags and file name are indexed in the `assets_fts` FTS5
+//! table, which `Db` keeps in step with the assets (see
+//! `Db::create_search_index`). FTS5's `unicode61` tokenizer splits on
+//! whitespace and punctuation only, so a run of Chinese or Japanese would
+//! become one giant token. [`segment`] inserts a zero-width space between
+//! CJK characters before indexing, making each one a token; a CJK query
+//! becomes a phrase of single-character tokens, which matches the characters
+//! adjacent and in order. The zero-width spaces are invisible and stripped
+//! from snippets.
+//!
+//! Query syntax understood by [`match_query`]:
+//!
+//! - `sunset beach` — both terms, each also matching as a prefix (`beach*`)
+//! - `"golden hour"` — exact phrase
+//! - `-night` — exclude
+//!
+//! Everything else is quoted, so user input can never be an FTS5 syntax error.
+
+/// Separator the tokenizer treats as a break but nobody sees.
+const ZWSP: char = '\u{200B}';
+
+/// Highlight markers placed around matches in snippets.
+pub const MARK_START: &str = "<mark>";
+pub const MARK_END: &str = "</mark>";
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x3400..=0x4DBF   // CJK Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF   // Hangul syllables
+        | 0xF900..=0xFAFF   // CJK Compatibility Ideographs
+        | 0x20000..=0x2FA1F // CJK Extensions B–F, Compatibility Supplement
+    )
+}
+
+/// Separate every CJK character from its neighbours with a zero-width space.
+pub fn segment(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / 2);
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        if let Some(p) = prev {
+            let joins = !p.is_whitespace() && !c.is_whitespace();
+            if joins && (is_cjk(p) || is_cjk(c)) {
+                out.push(ZWSP);
+            }
+        }
+        out.push(c);
+        prev = Some(c);
+    }
+    out
+}
+
+/// Turn user input into an FTS5 MATCH expression, or `None` when it has
+/// nothing searchable in it.
+pub fn match_query(input: &str) -> Option<String> {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let (negated, body) = match rest.strip_prefix('-') {
+            Some(b) => (true, b),
+            None => (false, rest),
+        };
+        let (term, is_phrase, next) = if let Some(quoted) = body.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            (&quoted[..end], true, quoted.get(end + 1..).unwrap_or(""))
+        } else {
+            let end = body.find(char::is_whitespace).unwrap_or(body.len());
+            (&body[..end], false, &body[end..])
+        };
+        rest = next.trim_start();
+
+        if !term.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+        let mut expr = format!("\"{}\"", segment(term).replace('"', "\"\""));
+        // Bare Latin words match as prefixes; a single trailing CJK character
+        // as a prefix would match nearly everything
+        if !is_phrase && !term.ends_with(is_cjk) {
+            expr.push('*');
+        }
+        if negated {
+            exclude.push(expr);
+        } else {
+            include.push(expr);
+        }
+    }
+
+    if include.is_empty() {
+        return None;
+    }
+    let mut query = include.join(" AND ");
+    for term in exclude {
+        query.push_str(" NOT ");
+        query.push_str(&term);
+    }
+    Some(query)
+}
+
+/// Remove the indexing separators from an FTS5 snippet and merge highlight
+/// runs that segmentation split apart.
+pub fn clean_snippet(snippet: &str) -> String {
+    snippet
+        .replace(ZWSP, "")
+        .replace(&format!("{MARK_END}{MARK_START}"), "")
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_only_around_cjk() {
+        assert_eq!(segment("red fox"), "red fox");
+        assert_eq!(segment("夜景"), "夜\u{200B}景");
+        assert_eq!(segment("4K夜景 city"), "4K\u{200B}夜\u{200B}景 city");
+    }
+
+    #[test]
+    fn builds_phrase_prefix_and_exclusion_queries() {
+        assert_eq!(match_query("sunset beach").unwrap(), r#""sunset"* AND "beach"*"#);
+        assert_eq!(match_query(r#""golden hour" -night"#).unwrap(), r#""golden hour" NOT "night"*"#);
+        assert_eq!(match_query("夜景").unwrap(), "\"夜\u{200B}景\"");
+        assert_eq!(match_query(r#"say "hi"#).unwrap(), r#""say"* AND "hi""#);
+        assert_eq!(match_query(r#"a"b"#).unwrap(), r#""a""b"*"#);
+        assert!(match_query("  !!! -foo").is_none());
+    }
+
+    #[test]
+    fn snippets_lose_separators_and_split_marks() {
+        let raw = "城市<mark>夜</mark>\u{200B}<mark>景</mark>\u{200B}灯光";
+        assert_eq!(clean_snippet(raw), "城市<mark>夜景</mark>灯光");
+    }
+}
@@ -0,0 +1,246 @@
+//! Near-duplicate detection over the asset library.
+//!
+//! Image assets carry a perceptual hash (`assets.dhash`, see
+//! [`crate::media::perceptual`]). Generated images are hashed when they are
+//! stored; imports and assets from before hashing existed are hashed here on
+//! first use. [`find_similar`] compares one asset against the rest;
+//! [`cluster_duplicates`] groups a project or the whole library into sets of
+//! near duplicates and suggests which one of each to keep.
+//!
+//! Everything here is blocking.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::db::AssetRow;
+use crate::library::AssetLibrary;
+use crate::media::perceptual::{self, DEFAULT_THRESHOLD};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarAsset {
+    pub asset: AssetRow,
+    /// Hamming distance between the perceptual hashes; 0 looks identical.
+    pub distance: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateCluster {
+    /// The asset suggested to keep: favorite first, then highest rated, then
+    /// largest, then oldest.
+    pub keep: String,
+    /// Every member, the suggested keeper first; distances are to it.
+    pub assets: Vec<SimilarAsset>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateReport {
+    /// Image assets compared.
+    pub scanned: usize,
+    pub threshold: u32,
+    /// Largest first.
+    pub clusters: Vec<DuplicateCluster>,
+    /// Assets beyond the keeper of each cluster.
+    pub redundant: usize,
+}
+
+/// Hash image assets that have no hash yet. Returns how many were hashed;
+/// unreadable files are logged and marked so later runs skip them.
+pub fn hash_missing(library: &AssetLibrary, project_id: Option<&str>) -> Result<usize> {
+    let pending = lock(library)?.list_unhashed_images(project_id)?;
+    let mut hashed = 0;
+    for (id, path) in pending {
+        match std::fs::read(&path).map_err(anyhow::Error::from).and_then(|b| perceptual::dhash(&b)) {
+            Ok(hash) => {
+                lock(library)?.set_asset_dhash(&id, hash)?;
+                hashed += 1;
+            }
+            Err(e) => {
+                warn!(asset_id = %id, path = %path, "asset not hashed: {e:#}");
+                lock(library)?.mark_asset_dhash_failed(&id)?;
+            }
+        }
+    }
+    Ok(hashed)
+}
+
+/// Assets within `threshold` of `asset_id`, closest first — across the
+/// library, or within `project_id`.
+pub fn find_similar(
+    library: &AssetLibrary,
+    asset_id: &str,
+    threshold: Option<u32>,
+    project_id: Option<&str>,
+) -> Result<Vec<SimilarAsset>> {
+    let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD);
+    let asset = lock(library)?
+        .get_assets_by_ids(&[asset_id.to_string()])?
+        .pop()
+        .with_context(|| format!("asset {asset_id} does not exist"))?;
+    if asset.asset_type != "image" {
+        bail!("asset {asset_id} is not an image");
+    }
+    hash_missing(library, Some(&asset.project_id))?;
+    hash_missing(library, project_id)?;
+
+    let db = lock(library)?;
+    let target = db
+        .get_asset_dhash(asset_id)?
+        .with_context(|| format!("asset {asset_id} could not be hashed"))?;
+    let mut matches: Vec<(String, u32)> = db
+        .list_asset_dhashes(project_id)?
+        .into_iter()
+        .filter(|(id, _)| id != asset_id)
+        .map(|(id, hash)| (id, perceptual::distance(target, hash)))
+        .filter(|(_, d)| *d <= threshold)
+        .collect();
+    matches.sort_by_key(|(_, d)| *d);
+
+    let distances: HashMap<String, u32> = matches.iter().cloned().collect();
+    let ids: Vec<String> = matches.into_iter().map(|(id, _)| id).collect();
+    Ok(db
+        .get_assets_by_ids(&ids)?
+        .into_iter()
+        .map(|asset| SimilarAsset { distance: distances[&asset.id], asset })
+        .collect())
+}
+
+/// Group image assets whose hashes are within `threshold` of one another —
+/// directly or through a chain of near duplicates.
+pub fn cluster_duplicates(
+    library: &AssetLibrary,
+    project_id: Option<&str>,
+    threshold: Option<u32>,
+) -> Result<DuplicateReport> {
+    let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD);
+    hash_missing(library, project_id)?;
+    let hashes = lock(library)?.list_asset_dhashes(project_id)?;
+
+    // Union-find over every close pair, without holding the lock
+    let mut parent: Vec<usize> = (0..hashes.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..hashes.len() {
+        for j in i + 1..hashes.len() {
+            if perceptual::distance(hashes[i].1, hashes[j].1) <= threshold {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                if a != b {
+                    parent[b] = a;
+                }
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..hashes.len() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+
+    let hash_of: HashMap<&str, u64> = hashes.iter().map(|(id, h)| (id.as_str(), *h)).collect();
+    let db = lock(library)?;
+    let mut clusters = Vec::new();
+    for members in groups.into_values().filter(|m| m.len() > 1) {
+        let ids: Vec<String> = members.iter().map(|&i| hashes[i].0.clone()).collect();
+        let mut assets = db.get_assets_by_ids(&ids)?;
+        assets.sort_by(|a, b| keep_rank(b).cmp(&keep_rank(a)).then_with(|| a.created_at.cmp(&b.created_at)));
+        let Some(keeper) = assets.first() else { continue };
+        let keep = keeper.id.clone();
+        let keeper_hash = hash_of[keep.as_str()];
+        let assets = assets
+            .into_iter()
+            .map(|asset| SimilarAsset { distance: perceptual::distance(keeper_hash, hash_of[asset.id.as_str()]), asset })
+            .collect();
+        clusters.push(DuplicateCluster { keep, assets });
+    }
+    clusters.sort_by(|a, b| b.assets.len().cmp(&a.assets.len()).then_with(|| a.keep.cmp(&b.keep)));
+
+    Ok(DuplicateReport {
+        scanned: hashes.len(),
+        threshold,
+        redundant: clusters.iter().map(|c| c.assets.len() - 1).sum(),
+        clusters,
+    })
+}
+
+/// Higher is a better keeper.
+fn keep_rank(asset: &AssetRow) -> (bool, i32, i64, i64) {
+    let pixels = i64::from(asset.width.unwrap_or(0)) * i64::from(asset.height.unwrap_or(0));
+    (asset.favorite, asset.rating.unwrap_or(0), pixels, asset.file_size.unwrap_or(0))
+}
+
+fn lock(library: &AssetLibrary) -> Result<std::sync::MutexGuard<'_, crate::db::Db>> {
+    library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{asset_row, setup};
+    use image::{DynamicImage, ImageFormat, RgbImage};
+
+    fn add_image(lib: &AssetLibrary, id: &str, img: RgbImage, rating: Option<i32>) {
+        let path = lib.project_dir("p1").join("assets").join(format!("{id}.png"));
+        DynamicImage::ImageRgb8(img.clone()).save_with_format(&path, ImageFormat::Png).unwrap();
+        let asset = AssetRow {
+            width: Some(img.width() as i32),
+            height: Some(img.height() as i32),
+            rating,
+            ..asset_row(id, &path)
+        };
+        lib.db().lock().unwrap().insert_asset(&asset).unwrap();
+    }
+
+    /// Diagonal sawtooth; `mirrored` flips it left to right, which inverts
+    /// every dHash bit.
+    fn sawtooth(w: u32, h: u32, shift: u8, mirrored: bool) -> RgbImage {
+        RgbImage::from_fn(w, h, |x, y| {
+            let x = if mirrored { w - 1 - x } else { x };
+            let v = (((x * 8 / w + y * 8 / h) % 4) * 60) as u8;
+            let v = v.saturating_add(shift);
+            image::Rgb([v, v, v])
+        })
+    }
+
+    #[test]
+    fn clusters_near_duplicates_and_suggests_best_keeper() {
+        let (root, lib) = setup();
+        add_image(&lib, "a", sawtooth(128, 96, 0, false), None);
+        add_image(&lib, "b", sawtooth(64, 48, 4, false), Some(4));
+        add_image(&lib, "c", sawtooth(128, 96, 0, true), None);
+
+        let similar = find_similar(&lib, "a", None, None).unwrap();
+        assert_eq!(similar.iter().map(|s| s.asset.id.as_str()).collect::<Vec<_>>(), vec!["b"]);
+
+        let report = cluster_duplicates(&lib, Some("p1"), None).unwrap();
+        assert_eq!((report.scanned, report.clusters.len(), report.redundant), (3, 1, 1));
+        let cluster = &report.clusters[0];
+        assert_eq!(cluster.keep, "b");
+        assert_eq!(cluster.assets[0].asset.id, "b");
+        assert_eq!(cluster.assets[0].distance, 0);
+
+        // Hashes are stored, so the next run has nothing to compute
+        assert_eq!(hash_missing(&lib, None).unwrap(), 0);
+
+        // An undecodable file is tried once, then left alone
+        add_image(&lib, "d", sawtooth(64, 48, 0, false), None);
+        std::fs::write(lib.project_dir("p1").join("assets/d.png"), b"not a png").unwrap();
+        assert_eq!(hash_missing(&lib, None).unwrap(), 0);
+        assert!(lib.db().lock().unwrap().list_unhashed_images(None).unwrap().is_empty());
+        assert!(find_similar(&lib, "missing", None, None).is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,480 @@
+//! Where asset files live and how they are written.
+//!
+//! Every write is hashed (SHA-256, recorded in `assets.sha256`) so corruption
+//! and truncation can be detected later by [`verify_assets`].
+//!
+//! With `contentAddressed` enabled, files are named by their hash —
+//! `{project}/assets/{sha256}.{ext}` — and identical outputs within a project
+//! share one file. There is no separate counter to drift out of sync: the
+//! asset rows pointing at a path are its references
+//! (`Db::count_asset_file_refs`), and a shared file is only removed together
+//! with its last row.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::db::{AssetRow, SharedDb};
+use crate::media::transcode::{ImageStorageFormat, DEFAULT_IMAGE_QUALITY};
+
+// ---------------------------------------------------------------------------
+// Options — `assetStorage` in settings.json
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageOptions {
+    /// Name files `{sha256}.{ext}` and reuse an existing identical file.
+    #[serde(default)]
+    pub content_addressed: bool,
+    /// Format generated images are stored in; see [`crate::media::transcode`].
+    #[serde(default)]
+    pub image_format: ImageStorageFormat,
+    /// Quality (1–100) for the lossy `jpeg` and `webp` formats.
+    #[serde(default)]
+    pub image_quality: Option<u8>,
+}
+
+impl StorageOptions {
+    pub fn image_quality(&self) -> u8 {
+        self.image_quality.unwrap_or(DEFAULT_IMAGE_QUALITY).clamp(1, 100)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// AssetStore
+// ---------------------------------------------------------------------------
+
+/// A file written by [`AssetStore::write`].
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub path: PathBuf,
+    pub file_name: String,
+    pub sha256: String,
+    pub size: u64,
+    /// An identical file already existed and was reused.
+    pub deduplicated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    projects_dir: PathBuf,
+    options: StorageOptions,
+}
+
+impl AssetStore {
+    pub fn new(projects_dir: PathBuf) -> Self {
+        Self {
+            projects_dir,
+            options: StorageOptions::default(),
+        }
+    }
+
+    pub fn set_options(&mut self, options: StorageOptions) {
+        self.options = options;
+    }
+
+    pub fn options(&self) -> &StorageOptions {
+        &self.options
+    }
+
+    pub fn projects_dir(&self) -> &Path {
+        &self.projects_dir
+    }
+
+    pub fn project_dir(&self, project_id: &str) -> PathBuf {
+        self.projects_dir.join(project_id)
+    }
+
+    pub fn asset_dir(&self, project_id: &str) -> PathBuf {
+        self.project_dir(project_id).join("assets")
+    }
+
+    /// Hash and write `bytes` into the project's `assets/` directory.
+    pub async fn write(&self, project_id: &str, bytes: &[u8], ext: &str) -> Result<StoredFile> {
+        let sha256 = sha256_hex(bytes);
+        let dir = self.asset_dir(project_id);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let file_name = self.file_name_for(&sha256, ext);
+        let path = dir.join(&file_name);
+        let size = bytes.len() as u64;
+
+        if self.options.content_addressed {
+            if let Ok(meta) = tokio::fs::metadata(&path).await {
+                if meta.len() == size {
+                    return Ok(StoredFile { path, file_name, sha256, size, deduplicated: true });
+                }
+                warn!(path = %path.display(), "content-addressed file has wrong size, rewriting");
+            }
+        }
+
+        // Write-then-rename so a crash never leaves a partial file under the final name
+        let tmp = partial_path(&dir, &file_name);
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &path).await?;
+
+        Ok(StoredFile { path, file_name, sha256, size, deduplicated: false })
+    }
+
+    /// Bring an existing file into the project's `assets/` directory, named
+    /// as [`write`](Self::write) would name it. With `remove_source` the
+    /// original is moved (renamed when on the same filesystem) rather than
+    /// copied. Blocking.
+    pub fn import_file(&self, project_id: &str, src: &Path, ext: &str, remove_source: bool) -> Result<StoredFile> {
+        let (sha256, size) = sha256_file(src)?;
+        let dir = self.asset_dir(project_id);
+        std::fs::create_dir_all(&dir)?;
+        let file_name = self.file_name_for(&sha256, ext);
+        let path = dir.join(&file_name);
+
+        let finish = |deduplicated| -> Result<StoredFile> {
+            if remove_source && src.exists() {
+                std::fs::remove_file(src).with_context(|| format!("failed to remove {}", src.display()))?;
+            }
+            Ok(StoredFile { path: path.clone(), file_name: file_name.clone(), sha256: sha256.clone(), size, deduplicated })
+        };
+
+        if self.options.content_addressed
+            && std::fs::metadata(&path).is_ok_and(|meta| meta.len() == size)
+        {
+            return finish(true);
+        }
+        if remove_source && std::fs::rename(src, &path).is_ok() {
+            return finish(false);
+        }
+        let tmp = partial_path(&dir, &file_name);
+        if let Err(e) = std::fs::copy(src, &tmp) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e).with_context(|| format!("failed to copy {}", src.display()));
+        }
+        std::fs::rename(&tmp, &path)?;
+        finish(false)
+    }
+
+    fn file_name_for(&self, sha256: &str, ext: &str) -> String {
+        if self.options.content_addressed {
+            format!("{sha256}.{ext}")
+        } else {
+            format!("{}.{ext}", uuid::Uuid::new_v4())
+        }
+    }
+}
+
+/// Hidden, per-writer temp name next to `file_name`. Concurrent writers of
+/// the same content-addressed file each get their own, so one never renames
+/// the other's half-written bytes into place.
+fn partial_path(dir: &Path, file_name: &str) -> PathBuf {
+    dir.join(format!(".{file_name}.{}.partial", uuid::Uuid::new_v4()))
+}
+
+// ---------------------------------------------------------------------------
+// Hashing
+// ---------------------------------------------------------------------------
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+/// Stream a file through SHA-256. Returns `(hex digest, bytes read)`.
+pub fn sha256_file(path: &Path) -> Result<(String, u64)> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut total = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+    Ok((hex(&hasher.finalize()), total))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// ---------------------------------------------------------------------------
+// Verification
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// File does not exist.
+    Missing,
+    /// File is shorter than the recorded size.
+    Truncated,
+    /// File content no longer matches the recorded hash.
+    HashMismatch,
+    /// File exists but could not be read.
+    Unreadable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetProblem {
+    pub asset_id: String,
+    pub project_id: String,
+    pub file_path: String,
+    pub kind: ProblemKind,
+    pub expected_size: Option<i64>,
+    pub actual_size: Option<u64>,
+    pub expected_sha256: Option<String>,
+    pub actual_sha256: Option<String>,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    pub checked: usize,
+    pub ok: usize,
+    /// Assets that had no hash yet; their current hash was recorded.
+    pub newly_hashed: usize,
+    pub problems: Vec<AssetProblem>,
+}
+
+enum Check {
+    Ok,
+    Unhashed(String),
+    Problem(AssetProblem),
+}
+
+/// Re-hash every asset file (optionally one project) against the DB.
+/// Blocking — the DB lock is only held to read the list and record new hashes.
+pub fn verify_assets(db: &SharedDb, project_id: Option<&str>) -> Result<VerifyReport> {
+    let assets = {
+        let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        guard.list_all_assets(project_id)?
+    };
+
+    let mut report = VerifyReport { checked: assets.len(), ..Default::default() };
+    let mut new_hashes = Vec::new();
+    for asset in &assets {
+        match check_asset(asset) {
+            Check::Ok => report.ok += 1,
+            Check::Unhashed(sha) => {
+                report.ok += 1;
+                new_hashes.push((asset.id.clone(), sha));
+            }
+            Check::Problem(p) => report.problems.push(p),
+        }
+    }
+
+    if !new_hashes.is_empty() {
+        let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        for (id, sha) in &new_hashes {
+            guard.set_asset_sha256(id, sha)?;
+        }
+        report.newly_hashed = new_hashes.len();
+    }
+    Ok(report)
+}
+
+fn check_asset(asset: &AssetRow) -> Check {
+    let problem = |kind, actual_size, actual_sha256, detail| {
+        Check::Problem(AssetProblem {
+            asset_id: asset.id.clone(),
+            project_id: asset.project_id.clone(),
+            file_path: asset.file_path.clone(),
+            kind,
+            expected_size: asset.file_size,
+            actual_size,
+            expected_sha256: asset.sha256.clone(),
+            actual_sha256,
+            detail,
+        })
+    };
+
+    let path = Path::new(&asset.file_path);
+    let size = match std::fs::metadata(path) {
+        Ok(meta) => meta.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return problem(ProblemKind::Missing, None, None, None)
+        }
+        Err(e) => return problem(ProblemKind::Unreadable, None, None, Some(e.to_string())),
+    };
+    if asset.file_size.is_some_and(|expected| (size as i64) < expected) {
+        return problem(ProblemKind::Truncated, Some(size), None, None);
+    }
+
+    let (sha, size) = match sha256_file(path) {
+        Ok(r) => r,
+        Err(e) => return problem(ProblemKind::Unreadable, Some(size), None, Some(format!("{e:#}"))),
+    };
+    match &asset.sha256 {
+        None => Check::Unhashed(sha),
+        Some(expected) if *expected == sha => Check::Ok,
+        Some(_) => problem(ProblemKind::HashMismatch, Some(size), Some(sha), None),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Disk usage
+// ---------------------------------------------------------------------------
+
+/// Bytes on disk under one project directory, by subdirectory.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDiskUsage {
+    pub project_id: String,
+    /// `assets/` — shared content-addressed files are counted once.
+    pub assets: u64,
+    /// `thumbnails/` — previews and video posters.
+    pub thumbnails: u64,
+    /// Manifest, canvas and anything else.
+    pub other: u64,
+    pub total: u64,
+    pub files: u64,
+}
+
+/// Walk every project directory under `projects_dir`, largest first. Hidden
+/// entries (import staging, replaced-project backups) are skipped. Blocking.
+pub fn project_disk_usage(projects_dir: &Path) -> Result<Vec<ProjectDiskUsage>> {
+    let entries = match std::fs::read_dir(projects_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", projects_dir.display())),
+    };
+
+    let mut usage = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let mut project = ProjectDiskUsage { project_id: name, ..Default::default() };
+        let dir = entry.path();
+        for sub in std::fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+            let sub = sub?;
+            let (bytes, files) = dir_size(&sub.path())?;
+            match sub.file_name().to_str() {
+                Some("assets") => project.assets += bytes,
+                Some("thumbnails") => project.thumbnails += bytes,
+                _ => project.other += bytes,
+            }
+            project.files += files;
+        }
+        project.total = project.assets + project.thumbnails + project.other;
+        usage.push(project);
+    }
+    usage.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.project_id.cmp(&b.project_id)));
+    Ok(usage)
+}
+
+/// Total size and file count of `path`, recursing into directories but not
+/// following symlinks.
+fn dir_size(path: &Path) -> Result<(u64, u64)> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(if meta.is_file() { (meta.len(), 1) } else { (0, 0) });
+    }
+    let (mut bytes, mut files) = (0, 0);
+    for entry in std::fs::read_dir(path)? {
+        let (b, f) = dir_size(&entry?.path())?;
+        bytes += b;
+        files += f;
+    }
+    Ok((bytes, files))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Db;
+    use crate::test_support::asset_row;
+    use std::sync::{Arc, Mutex};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("seedcanvas-store-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn asset_for(id: &str, stored: &StoredFile) -> AssetRow {
+        AssetRow {
+            file_name: stored.file_name.clone(),
+            file_size: Some(stored.size as i64),
+            sha256: Some(stored.sha256.clone()),
+            ..asset_row(id, &stored.path)
+        }
+    }
+
+    #[test]
+    fn sha256_matches_known_vector() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[tokio::test]
+    async fn content_addressed_writes_share_one_file() {
+        let dir = temp_dir();
+        let mut store = AssetStore::new(dir.clone());
+        store.set_options(StorageOptions { content_addressed: true, ..Default::default() });
+
+        let a = store.write("p1", b"same bytes", "png").await.unwrap();
+        let b = store.write("p1", b"same bytes", "png").await.unwrap();
+        assert_eq!(a.path, b.path);
+        assert_eq!(a.file_name, format!("{}.png", a.sha256));
+        assert!(!a.deduplicated && b.deduplicated);
+
+        let db = Db::open(&dir.join("test.db")).unwrap();
+        db.insert_asset(&asset_for("a1", &a)).unwrap();
+        db.insert_asset(&asset_for("a2", &b)).unwrap();
+        assert_eq!(db.count_asset_file_refs(&a.path.to_string_lossy()).unwrap(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn verify_reports_missing_truncated_and_modified_files() {
+        let dir = temp_dir();
+        let store = AssetStore::new(dir.clone());
+        let files = write_copies(&store, 4).await;
+        let db = Db::open(&dir.join("test.db")).unwrap();
+        for (i, f) in files.iter().enumerate() {
+            db.insert_asset(&asset_for(&format!("a{i}"), f)).unwrap();
+        }
+        std::fs::remove_file(&files[1].path).unwrap();
+        std::fs::write(&files[2].path, b"0123").unwrap();
+        std::fs::write(&files[3].path, b"9876543210").unwrap();
+
+        let shared: SharedDb = Arc::new(Mutex::new(db));
+        let report = verify_assets(&shared, Some("p1")).unwrap();
+        assert_eq!((report.checked, report.ok), (4, 1));
+        let kinds: Vec<_> = report.problems.iter().map(|p| (p.asset_id.as_str(), p.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a1", ProblemKind::Missing),
+                ("a2", ProblemKind::Truncated),
+                ("a3", ProblemKind::HashMismatch),
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    async fn write_copies(store: &AssetStore, n: usize) -> Vec<StoredFile> {
+        let mut out = Vec::new();
+        for _ in 0..n {
+            out.push(store.write("p1", b"0123456789", "png").await.unwrap());
+        }
+        out
+    }
+}
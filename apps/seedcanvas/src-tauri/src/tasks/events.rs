@@ -0,0 +1,210 @@
+//! Task lifecycle event bus.
+//!
+//! Every state change a `TaskQueue` makes is published once on a tokio
+//! broadcast channel. Consumers (the Tauri frontend emitter, the MCP bridge
+//! push, loggers, webhooks) subscribe independently — the queue itself has
+//! no knowledge of who is listening.
+//!
+//! All events share one payload schema, [`TaskEventPayload`]:
+//!
+//! ```json
+//! {
+//!   "taskId": "…", "projectId": "…", "type": "image" | "video",
+//!   "status": "pending" | "running" | "queued_remote" | "downloading"
+//!           | "done" | "failed" | "cancelled",
+//!   "output": { "assetPath": "…", "width": 2048, "height": 2048 } | null,
+//!   "error": "…" | null,
+//!   "nodeId": "…" | null,
+//!   "createdAt": "…", "updatedAt": "…"
+//! }
+//! ```
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::db::{TaskRow, TaskStatus, TaskType};
+
+/// Buffered events per subscriber before slow receivers start lagging.
+const EVENT_BUS_CAPACITY: usize = 256;
+
+// ---------------------------------------------------------------------------
+// Event types
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub enum TaskEvent {
+    /// Task row was created and queued for execution.
+    Submitted(TaskEventPayload),
+    /// Task moved to an in-flight state (`running`, `queued_remote`,
+    /// `downloading`), including resumption after restart.
+    Progress(TaskEventPayload),
+    /// Task reached a terminal state (`done`, `failed` or `cancelled`).
+    Completed(TaskEventPayload),
+}
+
+impl TaskEvent {
+    /// Event name used on the Tauri side (`listen("task:complete", …)`).
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskEvent::Submitted(_) => "task:submitted",
+            TaskEvent::Progress(_) => "task:progress",
+            TaskEvent::Completed(_) => "task:complete",
+        }
+    }
+
+    pub fn payload(&self) -> &TaskEventPayload {
+        match self {
+            TaskEvent::Submitted(p) | TaskEvent::Progress(p) | TaskEvent::Completed(p) => p,
+        }
+    }
+}
+
+/// The single wire schema shared by every task event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskEventPayload {
+    pub task_id: String,
+    pub project_id: String,
+    #[serde(rename = "type")]
+    pub task_type: TaskType,
+    pub status: TaskStatus,
+    pub output: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub node_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl TaskEventPayload {
+    pub fn from_task(task: &TaskRow) -> Self {
+        Self {
+            task_id: task.id.clone(),
+            project_id: task.project_id.clone(),
+            task_type: task.task_type,
+            status: task.status,
+            output: task
+                .output
+                .as_deref()
+                .and_then(|s| serde_json::from_str(s).ok()),
+            error: task.error.clone(),
+            node_id: serde_json::from_str::<serde_json::Value>(&task.input)
+                .ok()
+                .and_then(|v| v["node_id"].as_str().map(String::from)),
+            created_at: task.created_at.clone(),
+            updated_at: task.updated_at.clone(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Bus
+// ---------------------------------------------------------------------------
+
+/// Cloneable handle to the broadcast channel. Publishing never blocks and
+/// never fails — events with no subscribers are simply dropped.
+#[derive(Clone)]
+pub struct TaskEventBus {
+    tx: broadcast::Sender<TaskEvent>,
+}
+
+impl Default for TaskEventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TaskEventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.tx.subscribe()
+    }
+
+    pub fn publish(&self, event: TaskEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+/// Drive `handler` for every event received on `rx` until the bus is dropped.
+/// A lagging subscriber logs how many events it missed and keeps going.
+pub async fn for_each_event<F>(mut rx: broadcast::Receiver<TaskEvent>, mut handler: F)
+where
+    F: FnMut(TaskEvent),
+{
+    loop {
+        match rx.recv().await {
+            Ok(event) => handler(event),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!(skipped, "task event subscriber lagged");
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests — payload must match what the frontend listeners destructure
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(status: TaskStatus, output: Option<&str>, error: Option<&str>) -> TaskRow {
+        TaskRow {
+            id: "t1".into(),
+            project_id: "p1".into(),
+            task_type: TaskType::Image,
+            status,
+            input: r#"{"prompt":"cat","node_id":"n1"}"#.into(),
+            output: output.map(String::from),
+            ark_task_id: None,
+            error: error.map(String::from),
+            created_at: "2026-01-01T00:00:00Z".into(),
+            updated_at: "2026-01-01T00:00:10Z".into(),
+        }
+    }
+
+    #[test]
+    fn payload_uses_camel_case_schema() {
+        let t = task(TaskStatus::Done, Some(r#"{"assetPath":"/a.png","width":1,"height":2}"#), None);
+        let v = serde_json::to_value(TaskEventPayload::from_task(&t)).unwrap();
+        assert_eq!(v["taskId"], "t1");
+        assert_eq!(v["projectId"], "p1");
+        assert_eq!(v["type"], "image");
+        assert_eq!(v["status"], "done");
+        assert_eq!(v["output"]["assetPath"], "/a.png");
+        assert_eq!(v["nodeId"], "n1");
+        assert!(v["error"].is_null());
+    }
+
+    #[test]
+    fn failed_and_completed_share_the_same_shape() {
+        let done = task(TaskStatus::Done, Some("{}"), None);
+        let failed = task(TaskStatus::Failed, None, Some("boom"));
+        let a = serde_json::to_value(TaskEventPayload::from_task(&done)).unwrap();
+        let b = serde_json::to_value(TaskEventPayload::from_task(&failed)).unwrap();
+        let keys = |v: &serde_json::Value| {
+            let mut k: Vec<_> = v.as_object().unwrap().keys().cloned().collect();
+            k.sort();
+            k
+        };
+        assert_eq!(keys(&a), keys(&b));
+        assert_eq!(b["error"], "boom");
+        assert_eq!(b["projectId"], "p1");
+    }
+
+    #[tokio::test]
+    async fn every_subscriber_receives_each_event() {
+        let bus = TaskEventBus::new();
+        let mut a = bus.subscribe();
+        let mut b = bus.subscribe();
+        bus.publish(TaskEvent::Submitted(TaskEventPayload::from_task(&task(TaskStatus::Pending, None, None))));
+        assert_eq!(a.recv().await.unwrap().name(), "task:submitted");
+        assert_eq!(b.recv().await.unwrap().name(), "task:submitted");
+    }
+}
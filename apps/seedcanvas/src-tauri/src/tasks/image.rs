@@ -1,35 +1,30 @@
 use anyhow::{Context, Result};
 use base64::Engine;
-use std::path::PathBuf;
-use tauri::{AppHandle, Emitter};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+use super::events::TaskEventBus;
 use super::SharedDb;
 use crate::ark::types::ImageGenRequest;
 use crate::ark::ArkClient;
-use crate::db::{AssetRow, TaskRow};
+use crate::db::{AssetRow, TaskRow, TaskStatus};
+use crate::media::provenance::{self, Provenance};
+use crate::media::{palette, perceptual, probe, thumbnail, transcode};
+use crate::storage::AssetStore;
 
 /// Execute image generation: call ARK API, decode base64, write asset, update DB.
 pub async fn run_image_task(
     db: &SharedDb,
     ark: &ArkClient,
-    app_handle: &Option<AppHandle>,
+    events: &TaskEventBus,
     task: &TaskRow,
-    projects_dir: &PathBuf,
+    store: &AssetStore,
 ) {
     let task_id = task.id.clone();
 
-    if let Err(e) = execute(db, ark, task, projects_dir).await {
+    if let Err(e) = execute(db, ark, events, task, store).await {
         error!(task_id = %task_id, "image task failed: {e:#}");
         if let Ok(guard) = db.lock() {
-            let _ = guard.update_task(&task_id, "failed", None, None, Some(&format!("{e:#}")));
-        }
-        if let Some(ref handle) = app_handle {
-            let _ = handle.emit("task:complete", serde_json::json!({
-                "taskId": task_id,
-                "status": "failed",
-                "error": format!("{e:#}"),
-            }));
+            let _ = guard.update_task(&task_id, TaskStatus::Failed, None, None, Some(&format!("{e:#}")));
         }
         return;
     }
@@ -40,8 +35,9 @@ pub async fn run_image_task(
 async fn execute(
     db: &SharedDb,
     ark: &ArkClient,
+    events: &TaskEventBus,
     task: &TaskRow,
-    projects_dir: &PathBuf,
+    store: &AssetStore,
 ) -> Result<()> {
     // Parse input parameters
     let input: serde_json::Value =
@@ -53,18 +49,22 @@ async fn execute(
         .as_str()
         .unwrap_or("doubao-seedream-5-0-260128");
     let size = input["size"].as_str().map(String::from);
+    let reference_ids: Vec<String> = input["reference_asset_ids"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(String::from))
+        .collect();
 
     // Mark as running
-    {
-        let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
-        guard.update_task(&task.id, "running", None, None, None)?;
-    }
+    super::mark_status(db, events, &task.id, TaskStatus::Running, None)?;
 
     // Call ARK image generation API
     let req = ImageGenRequest {
         model: model.to_string(),
         prompt: prompt.to_string(),
         size,
+        image: super::source_data_urls(db, &reference_ids).await?,
         n: Some(1),
         response_format: "b64_json".to_string(),
         watermark: false,
@@ -81,39 +81,82 @@ async fn execute(
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("no b64_json in image response"))?;
 
-    // Decode base64 → write PNG asset
+    // Decode base64, then identify the real format from the magic bytes and
+    // convert it to the configured storage format
     let bytes = base64::engine::general_purpose::STANDARD
         .decode(b64)
         .context("failed to decode base64 image")?;
+    let (target, quality) = (store.options().image_format, store.options().image_quality());
+    let encoded = tokio::task::spawn_blocking(move || transcode::transcode(bytes, target, quality))
+        .await?
+        .context("unusable image in ARK response")?;
+    let (bytes, format) = (encoded.bytes, encoded.format);
+
+    // Read the real dimensions from the file header; fall back to the size
+    // string ARK reported (e.g. "2048x2048") if the header is unreadable.
+    let info = match probe::probe_bytes(&bytes) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warn!(task_id = %task.id, "failed to probe generated image: {e:#}");
+            None
+        }
+    };
+    let (width, height) = info
+        .as_ref()
+        .and_then(|i| i.width.zip(i.height))
+        .or_else(|| item.size.as_deref().and_then(parse_dimensions))
+        .unwrap_or((2048, 2048));
 
-    let asset_dir = projects_dir.join(&task.project_id).join("assets");
-    tokio::fs::create_dir_all(&asset_dir).await?;
-
-    let filename = format!("{}.png", uuid::Uuid::new_v4());
-    let asset_path = asset_dir.join(&filename);
-    tokio::fs::write(&asset_path, &bytes).await?;
+    let record = Provenance {
+        prompt: Some(prompt.to_string()),
+        model: Some(model.to_string()),
+        seed: None,
+        size: Some(format!("{width}x{height}")),
+        task_id: Some(task.id.clone()),
+        created_at: Some(chrono::Utc::now().to_rfc3339()),
+        creator_tool: Some(provenance::CREATOR_TOOL.to_string()),
+    };
+    let bytes = super::with_provenance(bytes, &record, &task.id);
 
-    // Parse dimensions from size string (e.g. "2048x2048") or default
-    let (width, height) = item
-        .size
-        .as_deref()
-        .and_then(parse_dimensions)
-        .unwrap_or((2048, 2048));
+    let stored = store.write(&task.project_id, &bytes, format.extension()).await?;
+    let (asset_path, filename) = (stored.path.clone(), stored.file_name.clone());
 
     let output = serde_json::json!({
         "assetPath": asset_path.to_string_lossy(),
         "width": width,
         "height": height,
+        "usageTokens": resp.usage.and_then(|u| u.billed_tokens()),
     });
 
-    let file_size = bytes.len() as i64;
+    let file_size = stored.size as i64;
+
+    // Library thumbnail, perceptual hash and palette — none may fail the generation
+    let project_dir = store.project_dir(&task.project_id);
+    let thumb_name = filename.clone();
+    let (thumbnail, dhash, colors) = tokio::task::spawn_blocking(move || {
+        (
+            thumbnail::create_thumbnail(&project_dir, &thumb_name, &bytes),
+            perceptual::dhash(&bytes),
+            palette::extract(&bytes),
+        )
+    })
+    .await?;
+    let thumbnail = thumbnail
+        .map_err(|e| warn!(task_id = %task.id, "failed to create thumbnail: {e:#}"))
+        .ok();
+    let dhash = dhash
+        .map_err(|e| warn!(task_id = %task.id, "failed to hash image: {e:#}"))
+        .ok();
+    let colors = colors
+        .map_err(|e| warn!(task_id = %task.id, "failed to extract palette: {e:#}"))
+        .unwrap_or_default();
 
     {
         let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
-        guard.update_task(&task.id, "done", Some(&output.to_string()), None, None)?;
+        guard.update_task(&task.id, TaskStatus::Done, Some(&output.to_string()), None, None)?;
 
         // Record the generated asset in the assets table
-        let asset = AssetRow {
+        let mut asset = AssetRow {
             id: uuid::Uuid::new_v4().to_string(),
             project_id: task.project_id.clone(),
             task_id: Some(task.id.clone()),
@@ -127,9 +170,31 @@ async fn execute(
             file_size: Some(file_size),
             source: "generated".to_string(),
             created_at: task.created_at.clone(),
+            duration: None,
+            frame_rate: None,
+            codec: None,
+            thumbnail_path: thumbnail.map(|p| p.to_string_lossy().to_string()),
+            poster_path: None,
+            sha256: Some(stored.sha256.clone()),
+            favorite: false,
+            rating: None,
+            tags: Vec::new(),
+            palette: colors,
+            notes: None,
         };
-        if let Err(e) = guard.insert_asset(&asset) {
-            error!(task_id = %task.id, "failed to insert asset record: {e:#}");
+        if let Some(ref info) = info {
+            asset.set_media_info(info);
+        }
+        match guard.insert_asset(&asset) {
+            Ok(_) => {
+                super::record_lineage(&guard, &asset.id, &task.id, &input);
+                if let Some(dhash) = dhash {
+                    if let Err(e) = guard.set_asset_dhash(&asset.id, dhash) {
+                        warn!(task_id = %task.id, "failed to store image hash: {e:#}");
+                    }
+                }
+            }
+            Err(e) => error!(task_id = %task.id, "failed to insert asset record: {e:#}"),
         }
     }
 
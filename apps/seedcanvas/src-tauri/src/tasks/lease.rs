@@ -0,0 +1,136 @@
+//! Cross-process task ownership.
+//!
+//! The desktop app and `seedcanvas-mcp` share `seedcanvas.db` and each run a
+//! `TaskQueue`. A queue only executes a task while holding its lease (see the
+//! lease section of [`crate::db::Db`]); [`TaskLease::hold`] renews it on a
+//! heartbeat and abandons the work if another process has taken it over.
+
+use std::future::Future;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::{error, warn};
+
+use crate::db::SharedDb;
+
+/// How long a lease stays valid without a heartbeat.
+pub const LEASE_TTL: Duration = Duration::from_secs(30);
+/// Renew well before expiry so one slow tick doesn't lose the lease.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Unique per `TaskQueue` instance: `{pid}-{random}`. The PID alone can be
+/// reused after a restart, so a random suffix keeps old leases distinct.
+pub fn new_owner_id() -> String {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    format!("{}-{}", std::process::id(), &suffix[..8])
+}
+
+pub struct TaskLease {
+    db: SharedDb,
+    owner: String,
+    task_id: String,
+}
+
+impl TaskLease {
+    pub fn new(db: SharedDb, owner: String, task_id: String) -> Self {
+        Self { db, owner, task_id }
+    }
+
+    /// Drive `work` to completion while renewing the lease, then release it.
+    /// Returns `None` — dropping `work` mid-flight — if the lease was taken over.
+    pub async fn hold<F: Future>(&self, work: F) -> Option<F::Output> {
+        tokio::pin!(work);
+        let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        heartbeat.tick().await; // first tick completes immediately
+
+        loop {
+            tokio::select! {
+                output = &mut work => {
+                    self.release();
+                    return Some(output);
+                }
+                _ = heartbeat.tick() => {
+                    if !self.renew() {
+                        warn!(task_id = %self.task_id, owner = %self.owner, "task lease lost, abandoning execution");
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// False only when the lease is definitely gone; DB errors keep the task
+    /// running and are retried on the next heartbeat.
+    fn renew(&self) -> bool {
+        match self.db.lock() {
+            Ok(db) => match db.renew_lease(&self.task_id, &self.owner, LEASE_TTL) {
+                Ok(held) => held,
+                Err(e) => {
+                    error!(task_id = %self.task_id, "lease renewal failed: {e:#}");
+                    true
+                }
+            },
+            Err(e) => {
+                error!(task_id = %self.task_id, "db lock poisoned during lease renewal: {e}");
+                true
+            }
+        }
+    }
+
+    fn release(&self) {
+        if let Ok(db) = self.db.lock() {
+            if let Err(e) = db.release_lease(&self.task_id, &self.owner) {
+                error!(task_id = %self.task_id, "lease release failed: {e:#}");
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests — two connections to one DB file stand in for the two processes
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{Db, TaskRow, TaskStatus, TaskType};
+    use std::time::Duration;
+
+    fn task(id: &str) -> TaskRow {
+        let now = chrono::Utc::now().to_rfc3339();
+        TaskRow {
+            id: id.into(),
+            project_id: "p1".into(),
+            task_type: TaskType::Image,
+            status: TaskStatus::Running,
+            input: "{}".into(),
+            output: None,
+            ark_task_id: None,
+            error: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn live_lease_blocks_other_owner_and_expired_lease_is_taken_over() {
+        let path = std::env::temp_dir().join(format!("seedcanvas-lease-{}.db", uuid::Uuid::new_v4()));
+        let app = Db::open(&path).unwrap();
+        let mcp = Db::open(&path).unwrap();
+
+        app.insert_claimed_task(&task("live"), "app", Duration::from_secs(30)).unwrap();
+        assert!(!mcp.claim_task("live", "mcp", Duration::from_secs(30)).unwrap());
+        assert!(app.renew_lease("live", "app", Duration::from_secs(30)).unwrap());
+        assert!(mcp.get_claimable_tasks().unwrap().is_empty());
+
+        // Owner "crashed": its lease has already expired.
+        app.insert_claimed_task(&task("stale"), "app", Duration::ZERO).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        let claimable: Vec<_> = mcp.get_claimable_tasks().unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(claimable, vec!["stale".to_string()]);
+        assert!(mcp.claim_task("stale", "mcp", Duration::from_secs(30)).unwrap());
+        assert!(!app.claim_task("stale", "app", Duration::from_secs(30)).unwrap());
+        assert!(!app.renew_lease("stale", "app", Duration::from_secs(30)).unwrap());
+
+        drop((app, mcp));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,19 +1,28 @@
+pub mod events;
 pub mod image;
+pub mod lease;
+pub mod poller;
 pub mod video;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
-use tracing::{error, info};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
 
+use crate::ark::callback::ArkCallbacks;
 use crate::ark::ArkClient;
-use crate::db::{Db, SharedDb, TaskRow};
-
-/// Callback invoked when a task completes (used in headless mode to notify the frontend
-/// via the Unix socket bridge instead of Tauri events).
-pub type OnCompleteCallback = Arc<dyn Fn(TaskRow) + Send + Sync>;
+use crate::db::{Db, LinkRelation, SharedDb, TaskRow, TaskStatus, TaskType};
+use crate::media::probe::MediaFormat;
+use crate::media::provenance::{self, Provenance};
+use crate::pricing::PriceTable;
+use crate::storage::{AssetStore, StorageOptions};
+use events::{TaskEvent, TaskEventBus, TaskEventPayload};
+use lease::{TaskLease, LEASE_TTL};
+use poller::{PollPolicy, VideoPoller};
 
 // ---------------------------------------------------------------------------
 // Valid values — single source of truth for Tauri commands AND future MCP tools
@@ -69,6 +78,11 @@ pub const DEFAULT_VIDEO_RESOLUTION: &str = "720p";
 pub const DEFAULT_VIDEO_RATIO: &str = "16:9";
 pub const DEFAULT_VIDEO_DURATION: i32 = 5;
 
+/// Seedream accepts at most 15 images per request, inputs and output combined.
+pub const MAX_REFERENCE_IMAGES: usize = 14;
+/// Text-to-video only — no first frame.
+const TEXT_ONLY_VIDEO_MODELS: &[&str] = &["doubao-seedance-1-0-lite-t2v-250428"];
+
 // ---------------------------------------------------------------------------
 // Submit parameters — with validation + defaults
 // ---------------------------------------------------------------------------
@@ -80,6 +94,14 @@ pub struct ImageParams {
     pub model: Option<String>,
     pub node_id: Option<String>,
     pub size: Option<String>,
+    /// Library images sent to the model as references.
+    #[serde(default)]
+    pub reference_asset_ids: Vec<String>,
+    /// Lineage only: the asset this generation varies or retries.
+    #[serde(default)]
+    pub variant_of: Option<String>,
+    #[serde(default)]
+    pub retry_of: Option<String>,
 }
 
 impl ImageParams {
@@ -99,6 +121,9 @@ impl ImageParams {
         if !IMAGE_SIZES.contains(&size.as_str()) {
             bail!("invalid image size \"{size}\". Valid: {}", IMAGE_SIZES.join(", "));
         }
+        if self.reference_asset_ids.len() > MAX_REFERENCE_IMAGES {
+            bail!("at most {MAX_REFERENCE_IMAGES} reference images, got {}", self.reference_asset_ids.len());
+        }
         Ok(())
     }
 }
@@ -112,6 +137,14 @@ pub struct VideoParams {
     pub resolution: Option<String>,
     pub ratio: Option<String>,
     pub duration: Option<i32>,
+    /// Library image used as the video's first frame.
+    #[serde(default)]
+    pub first_frame_asset_id: Option<String>,
+    /// Lineage only: the asset this generation varies or retries.
+    #[serde(default)]
+    pub variant_of: Option<String>,
+    #[serde(default)]
+    pub retry_of: Option<String>,
 }
 
 impl VideoParams {
@@ -139,6 +172,9 @@ impl VideoParams {
         if !(2..=12).contains(dur) {
             bail!("duration must be 2-12 seconds, got {dur}");
         }
+        if self.first_frame_asset_id.is_some() && TEXT_ONLY_VIDEO_MODELS.contains(&model.as_str()) {
+            bail!("model \"{model}\" is text-to-video only and cannot take a first frame");
+        }
         Ok(())
     }
 }
@@ -157,63 +193,76 @@ pub struct UserDefaults {
 pub struct TaskQueue {
     db: SharedDb,
     ark: Arc<ArkClient>,
-    app_handle: Option<AppHandle>,
-    projects_dir: PathBuf,
-    on_complete: Option<OnCompleteCallback>,
+    poller: Arc<VideoPoller>,
+    callbacks: Option<ArkCallbacks>,
+    events: TaskEventBus,
+    store: AssetStore,
+    prices: Arc<PriceTable>,
     user_defaults: UserDefaults,
+    /// Lease owner ID for tasks this queue executes (see [`lease`]).
+    owner_id: String,
 }
 
 impl TaskQueue {
-    /// Create a TaskQueue with a Tauri AppHandle (normal app mode).
-    pub fn new(db: Db, ark: ArkClient, app_handle: AppHandle, projects_dir: PathBuf, user_defaults: UserDefaults) -> Self {
-        Self {
-            db: Arc::new(std::sync::Mutex::new(db)),
-            ark: Arc::new(ark),
-            app_handle: Some(app_handle),
-            projects_dir,
-            on_complete: None,
-            user_defaults,
-        }
-    }
-
-    /// Create a TaskQueue without a Tauri AppHandle (headless MCP mode).
-    pub fn new_headless(db: Db, ark: ArkClient, projects_dir: PathBuf, user_defaults: UserDefaults) -> Self {
-        Self {
-            db: Arc::new(std::sync::Mutex::new(db)),
-            ark: Arc::new(ark),
-            app_handle: None,
-            projects_dir,
-            on_complete: None,
-            user_defaults,
-        }
+    /// Create a TaskQueue that owns its database handle.
+    pub fn new(db: Db, ark: ArkClient, projects_dir: PathBuf, user_defaults: UserDefaults) -> Self {
+        Self::new_with_shared(Arc::new(std::sync::Mutex::new(db)), ark, projects_dir, user_defaults)
     }
 
     /// Create a TaskQueue with a pre-wrapped SharedDb (used when DB is shared across subsystems).
-    pub fn new_with_shared(db: SharedDb, ark: ArkClient, app_handle: AppHandle, projects_dir: PathBuf, user_defaults: UserDefaults) -> Self {
+    pub fn new_with_shared(db: SharedDb, ark: ArkClient, projects_dir: PathBuf, user_defaults: UserDefaults) -> Self {
+        let ark = Arc::new(ark);
         Self {
             db,
-            ark: Arc::new(ark),
-            app_handle: Some(app_handle),
-            projects_dir,
-            on_complete: None,
+            poller: Arc::new(VideoPoller::new(Arc::clone(&ark))),
+            ark,
+            callbacks: None,
+            events: TaskEventBus::new(),
+            store: AssetStore::new(projects_dir),
+            prices: Arc::new(PriceTable::default()),
             user_defaults,
+            owner_id: lease::new_owner_id(),
         }
     }
 
-    /// Register a callback for task completion (headless mode).
-    /// Called after a task finishes with the updated TaskRow.
-    pub fn set_on_complete(&mut self, cb: OnCompleteCallback) {
-        self.on_complete = Some(cb);
+    /// Route video completion through ARK callbacks (see [`crate::ark::callback`]).
+    /// Polling continues at a slower rate as a fallback.
+    pub fn set_callbacks(&mut self, callbacks: ArkCallbacks) {
+        self.callbacks = Some(callbacks);
+    }
+
+    /// Override video polling per model (keyed by model ID); other models keep
+    /// their built-in [`PollPolicy`].
+    pub fn set_poll_policies(&self, policies: HashMap<String, PollPolicy>) {
+        self.poller.set_policy_overrides(policies);
+    }
+
+    /// Configure how generated files are laid out (see [`crate::storage`]).
+    pub fn set_storage_options(&mut self, options: StorageOptions) {
+        self.store.set_options(options);
+    }
+
+    /// Price tasks with `prices` (see [`crate::pricing`]).
+    pub fn set_price_table(&mut self, prices: PriceTable) {
+        self.prices = Arc::new(prices);
+    }
+
+    /// Subscribe to task lifecycle events. Each receiver sees every event
+    /// published after the call; see [`events`] for the payload schema.
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.events.subscribe()
     }
 
     /// Submit an image generation task. Returns the task ID immediately.
     pub fn submit_image(&self, mut params: ImageParams) -> Result<String> {
         params.normalize(self.user_defaults.default_image_model.as_deref())?;
         self.validate_project_exists(&params.project_id)?;
+        self.validate_sources(&params.reference_asset_ids, [&params.variant_of, &params.retry_of])?;
         let project_id = params.project_id.clone();
-        let task = self.create_task_row(&project_id, "image", &params)?;
+        let task = self.create_task_row(&project_id, TaskType::Image, &params)?;
         let task_id = task.id.clone();
-        self.emit_submitted(&task_id, &project_id, "image");
+        record_estimate(&self.db, &self.prices, &task);
+        self.events.publish(TaskEvent::Submitted(TaskEventPayload::from_task(&task)));
         self.spawn_image(task);
         Ok(task_id)
     }
@@ -222,10 +271,13 @@ impl TaskQueue {
     pub fn submit_video(&self, mut params: VideoParams) -> Result<String> {
         params.normalize(self.user_defaults.default_video_model.as_deref())?;
         self.validate_project_exists(&params.project_id)?;
+        let first_frame: Vec<String> = params.first_frame_asset_id.iter().cloned().collect();
+        self.validate_sources(&first_frame, [&params.variant_of, &params.retry_of])?;
         let project_id = params.project_id.clone();
-        let task = self.create_task_row(&project_id, "video", &params)?;
+        let task = self.create_task_row(&project_id, TaskType::Video, &params)?;
         let task_id = task.id.clone();
-        self.emit_submitted(&task_id, &project_id, "video");
+        record_estimate(&self.db, &self.prices, &task);
+        self.events.publish(TaskEvent::Submitted(TaskEventPayload::from_task(&task)));
         self.spawn_video(task);
         Ok(task_id)
     }
@@ -236,35 +288,51 @@ impl TaskQueue {
         db.get_task(task_id)
     }
 
-    /// Resume any tasks that were left in "running" state (e.g. after app restart).
+    /// Claim and resume unfinished tasks whose lease is free or expired — left
+    /// over from an app restart, or from a crashed `seedcanvas-mcp` / app
+    /// process. Tasks leased by a live process are left alone.
     pub fn resume_running_tasks(&self) -> Result<()> {
-        let running = {
+        let claimed: Vec<TaskRow> = {
             let db = self.db.lock().map_err(|e| anyhow::anyhow!("db lock poisoned: {e}"))?;
-            db.get_running_tasks()?
+            let mut claimed = Vec::new();
+            for task in db.get_claimable_tasks()? {
+                if db.claim_task(&task.id, &self.owner_id, LEASE_TTL)? {
+                    claimed.push(task);
+                }
+            }
+            claimed
         };
-        if running.is_empty() {
+        if claimed.is_empty() {
             return Ok(());
         }
-        info!(count = running.len(), "resuming running tasks");
-        for task in running {
-            match task.task_type.as_str() {
-                "image" => self.spawn_image(task),
-                "video" => self.spawn_video(task),
-                other => {
-                    error!(task_type = %other, task_id = %task.id, "unknown task type during resume");
-                }
+        info!(count = claimed.len(), owner = %self.owner_id, "resuming claimed tasks");
+        for task in claimed {
+            match task.task_type {
+                TaskType::Image => self.spawn_image(task),
+                TaskType::Video => self.spawn_video(task),
             }
         }
         Ok(())
     }
 
+    /// Periodically take over tasks whose lease expired while this process
+    /// was running (e.g. the other process crashed). Runs until the queue is dropped.
+    pub async fn sweep_stale_leases(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(LEASE_TTL).await;
+            if let Err(e) = self.resume_running_tasks() {
+                error!("stale lease sweep failed: {e:#}");
+            }
+        }
+    }
+
     // -----------------------------------------------------------------------
     // Internals
     // -----------------------------------------------------------------------
 
     /// Reject tasks for non-existent projects to prevent orphan directory creation.
     fn validate_project_exists(&self, project_id: &str) -> Result<()> {
-        let manifest = self.projects_dir.join(project_id).join("manifest.json");
+        let manifest = self.store.project_dir(project_id).join("manifest.json");
         if !manifest.exists() {
             bail!(
                 "project \"{project_id}\" does not exist (no manifest.json found). \
@@ -274,28 +342,36 @@ impl TaskQueue {
         Ok(())
     }
 
-    fn emit_submitted(&self, task_id: &str, project_id: &str, task_type: &str) {
-        if let Some(ref handle) = self.app_handle {
-            let _ = handle.emit("task:submitted", serde_json::json!({
-                "taskId": task_id,
-                "projectId": project_id,
-                "type": task_type,
-            }));
+    /// Source assets must exist; those sent to the model must be images.
+    fn validate_sources(&self, inputs: &[String], lineage: [&Option<String>; 2]) -> Result<()> {
+        let lineage: Vec<String> = lineage.into_iter().flatten().cloned().collect();
+        if inputs.is_empty() && lineage.is_empty() {
+            return Ok(());
         }
+        let db = self.db.lock().map_err(|e| anyhow::anyhow!("db lock poisoned: {e}"))?;
+        for id in inputs.iter().chain(&lineage) {
+            let Some(asset) = db.get_assets_by_ids(std::slice::from_ref(id))?.pop() else {
+                bail!("source asset {id} does not exist");
+            };
+            if inputs.contains(id) && asset.asset_type != "image" {
+                bail!("source asset {id} is a {}, expected an image", asset.asset_type);
+            }
+        }
+        Ok(())
     }
 
     fn create_task_row<T: Serialize>(
         &self,
         project_id: &str,
-        task_type: &str,
+        task_type: TaskType,
         params: &T,
     ) -> Result<TaskRow> {
         let now = chrono::Utc::now().to_rfc3339();
         let task = TaskRow {
             id: uuid::Uuid::new_v4().to_string(),
             project_id: project_id.to_string(),
-            task_type: task_type.to_string(),
-            status: "pending".to_string(),
+            task_type,
+            status: TaskStatus::Pending,
             input: serde_json::to_string(params)?,
             output: None,
             ark_task_id: None,
@@ -304,29 +380,23 @@ impl TaskQueue {
             updated_at: now,
         };
         let db = self.db.lock().map_err(|e| anyhow::anyhow!("db lock poisoned: {e}"))?;
-        db.insert_task(&task)?;
+        db.insert_claimed_task(&task, &self.owner_id, LEASE_TTL)?;
         Ok(task)
     }
 
     fn spawn_image(&self, task: TaskRow) {
         let db = Arc::clone(&self.db);
         let ark = Arc::clone(&self.ark);
-        let app_handle = self.app_handle.clone();
-        let on_complete = self.on_complete.clone();
-        let projects_dir = self.projects_dir.clone();
+        let events = self.events.clone();
+        let store = self.store.clone();
+        let prices = Arc::clone(&self.prices);
+        let lease = self.lease_for(&task);
 
         tokio::spawn(async move {
-            image::run_image_task(&db, &ark, &app_handle, &task, &projects_dir).await;
-            let updated = db.lock().ok().and_then(|g| g.get_task(&task.id).ok().flatten());
-            if let Some(ref updated) = updated {
-                // Tauri app mode: emit event to frontend
-                if let Some(ref handle) = app_handle {
-                    let _ = handle.emit("task:complete", task_complete_payload(updated));
-                }
-                // Headless mode: invoke callback (e.g. push via socket bridge)
-                if let Some(ref cb) = on_complete {
-                    cb(updated.clone());
-                }
+            let run = image::run_image_task(&db, &ark, &events, &task, &store);
+            if lease.hold(run).await.is_some() {
+                settle_cost(&db, &prices, &task.id);
+                publish_completed(&db, &events, &task.id);
             }
         });
     }
@@ -334,35 +404,159 @@ impl TaskQueue {
     fn spawn_video(&self, task: TaskRow) {
         let db = Arc::clone(&self.db);
         let ark = Arc::clone(&self.ark);
-        let app_handle = self.app_handle.clone();
-        let on_complete = self.on_complete.clone();
-        let projects_dir = self.projects_dir.clone();
+        let poller = Arc::clone(&self.poller);
+        let callbacks = self.callbacks.clone();
+        let events = self.events.clone();
+        let store = self.store.clone();
+        let prices = Arc::clone(&self.prices);
+        let lease = self.lease_for(&task);
 
         tokio::spawn(async move {
-            video::run_video_task(&db, &ark, &app_handle, &task, &projects_dir).await;
-            let updated = db.lock().ok().and_then(|g| g.get_task(&task.id).ok().flatten());
-            if let Some(ref updated) = updated {
-                if let Some(ref handle) = app_handle {
-                    let _ = handle.emit("task:complete", task_complete_payload(updated));
-                }
-                if let Some(ref cb) = on_complete {
-                    cb(updated.clone());
-                }
+            let run = video::run_video_task(&db, &ark, &poller, callbacks.as_ref(), &events, &task, &store);
+            if lease.hold(run).await.is_some() {
+                settle_cost(&db, &prices, &task.id);
+                publish_completed(&db, &events, &task.id);
             }
         });
     }
+
+    fn lease_for(&self, task: &TaskRow) -> TaskLease {
+        TaskLease::new(Arc::clone(&self.db), self.owner_id.clone(), task.id.clone())
+    }
+}
+
+/// Store the submission estimate of a priced task. Pricing is bookkeeping
+/// only and never blocks a submission.
+fn record_estimate(db: &SharedDb, prices: &PriceTable, task: &TaskRow) {
+    let input: serde_json::Value = serde_json::from_str(&task.input).unwrap_or_default();
+    let Some(cost) = prices.estimate(task.task_type, &input) else {
+        return;
+    };
+    let result = db
+        .lock()
+        .map_err(|e| anyhow::anyhow!("db lock: {e}"))
+        .and_then(|g| g.set_task_estimate(&task.id, &prices.currency, cost.amount));
+    if let Err(e) = result {
+        warn!(task_id = %task.id, "failed to record cost estimate: {e:#}");
+    }
+}
+
+/// Price a finished task from its output — ARK's token usage where the
+/// model has a token price, otherwise what was produced.
+fn settle_cost(db: &SharedDb, prices: &PriceTable, task_id: &str) {
+    let result = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}")).and_then(|g| {
+        let Some(task) = g.get_task(task_id)?.filter(|t| t.status == TaskStatus::Done) else {
+            return Ok(());
+        };
+        let input: serde_json::Value = serde_json::from_str(&task.input).unwrap_or_default();
+        let output: serde_json::Value = task.output.as_deref().and_then(|o| serde_json::from_str(o).ok()).unwrap_or_default();
+        match prices.settle(task.task_type, &input, &output) {
+            Some(cost) => g.set_task_actual_cost(
+                task_id,
+                &prices.currency,
+                cost.amount,
+                output["usageTokens"].as_i64(),
+                cost.basis.as_str(),
+            ),
+            None => Ok(()),
+        }
+    });
+    if let Err(e) = result {
+        warn!(task_id, "failed to record task cost: {e:#}");
+    }
+}
+
+/// Re-read the task after execution and publish its terminal state.
+fn publish_completed(db: &SharedDb, events: &TaskEventBus, task_id: &str) {
+    let updated = db.lock().ok().and_then(|g| g.get_task(task_id).ok().flatten());
+    if let Some(ref updated) = updated {
+        events.publish(TaskEvent::Completed(TaskEventPayload::from_task(updated)));
+    }
+}
+
+/// Read library images into `data:` URLs, the form ARK accepts inline.
+async fn source_data_urls(db: &SharedDb, ids: &[String]) -> Result<Vec<String>> {
+    let paths: Vec<PathBuf> = {
+        let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        let assets = guard.get_assets_by_ids(ids)?;
+        ids.iter()
+            .map(|id| {
+                assets
+                    .iter()
+                    .find(|a| &a.id == id)
+                    .map(|a| PathBuf::from(&a.file_path))
+                    .ok_or_else(|| anyhow::anyhow!("source asset {id} no longer exists"))
+            })
+            .collect::<Result<_>>()?
+    };
+    let mut urls = Vec::with_capacity(paths.len());
+    for path in paths {
+        let bytes = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("failed to read source image {}", path.display()))?;
+        let mime = MediaFormat::sniff(&bytes)
+            .map(MediaFormat::mime_type)
+            .filter(|m| m.starts_with("image/"))
+            .ok_or_else(|| anyhow::anyhow!("{} is not a PNG, JPEG or WebP image", path.display()))?;
+        urls.push(format!("data:{mime};base64,{}", base64::engine::general_purpose::STANDARD.encode(&bytes)));
+    }
+    Ok(urls)
+}
+
+/// Embed `record` in a generated file. A file that cannot take it is stored
+/// as it came.
+fn with_provenance(bytes: Vec<u8>, record: &Provenance, task_id: &str) -> Vec<u8> {
+    match provenance::embed(&bytes, record) {
+        Ok(Some(tagged)) => tagged,
+        Ok(None) => bytes,
+        Err(e) => {
+            warn!(task_id, "failed to embed provenance: {e:#}");
+            bytes
+        }
+    }
+}
+
+/// Link a generated asset to the assets named in its task input. A source
+/// deleted while the task ran only loses its link.
+fn record_lineage(db: &Db, asset_id: &str, task_id: &str, input: &serde_json::Value) {
+    let mut sources: Vec<(&str, LinkRelation)> = input["reference_asset_ids"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+        .map(|id| (id, LinkRelation::ReferenceOf))
+        .collect();
+    for (key, relation) in [
+        ("first_frame_asset_id", LinkRelation::FirstFrameOf),
+        ("variant_of", LinkRelation::VariantOf),
+        ("retry_of", LinkRelation::RetryOf),
+    ] {
+        if let Some(id) = input[key].as_str() {
+            sources.push((id, relation));
+        }
+    }
+    for (source_id, relation) in sources {
+        if let Err(e) = db.add_asset_link(source_id, asset_id, relation, Some(task_id)) {
+            warn!(task_id, source_id, "failed to record {relation} link: {e:#}");
+        }
+    }
 }
 
-fn task_complete_payload(task: &TaskRow) -> serde_json::Value {
-    serde_json::json!({
-        "taskId": task.id,
-        "projectId": task.project_id,
-        "type": task.task_type,
-        "status": task.status,
-        "output": task.output.as_deref().and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok()),
-        "error": task.error,
-        "nodeId": serde_json::from_str::<serde_json::Value>(&task.input)
-            .ok()
-            .and_then(|v| v["node_id"].as_str().map(String::from)),
-    })
+/// Move a task to an in-flight state and publish the transition.
+fn mark_status(
+    db: &SharedDb,
+    events: &TaskEventBus,
+    task_id: &str,
+    status: TaskStatus,
+    ark_task_id: Option<&str>,
+) -> Result<()> {
+    let updated = {
+        let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
+        guard.update_task(task_id, status, None, ark_task_id, None)?;
+        guard.get_task(task_id)?
+    };
+    if let Some(ref updated) = updated {
+        events.publish(TaskEvent::Progress(TaskEventPayload::from_task(updated)));
+    }
+    Ok(())
 }
@@ -0,0 +1,574 @@
+//! Shared video status poller.
+//!
+//! Instead of one sleep/GET loop per video task, a single background loop
+//! tracks every in-flight ARK task ID. Each task backs off independently
+//! according to its model's [`PollPolicy`], and checks that fall due at about
+//! the same time are coalesced into one list query
+//! (`GET /contents/generations/tasks?filter.task_ids=…`). If the list endpoint
+//! is unavailable the poller falls back to per-task GETs for the session.
+//!
+//! ARK callbacks (see [`crate::ark::callback`]) wake a task for an immediate
+//! check; polling then only runs at the slow fallback interval.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Duration, Instant};
+use tracing::{info, warn};
+
+use crate::ark::callback::ArkCallbacks;
+use crate::ark::types::VideoTaskStatus;
+use crate::ark::ArkClient;
+
+/// Most task IDs sent in a single list query.
+const BATCH_LIMIT: usize = 100;
+/// Checks due within this window of the earliest one are polled together.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+/// Interval growth per unfinished check, capped at the policy's max.
+const BACKOFF_FACTOR: f64 = 1.5;
+/// Give up on a task after this many status requests fail in a row.
+const MAX_CONSECUTIVE_ERRORS: u32 = 3;
+/// Safety-net interval while ARK callbacks are enabled, in case one is lost.
+const CALLBACK_FALLBACK_INTERVAL: Duration = Duration::from_secs(60);
+/// How long the loop sleeps when nothing is tracked.
+const IDLE_WAIT: Duration = Duration::from_secs(3600);
+/// How long per-task polling stands in after a list query fails.
+const BATCH_RETRY_AFTER: Duration = Duration::from_secs(600);
+
+// ---------------------------------------------------------------------------
+// Policy — configurable per model via settings.json `videoPollPolicies`
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PollPolicy {
+    /// Delay before the first check; also the starting interval.
+    pub initial_interval_secs: u64,
+    /// Ceiling the interval backs off to.
+    pub max_interval_secs: u64,
+    /// Fail the task once it has been in flight this long.
+    pub timeout_secs: u64,
+}
+
+impl PollPolicy {
+    /// Built-in policy for `model`, tuned to its typical generation time:
+    /// fast/lite models usually finish within a minute, pro models take 1-5 min.
+    pub fn for_model(model: &str) -> Self {
+        if model.contains("-fast-") || model.contains("-lite-") {
+            Self { initial_interval_secs: 3, max_interval_secs: 15, timeout_secs: 600 }
+        } else {
+            Self { initial_interval_secs: 10, max_interval_secs: 30, timeout_secs: 900 }
+        }
+    }
+
+    fn initial_interval(&self) -> Duration {
+        Duration::from_secs(self.initial_interval_secs.max(1))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// Interval to wait after `checks` unfinished status checks.
+    fn interval_after(&self, checks: u32) -> Duration {
+        let initial = self.initial_interval().as_secs_f64();
+        let max = (self.max_interval_secs as f64).max(initial);
+        let secs = initial * BACKOFF_FACTOR.powi(checks.min(32) as i32);
+        Duration::from_secs_f64(secs.min(max))
+    }
+
+    /// Stretch intervals to the fallback rate when callbacks drive completion.
+    fn with_callbacks(self) -> Self {
+        let fallback = CALLBACK_FALLBACK_INTERVAL.as_secs();
+        Self {
+            initial_interval_secs: self.initial_interval_secs.max(fallback),
+            max_interval_secs: self.max_interval_secs.max(fallback),
+            timeout_secs: self.timeout_secs,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Public handle
+// ---------------------------------------------------------------------------
+
+/// Result URLs of a succeeded ARK video task.
+#[derive(Debug, Clone)]
+pub struct VideoOutput {
+    pub video_url: String,
+    /// Present when the task was created with `return_last_frame`.
+    pub last_frame_url: Option<String>,
+    pub seed: Option<i64>,
+    /// Billed tokens, when ARK reports usage.
+    pub usage_tokens: Option<i64>,
+}
+
+enum Command {
+    Track {
+        ark_task_id: String,
+        policy: PollPolicy,
+        reply: oneshot::Sender<Result<VideoOutput>>,
+    },
+    Wake(String),
+}
+
+pub struct VideoPoller {
+    ark: Arc<ArkClient>,
+    overrides: RwLock<HashMap<String, PollPolicy>>,
+    /// Started lazily on first use so construction needs no runtime.
+    tx: OnceLock<mpsc::UnboundedSender<Command>>,
+}
+
+impl VideoPoller {
+    pub fn new(ark: Arc<ArkClient>) -> Self {
+        Self {
+            ark,
+            overrides: RwLock::new(HashMap::new()),
+            tx: OnceLock::new(),
+        }
+    }
+
+    /// Replace the per-model policy overrides (keyed by model ID).
+    pub fn set_policy_overrides(&self, overrides: HashMap<String, PollPolicy>) {
+        *self.overrides.write().unwrap_or_else(|e| e.into_inner()) = overrides;
+    }
+
+    pub fn policy_for(&self, model: &str) -> PollPolicy {
+        let overrides = self.overrides.read().unwrap_or_else(|e| e.into_inner());
+        overrides
+            .get(model)
+            .copied()
+            .unwrap_or_else(|| PollPolicy::for_model(model))
+    }
+
+    /// Track `ark_task_id` until it finishes and return its result URLs.
+    pub async fn wait(
+        &self,
+        ark_task_id: &str,
+        model: &str,
+        callbacks: Option<&ArkCallbacks>,
+    ) -> Result<VideoOutput> {
+        let mut policy = self.policy_for(model);
+        if callbacks.is_some() {
+            policy = policy.with_callbacks();
+        }
+
+        let tx = self.sender().clone();
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(Command::Track {
+            ark_task_id: ark_task_id.to_string(),
+            policy,
+            reply: reply_tx,
+        })
+        .map_err(|_| anyhow!("video poller stopped"))?;
+
+        // Forward ARK callbacks for this task as wake-ups.
+        let forwarder = callbacks.map(|cb| {
+            let mut notify = cb.register(ark_task_id);
+            let id = ark_task_id.to_string();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                while let Some(status) = notify.recv().await {
+                    info!(
+                        ark_task_id = %id,
+                        status = status.status.as_deref().unwrap_or("?"),
+                        "callback received, checking video task"
+                    );
+                    if tx.send(Command::Wake(id.clone())).is_err() {
+                        break;
+                    }
+                }
+            })
+        });
+
+        let result = reply_rx
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("video poller stopped")));
+
+        if let Some(cb) = callbacks {
+            cb.unregister(ark_task_id);
+        }
+        if let Some(handle) = forwarder {
+            handle.abort();
+        }
+        result
+    }
+
+    fn sender(&self) -> &mpsc::UnboundedSender<Command> {
+        self.tx.get_or_init(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tokio::spawn(run(Arc::clone(&self.ark), rx));
+            tx
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Poll loop
+// ---------------------------------------------------------------------------
+
+struct Tracked {
+    policy: PollPolicy,
+    started: Instant,
+    next_check: Instant,
+    checks: u32,
+    errors: u32,
+    reply: oneshot::Sender<Result<VideoOutput>>,
+}
+
+async fn run(ark: Arc<ArkClient>, mut rx: mpsc::UnboundedReceiver<Command>) {
+    let mut tracked: HashMap<String, Tracked> = HashMap::new();
+    // Set while the list endpoint is failing; polled per task until then
+    let mut batch_paused_until: Option<Instant> = None;
+
+    loop {
+        let next = tracked
+            .values()
+            .map(|t| t.next_check)
+            .min()
+            .unwrap_or_else(|| Instant::now() + IDLE_WAIT);
+
+        tokio::select! {
+            cmd = rx.recv() => match cmd {
+                Some(Command::Track { ark_task_id, policy, reply }) => {
+                    let now = Instant::now();
+                    tracked.insert(ark_task_id, Tracked {
+                        policy,
+                        started: now,
+                        next_check: now + policy.initial_interval(),
+                        checks: 0,
+                        errors: 0,
+                        reply,
+                    });
+                }
+                Some(Command::Wake(id)) => {
+                    if let Some(t) = tracked.get_mut(&id) {
+                        t.next_check = Instant::now();
+                    }
+                }
+                None => break,
+            },
+            _ = sleep_until(next) => {
+                poll_due(&ark, &mut tracked, &mut batch_paused_until).await;
+            }
+        }
+    }
+}
+
+async fn poll_due(
+    ark: &Arc<ArkClient>,
+    tracked: &mut HashMap<String, Tracked>,
+    batch_paused_until: &mut Option<Instant>,
+) {
+    let now = Instant::now();
+
+    let expired: Vec<String> = tracked
+        .iter()
+        .filter(|(_, t)| now.duration_since(t.started) > t.policy.timeout())
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in expired {
+        if let Some(t) = tracked.remove(&id) {
+            let _ = t.reply.send(Err(anyhow!(
+                "video generation timed out after {}s (ark_task: {id})",
+                t.policy.timeout_secs
+            )));
+        }
+    }
+
+    let due: Vec<String> = tracked
+        .iter()
+        .filter(|(_, t)| t.next_check <= now + COALESCE_WINDOW)
+        .map(|(id, _)| id.clone())
+        .collect();
+    if due.is_empty() {
+        return;
+    }
+
+    let mut statuses = fetch_statuses(ark, &due, batch_paused_until).await;
+    let now = Instant::now();
+
+    for id in due {
+        let Some(t) = tracked.get_mut(&id) else { continue };
+        match statuses.remove(&id) {
+            Some(Ok(status)) => match outcome(&id, status) {
+                Some(result) => {
+                    if let Some(t) = tracked.remove(&id) {
+                        let _ = t.reply.send(result);
+                    }
+                }
+                None => {
+                    t.checks += 1;
+                    t.errors = 0;
+                    t.next_check = now + t.policy.interval_after(t.checks);
+                }
+            },
+            failed => {
+                let err = match failed {
+                    Some(Err(e)) => e,
+                    _ => anyhow!("task missing from ARK status response"),
+                };
+                t.errors += 1;
+                if t.errors >= MAX_CONSECUTIVE_ERRORS {
+                    if let Some(t) = tracked.remove(&id) {
+                        let _ = t.reply.send(Err(err.context(format!(
+                            "video status check failed {MAX_CONSECUTIVE_ERRORS} times (ark_task: {id})"
+                        ))));
+                    }
+                } else {
+                    warn!(ark_task_id = %id, "video status check failed: {err:#}");
+                    t.next_check = now + t.policy.interval_after(t.checks);
+                }
+            }
+        }
+    }
+}
+
+/// Query the status of every task in `ids`, batched through the list endpoint
+/// when more than one is due. A failed list query pauses batching for
+/// [`BATCH_RETRY_AFTER`].
+async fn fetch_statuses(
+    ark: &Arc<ArkClient>,
+    ids: &[String],
+    batch_paused_until: &mut Option<Instant>,
+) -> HashMap<String, Result<VideoTaskStatus>> {
+    let mut results = HashMap::new();
+    let mut singles: Vec<String> = Vec::new();
+
+    if batch_paused_until.is_some_and(|until| Instant::now() >= until) {
+        *batch_paused_until = None;
+    }
+    if batch_paused_until.is_none() && ids.len() > 1 {
+        for chunk in ids.chunks(BATCH_LIMIT) {
+            match ark.list_video_tasks(chunk).await {
+                Ok(list) => {
+                    for item in list.items {
+                        if let Some(id) = item.id.clone() {
+                            results.insert(id, Ok(item));
+                        }
+                    }
+                    singles.extend(chunk.iter().filter(|id| !results.contains_key(*id)).cloned());
+                }
+                Err(e) => {
+                    warn!("video list query failed, polling per task for a while: {e:#}");
+                    *batch_paused_until = Some(Instant::now() + BATCH_RETRY_AFTER);
+                    singles.extend(chunk.iter().cloned());
+                }
+            }
+        }
+    } else {
+        singles.extend(ids.iter().cloned());
+    }
+
+    let mut set = JoinSet::new();
+    for id in singles {
+        let ark = Arc::clone(ark);
+        set.spawn(async move {
+            let status = ark.get_video_task(&id).await;
+            (id, status)
+        });
+    }
+    while let Some(joined) = set.join_next().await {
+        if let Ok((id, status)) = joined {
+            results.insert(id, status);
+        }
+    }
+    results
+}
+
+/// `Some(result)` once the task is terminal, `None` while it is still going.
+fn outcome(ark_task_id: &str, status: VideoTaskStatus) -> Option<Result<VideoOutput>> {
+    match status.status.as_deref() {
+        Some("succeeded") => Some(
+            status
+                .content
+                .and_then(|c| {
+                    Some(VideoOutput {
+                        video_url: c.video_url?,
+                        last_frame_url: c.last_frame_url,
+                        seed: status.seed,
+                        usage_tokens: status.usage.and_then(|u| u.billed_tokens()),
+                    })
+                })
+                .ok_or_else(|| anyhow!("succeeded but no video URL")),
+        ),
+        Some(s @ ("failed" | "expired" | "cancelled")) => {
+            let msg = status
+                .error
+                .and_then(|e| e.message)
+                .unwrap_or_else(|| "unknown error".to_string());
+            Some(Err(anyhow!("video task {s}: {msg} (ark_task: {ark_task_id})")))
+        }
+        Some(s) => {
+            info!(ark_task_id = %ark_task_id, status = %s, "polling video task...");
+            None
+        }
+        None => {
+            warn!(ark_task_id = %ark_task_id, "poll returned no status");
+            None
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests — against a local fake ARK server
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ark::callback::{self, read_request};
+    use crate::ark::types::VideoGenRequest;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    const VIDEO_URL: &str = "https://example.com/out.mp4";
+    const PUBLIC_URL: &str = "https://tunnel.example.com/ark/callback";
+
+    #[derive(Default)]
+    struct FakeArk {
+        callback_sent: AtomicBool,
+        list_requests: AtomicUsize,
+        single_requests: AtomicUsize,
+    }
+
+    fn succeeded(id: &str) -> serde_json::Value {
+        serde_json::json!({ "id": id, "status": "succeeded", "content": { "video_url": VIDEO_URL } })
+    }
+
+    /// Fake ARK. Creation returns `cgt-cb` and then POSTs a `succeeded`
+    /// callback; acting as the tunnel too, it delivers the POST for
+    /// `PUBLIC_URL` to `receiver`. `cgt-cb` reports `running` until that
+    /// callback goes out; every other task ID is already `succeeded`.
+    async fn spawn_fake_ark(receiver: Option<SocketAddr>) -> (SocketAddr, Arc<FakeArk>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(FakeArk::default());
+        let shared = Arc::clone(&state);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let state = Arc::clone(&shared);
+                tokio::spawn(async move {
+                    let req = read_request(&mut stream).await.unwrap();
+                    let status_of = |id: &str| {
+                        if id == "cgt-cb" && !state.callback_sent.load(Ordering::SeqCst) {
+                            serde_json::json!({ "id": id, "status": "running" })
+                        } else {
+                            succeeded(id)
+                        }
+                    };
+                    let body = if req.method == "POST" {
+                        let create: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+                        assert_eq!(create["callback_url"], PUBLIC_URL);
+                        let receiver = receiver.expect("callback receiver");
+                        let state = Arc::clone(&state);
+                        tokio::spawn(async move {
+                            // Before sending: the GET the callback wakes must
+                            // already see `succeeded`
+                            state.callback_sent.store(true, Ordering::SeqCst);
+                            reqwest::Client::new()
+                                .post(format!("http://{receiver}/ark/callback"))
+                                .json(&succeeded("cgt-cb"))
+                                .send()
+                                .await
+                                .unwrap();
+                        });
+                        serde_json::json!({ "id": "cgt-cb" })
+                    } else if let Some(query) = req.path.strip_prefix("/contents/generations/tasks?") {
+                        state.list_requests.fetch_add(1, Ordering::SeqCst);
+                        let items: Vec<_> = query
+                            .split('&')
+                            .filter_map(|kv| kv.strip_prefix("filter.task_ids="))
+                            .map(status_of)
+                            .collect();
+                        serde_json::json!({ "items": items, "total": items.len() })
+                    } else {
+                        state.single_requests.fetch_add(1, Ordering::SeqCst);
+                        let id = req.path.rsplit('/').next().unwrap();
+                        status_of(id)
+                    };
+                    let body = body.to_string();
+                    let resp = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    stream.write_all(resp.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        (addr, state)
+    }
+
+    #[tokio::test]
+    async fn callback_completes_task_without_waiting_for_poll() {
+        let (callbacks, server) = callback::bind("127.0.0.1:0", PUBLIC_URL).unwrap();
+        let receiver_addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let (ark_addr, _) = spawn_fake_ark(Some(receiver_addr)).await;
+        let ark = Arc::new(ArkClient::new(format!("http://{ark_addr}"), "test-key".into()));
+
+        let req = VideoGenRequest {
+            model: "test-model".into(),
+            content: vec![],
+            resolution: None,
+            ratio: None,
+            duration: None,
+            watermark: false,
+            callback_url: Some(callbacks.public_url().to_string()),
+            return_last_frame: None,
+        };
+        let ark_task_id = ark.create_video_task(&req).await.unwrap();
+
+        // With callbacks on, polling drops to the 60s fallback, so only the
+        // callback can complete this within the test timeout.
+        let poller = VideoPoller::new(ark);
+        let out = tokio::time::timeout(
+            Duration::from_secs(10),
+            poller.wait(&ark_task_id, "test-model", Some(&callbacks)),
+        )
+        .await
+        .expect("callback should wake the poller")
+        .unwrap();
+        assert_eq!(out.video_url, VIDEO_URL);
+    }
+
+    #[tokio::test]
+    async fn concurrent_tasks_share_one_list_query() {
+        let (ark_addr, fake) = spawn_fake_ark(None).await;
+        let ark = Arc::new(ArkClient::new(format!("http://{ark_addr}"), "test-key".into()));
+
+        let poller = VideoPoller::new(ark);
+        let fast = PollPolicy { initial_interval_secs: 1, max_interval_secs: 1, timeout_secs: 30 };
+        poller.set_policy_overrides(HashMap::from([("test-model".to_string(), fast)]));
+
+        let (a, b, c) = tokio::join!(
+            poller.wait("cgt-a", "test-model", None),
+            poller.wait("cgt-b", "test-model", None),
+            poller.wait("cgt-c", "test-model", None),
+        );
+        for out in [a, b, c] {
+            assert_eq!(out.unwrap().video_url, VIDEO_URL);
+        }
+        assert_eq!(fake.list_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(fake.single_requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn interval_backs_off_to_policy_max() {
+        let policy = PollPolicy { initial_interval_secs: 4, max_interval_secs: 10, timeout_secs: 60 };
+        assert_eq!(policy.interval_after(0), Duration::from_secs(4));
+        assert_eq!(policy.interval_after(1), Duration::from_secs(6));
+        assert_eq!(policy.interval_after(5), Duration::from_secs(10));
+        assert!(PollPolicy::for_model("doubao-seedance-1-0-pro-fast-251015").initial_interval_secs
+            < PollPolicy::for_model("doubao-seedance-1-5-pro-251215").initial_interval_secs);
+    }
+}
@@ -1,38 +1,34 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use std::path::PathBuf;
-use tauri::{AppHandle, Emitter};
-use tokio::time::{sleep, Duration, Instant};
 use tracing::{error, info, warn};
 
+use super::events::TaskEventBus;
+use super::poller::VideoPoller;
 use super::SharedDb;
-use crate::ark::types::{VideoContentItem, VideoGenRequest};
+use crate::ark::callback::ArkCallbacks;
+use crate::ark::types::{VideoContentItem, VideoGenRequest, VideoImageUrl};
 use crate::ark::ArkClient;
-use crate::db::{AssetRow, TaskRow};
-
-const POLL_INTERVAL: Duration = Duration::from_secs(5);
-const POLL_TIMEOUT: Duration = Duration::from_secs(600); // 10 minutes
+use crate::db::{AssetRow, TaskRow, TaskStatus};
+use crate::media::provenance::{self, Provenance};
+use crate::media::{probe, thumbnail};
+use crate::storage::AssetStore;
 
 /// Execute video generation: create task, poll until done, download video, write asset.
 pub async fn run_video_task(
     db: &SharedDb,
     ark: &ArkClient,
-    app_handle: &Option<AppHandle>,
+    poller: &VideoPoller,
+    callbacks: Option<&ArkCallbacks>,
+    events: &TaskEventBus,
     task: &TaskRow,
-    projects_dir: &PathBuf,
+    store: &AssetStore,
 ) {
     let task_id = task.id.clone();
 
-    if let Err(e) = execute(db, ark, task, projects_dir).await {
+    if let Err(e) = execute(db, ark, poller, callbacks, events, task, store).await {
         error!(task_id = %task_id, "video task failed: {e:#}");
         if let Ok(guard) = db.lock() {
-            let _ = guard.update_task(&task_id, "failed", None, None, Some(&format!("{e:#}")));
-        }
-        if let Some(ref handle) = app_handle {
-            let _ = handle.emit("task:complete", serde_json::json!({
-                "taskId": task_id,
-                "status": "failed",
-                "error": format!("{e:#}"),
-            }));
+            let _ = guard.update_task(&task_id, TaskStatus::Failed, None, None, Some(&format!("{e:#}")));
         }
         return;
     }
@@ -43,8 +39,11 @@ pub async fn run_video_task(
 async fn execute(
     db: &SharedDb,
     ark: &ArkClient,
+    poller: &VideoPoller,
+    callbacks: Option<&ArkCallbacks>,
+    events: &TaskEventBus,
     task: &TaskRow,
-    projects_dir: &PathBuf,
+    store: &AssetStore,
 ) -> Result<()> {
     let input: serde_json::Value =
         serde_json::from_str(&task.input).context("invalid task input JSON")?;
@@ -58,103 +57,117 @@ async fn execute(
     let ratio = input["ratio"].as_str().map(String::from);
     let duration = input["duration"].as_i64().map(|v| v as i32);
 
-    // Mark as running
-    {
-        let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
-        guard.update_task(&task.id, "running", None, None, None)?;
-    }
-
-    // Step 1: Create async video generation task
-    let req = VideoGenRequest {
-        model: model.to_string(),
-        content: vec![VideoContentItem {
-            content_type: "text".to_string(),
-            text: Some(prompt.to_string()),
-        }],
-        resolution,
-        ratio,
-        duration,
-        watermark: false,
-    };
+    // Mark as running (keeping any ARK task ID from a previous lease holder)
+    super::mark_status(db, events, &task.id, TaskStatus::Running, task.ark_task_id.as_deref())?;
 
-    let ark_task_id = ark.create_video_task(&req).await?;
-    {
-        let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
-        guard.update_task(&task.id, "running", None, Some(&ark_task_id), None)?;
-    }
-
-    // Step 2: Poll for completion
-    let start = Instant::now();
-    let video_url = loop {
-        if start.elapsed() > POLL_TIMEOUT {
-            bail!(
-                "video generation timed out after {}s (ark_task: {ark_task_id})",
-                POLL_TIMEOUT.as_secs()
-            );
+    // Step 1: Create async video generation task — unless a previous lease
+    // holder already did, in which case keep waiting on that one.
+    let ark_task_id = match task.ark_task_id.clone() {
+        Some(existing) => {
+            info!(task_id = %task.id, ark_task_id = %existing, "resuming existing ARK video task");
+            existing
         }
-
-        sleep(POLL_INTERVAL).await;
-
-        let status = ark.get_video_task(&ark_task_id).await?;
-        match status.status.as_deref() {
-            Some("succeeded") => {
-                let url = status
-                    .content
-                    .and_then(|c| c.video_url)
-                    .ok_or_else(|| anyhow::anyhow!("succeeded but no video URL"))?;
-                break url;
-            }
-            Some("failed") | Some("expired") | Some("cancelled") => {
-                let msg = status
-                    .error
-                    .and_then(|e| e.message)
-                    .unwrap_or_else(|| "unknown error".to_string());
-                bail!(
-                    "video task {}: {} (ark_task: {ark_task_id})",
-                    status.status.as_deref().unwrap_or("unknown"),
-                    msg
-                );
-            }
-            Some(s) => {
-                info!(ark_task_id = %ark_task_id, status = %s, "polling video task...");
-            }
-            None => {
-                warn!(ark_task_id = %ark_task_id, "poll returned no status");
+        None => {
+            let mut content = vec![VideoContentItem {
+                content_type: "text".to_string(),
+                text: Some(prompt.to_string()),
+                image_url: None,
+                role: None,
+            }];
+            if let Some(id) = input["first_frame_asset_id"].as_str() {
+                let url = super::source_data_urls(db, &[id.to_string()]).await?.remove(0);
+                content.push(VideoContentItem {
+                    content_type: "image_url".to_string(),
+                    text: None,
+                    image_url: Some(VideoImageUrl { url }),
+                    role: Some("first_frame".to_string()),
+                });
             }
+            let req = VideoGenRequest {
+                model: model.to_string(),
+                content,
+                resolution,
+                ratio,
+                duration,
+                watermark: false,
+                callback_url: callbacks.map(|cb| cb.public_url().to_string()),
+                return_last_frame: Some(true),
+            };
+
+            ark.create_video_task(&req).await?
         }
     };
+    super::mark_status(db, events, &task.id, TaskStatus::QueuedRemote, Some(&ark_task_id))?;
+
+    // Step 2: Wait for completion via the shared poller (callback-driven when enabled)
+    let result = poller.wait(&ark_task_id, model, callbacks).await?;
+    super::mark_status(db, events, &task.id, TaskStatus::Downloading, Some(&ark_task_id))?;
 
     // Step 3: Download video → write to assets
     let http = reqwest::Client::new();
     let video_bytes = http
-        .get(&video_url)
+        .get(&result.video_url)
         .send()
         .await?
         .bytes()
         .await
         .context("failed to download video")?;
 
-    let asset_dir = projects_dir.join(&task.project_id).join("assets");
-    tokio::fs::create_dir_all(&asset_dir).await?;
-
-    let filename = format!("{}.mp4", uuid::Uuid::new_v4());
-    let asset_path = asset_dir.join(&filename);
-    tokio::fs::write(&asset_path, &video_bytes).await?;
+    let info = match probe::probe_bytes(&video_bytes) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warn!(task_id = %task.id, "failed to probe downloaded video: {e:#}");
+            None
+        }
+    };
+    let (width, height) = info
+        .as_ref()
+        .and_then(|i| i.width.zip(i.height))
+        .unwrap_or((1280, 720));
+
+    let record = Provenance {
+        prompt: Some(prompt.to_string()),
+        model: Some(model.to_string()),
+        seed: result.seed,
+        size: Some(format!("{width}x{height}")),
+        task_id: Some(task.id.clone()),
+        created_at: Some(chrono::Utc::now().to_rfc3339()),
+        creator_tool: Some(provenance::CREATOR_TOOL.to_string()),
+    };
+    let remote_sha256 = crate::storage::sha256_hex(&video_bytes);
+    let video_bytes = super::with_provenance(video_bytes.to_vec(), &record, &task.id);
 
-    let file_size = video_bytes.len() as i64;
+    let stored = store.write(&task.project_id, &video_bytes, "mp4").await?;
+    let (asset_path, filename) = (stored.path.clone(), stored.file_name.clone());
+    let file_size = stored.size as i64;
 
     let output = serde_json::json!({
         "assetPath": asset_path.to_string_lossy(),
-        "width": 1280,
-        "height": 720,
+        "width": width,
+        "height": height,
+        "duration": info.as_ref().and_then(|i| i.duration),
+        // Signed and short-lived, but lets reconcile re-download a lost file
+        "remoteUrl": result.video_url,
+        "remoteSha256": remote_sha256,
+        "usageTokens": result.usage_tokens,
     });
 
+    // Step 4: Poster frame + library thumbnail — optional, never fails the task
+    let project_dir = store.project_dir(&task.project_id);
+    let poster = match result.last_frame_url {
+        Some(ref url) => fetch_poster(&http, url, project_dir, filename.clone())
+            .await
+            .map_err(|e| warn!(task_id = %task.id, "failed to store poster frame: {e:#}"))
+            .ok(),
+        None => None,
+    };
+
     {
         let guard = db.lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))?;
-        guard.update_task(&task.id, "done", Some(&output.to_string()), Some(&ark_task_id), None)?;
+        guard.update_task(&task.id, TaskStatus::Done, Some(&output.to_string()), Some(&ark_task_id), None)?;
 
         // Record the generated asset in the assets table
-        let asset = AssetRow {
+        let mut asset = AssetRow {
             id: uuid::Uuid::new_v4().to_string(),
             project_id: task.project_id.clone(),
             task_id: Some(task.id.clone()),
@@ -163,16 +176,42 @@ async fn execute(
             file_name: filename.clone(),
             prompt: Some(prompt.to_string()),
             model: Some(model.to_string()),
-            width: Some(1280),
-            height: Some(720),
+            width: Some(width as i32),
+            height: Some(height as i32),
             file_size: Some(file_size),
             source: "generated".to_string(),
             created_at: task.created_at.clone(),
+            duration: None,
+            frame_rate: None,
+            codec: None,
+            thumbnail_path: poster.as_ref().map(|(_, thumb)| thumb.to_string_lossy().to_string()),
+            poster_path: poster.as_ref().map(|(poster, _)| poster.to_string_lossy().to_string()),
+            sha256: Some(stored.sha256.clone()),
+            favorite: false,
+            rating: None,
+            tags: Vec::new(),
+            palette: Vec::new(),
+            notes: None,
         };
-        if let Err(e) = guard.insert_asset(&asset) {
-            error!(task_id = %task.id, "failed to insert asset record: {e:#}");
+        if let Some(ref info) = info {
+            asset.set_media_info(info);
+        }
+        match guard.insert_asset(&asset) {
+            Ok(_) => super::record_lineage(&guard, &asset.id, &task.id, &input),
+            Err(e) => error!(task_id = %task.id, "failed to insert asset record: {e:#}"),
         }
     }
 
     Ok(())
 }
+
+/// Download ARK's last frame and store it as the asset's poster and thumbnail.
+async fn fetch_poster(
+    http: &reqwest::Client,
+    url: &str,
+    project_dir: PathBuf,
+    asset_file_name: String,
+) -> Result<(PathBuf, PathBuf)> {
+    let bytes = http.get(url).send().await?.error_for_status()?.bytes().await?;
+    tokio::task::spawn_blocking(move || thumbnail::store_poster(&project_dir, &asset_file_name, &bytes)).await?
+}
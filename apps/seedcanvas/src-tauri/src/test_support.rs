@@ -0,0 +1,41 @@
+//! Fixtures shared by the unit tests: a throwaway library with one project,
+//! `p1`, and image rows in it.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::db::{AssetRow, Db};
+use crate::library::AssetLibrary;
+
+/// A library in a fresh temp directory with an empty `p1` project. Returns
+/// the root, for the test to remove when it is done.
+pub(crate) fn setup() -> (PathBuf, AssetLibrary) {
+    let root = std::env::temp_dir().join(format!("seedcanvas-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("projects/p1/assets")).unwrap();
+    std::fs::write(root.join("projects/p1/manifest.json"), "{}").unwrap();
+    let db = Db::open(&root.join("test.db")).unwrap();
+    (root.clone(), AssetLibrary::new(Arc::new(Mutex::new(db)), root.join("projects")))
+}
+
+/// A generated image row in `p1` for the file at `path`, not yet stored.
+pub(crate) fn asset_row(id: &str, path: &Path) -> AssetRow {
+    AssetRow {
+        id: id.into(),
+        project_id: "p1".into(),
+        asset_type: "image".into(),
+        file_path: path.to_string_lossy().to_string(),
+        file_name: path.file_name().unwrap().to_string_lossy().to_string(),
+        source: "generated".into(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    }
+}
+
+/// Write `bytes` to `p1/assets/{file_name}` and record it as asset `id`.
+pub(crate) fn add_asset(lib: &AssetLibrary, id: &str, file_name: &str, bytes: &[u8]) -> AssetRow {
+    let path = lib.project_dir("p1").join("assets").join(file_name);
+    std::fs::write(&path, bytes).unwrap();
+    let asset = AssetRow { file_size: Some(bytes.len() as i64), ..asset_row(id, &path) };
+    lib.db().lock().unwrap().insert_asset(&asset).unwrap();
+    asset
+}
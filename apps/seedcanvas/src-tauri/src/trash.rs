@@ -0,0 +1,440 @@
+//! Trash for deleted projects and assets.
+//!
+//! Deleting moves things here instead of removing them:
+//!
+//! ```text
+//! {data_dir}/trash/projects/{project_id}/          — the whole project directory
+//! {data_dir}/trash/assets/{asset_id}/file-…        — an asset's file, thumbnail
+//!                                    thumbnail-…     and poster
+//!                                    poster-…
+//! ```
+//!
+//! The rows stay in the DB with `deleted_at` set, so everything can be put
+//! back where it was until the retention period (`trashRetentionDays` in
+//! settings.json) runs out and [`sweep`] purges it for good. A file another
+//! live asset still uses (content-addressed storage) stays where it is.
+//!
+//! Everything here except [`sweep`] is blocking.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::bundle::validate_project_id;
+use crate::db::{AssetRow, TrashedAsset, TrashedProject};
+use crate::library::AssetLibrary;
+
+/// Days trashed items are kept when `trashRetentionDays` is unset.
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// `{data_dir}/trash`, next to the projects directory.
+pub fn trash_dir(projects_dir: &Path) -> PathBuf {
+    projects_dir.parent().unwrap_or(projects_dir).join(crate::datadir::TRASH_DIR)
+}
+
+fn asset_trash_dir(library: &AssetLibrary, asset_id: &str) -> PathBuf {
+    trash_dir(library.projects_dir()).join("assets").join(asset_id)
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+// ---------------------------------------------------------------------------
+// Assets
+// ---------------------------------------------------------------------------
+
+/// The files an asset owns on disk, with the role prefixing their name in
+/// the trash.
+fn asset_files(asset: &AssetRow) -> impl Iterator<Item = (&'static str, &str)> {
+    [
+        ("file", Some(asset.file_path.as_str())),
+        ("thumbnail", asset.thumbnail_path.as_deref()),
+        ("poster", asset.poster_path.as_deref()),
+    ]
+    .into_iter()
+    .filter_map(|(role, path)| path.map(|path| (role, path)))
+}
+
+fn trashed_name(role: &str, path: &str) -> String {
+    let base = Path::new(path).file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    format!("{role}-{base}")
+}
+
+/// Move an asset's files into its trash directory. Returns the original
+/// paths that were moved; files already gone are skipped.
+pub(crate) fn stash_asset_files(library: &AssetLibrary, asset: &AssetRow, warnings: &mut Vec<String>) -> Vec<String> {
+    let dir = asset_trash_dir(library, &asset.id);
+    let mut moved = Vec::new();
+    for (role, path) in asset_files(asset) {
+        if !Path::new(path).exists() {
+            continue;
+        }
+        let dest = dir.join(trashed_name(role, path));
+        match std::fs::create_dir_all(&dir).and_then(|()| move_file(Path::new(path), &dest)) {
+            Ok(()) => moved.push(path.to_string()),
+            Err(e) => warnings.push(format!("failed to move {path} to the trash: {e}")),
+        }
+    }
+    moved
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreOutcome {
+    pub restored: Vec<String>,
+    /// Requested IDs that are not in the trash.
+    pub not_found: Vec<String>,
+    /// Assets that could not be restored, and previews that were missing.
+    pub warnings: Vec<String>,
+}
+
+/// Put trashed assets back: their files return to their original paths and
+/// their rows become visible again. Assets of a trashed project come back
+/// with [`restore_project`] instead.
+pub fn restore_assets(library: &AssetLibrary, ids: &[String]) -> Result<RestoreOutcome> {
+    let trashed = lock(library)?.get_trashed_assets(ids)?;
+    let mut outcome = RestoreOutcome {
+        not_found: ids
+            .iter()
+            .filter(|id| !trashed.iter().any(|t| &t.asset.id == *id))
+            .cloned()
+            .collect(),
+        ..Default::default()
+    };
+
+    for TrashedAsset { asset, .. } in trashed {
+        if !library.project_dir(&asset.project_id).is_dir() {
+            outcome
+                .warnings
+                .push(format!("{}: project {} no longer exists", asset.id, asset.project_id));
+            continue;
+        }
+        match restore_asset_files(library, &asset) {
+            Ok(missing) => outcome.warnings.extend(missing),
+            Err(e) => {
+                outcome.warnings.push(format!("{}: {e:#}", asset.id));
+                continue;
+            }
+        }
+        lock(library)?.restore_asset_rows(std::slice::from_ref(&asset.id))?;
+        let _ = std::fs::remove_dir_all(asset_trash_dir(library, &asset.id));
+        outcome.restored.push(asset.id);
+    }
+    Ok(outcome)
+}
+
+/// Returns warnings for previews that could not be found. Fails if the
+/// asset's file itself is gone.
+fn restore_asset_files(library: &AssetLibrary, asset: &AssetRow) -> Result<Vec<String>> {
+    let dir = asset_trash_dir(library, &asset.id);
+    let mut warnings = Vec::new();
+    for (role, path) in asset_files(asset) {
+        let dest = Path::new(path);
+        let stashed = dir.join(trashed_name(role, path));
+        if dest.exists() {
+            // Shared with a live asset, or the same content was stored again
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        if stashed.exists() {
+            move_file(&stashed, dest).with_context(|| format!("failed to restore {path}"))?;
+            continue;
+        }
+        // A shared file goes to the trash with the last row deleted
+        let others = lock(library)?.trashed_asset_ids_for_file(&asset.file_path)?;
+        let shared = others
+            .iter()
+            .filter(|id| *id != &asset.id)
+            .map(|id| asset_trash_dir(library, id).join(trashed_name(role, path)))
+            .find(|p| p.exists());
+        match shared {
+            Some(src) => {
+                std::fs::copy(&src, dest).with_context(|| format!("failed to restore {path}"))?;
+            }
+            None if role == "file" => bail!("{path} is gone"),
+            None => warnings.push(format!("{}: {role} {path} is gone", asset.id)),
+        }
+    }
+    Ok(warnings)
+}
+
+// ---------------------------------------------------------------------------
+// Projects
+// ---------------------------------------------------------------------------
+
+/// Move a project directory into the trash and mark its tasks and assets
+/// deleted. A project whose directory is already gone only has its rows
+/// trashed.
+pub fn trash_project(library: &AssetLibrary, project_id: &str) -> Result<TrashedProject> {
+    validate_project_id(project_id)?;
+    if lock(library)?.get_trashed_project(project_id)?.is_some() {
+        bail!("project {project_id} is already in the trash");
+    }
+    let src = library.project_dir(project_id);
+    let dest = trash_dir(library.projects_dir()).join("projects").join(project_id);
+    if dest.exists() {
+        bail!("{} already exists", dest.display());
+    }
+
+    let name = std::fs::read_to_string(src.join("manifest.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .and_then(|m| m["name"].as_str().map(String::from));
+    let project = TrashedProject {
+        project_id: project_id.to_string(),
+        name,
+        trash_dir: dest.to_string_lossy().to_string(),
+        deleted_at: now(),
+    };
+
+    let moved = src.is_dir();
+    if moved {
+        std::fs::create_dir_all(dest.parent().unwrap_or(&dest))?;
+        std::fs::rename(&src, &dest)
+            .with_context(|| format!("failed to move {} to the trash", src.display()))?;
+    }
+    let recorded = lock(library).and_then(|db| {
+        db.trash_project_rows(project_id, &project.deleted_at, false)?;
+        db.insert_trashed_project(&project)
+    });
+    if let Err(e) = recorded {
+        if moved {
+            let _ = std::fs::rename(&dest, &src);
+        }
+        return Err(e);
+    }
+    Ok(project)
+}
+
+/// Move a trashed project back into the projects directory and restore its
+/// rows. Assets trashed separately before the project stay in the trash.
+/// The project is not re-added to `projects.json`; that is up to the caller.
+pub fn restore_project(library: &AssetLibrary, project_id: &str) -> Result<TrashedProject> {
+    let project = lock(library)?
+        .get_trashed_project(project_id)?
+        .with_context(|| format!("project {project_id} is not in the trash"))?;
+    let src = PathBuf::from(&project.trash_dir);
+    if !src.is_dir() {
+        bail!("the files of project {project_id} are gone; only its records remain");
+    }
+    let dest = library.project_dir(project_id);
+    if dest.exists() {
+        bail!("{} already exists", dest.display());
+    }
+    std::fs::rename(&src, &dest).with_context(|| format!("failed to restore {}", dest.display()))?;
+
+    let db = lock(library)?;
+    db.restore_project_rows(project_id, &project.deleted_at)?;
+    db.delete_trashed_project(project_id)?;
+    Ok(project)
+}
+
+/// Mark a project's rows deleted without touching its directory — for a
+/// project the frontend already removed from disk. With `tasks_only` the
+/// asset rows stay live to keep tracking files that were kept.
+pub fn trash_project_records(library: &AssetLibrary, project_id: &str, tasks_only: bool) -> Result<()> {
+    if !tasks_only {
+        return trash_project(library, project_id).map(|_| ());
+    }
+    lock(library)?.trash_project_rows(project_id, &now(), true)
+}
+
+// ---------------------------------------------------------------------------
+// Listing and purging
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashListing {
+    pub projects: Vec<TrashedProject>,
+    /// Assets trashed on their own; those of trashed projects are not listed.
+    pub assets: Vec<TrashedAsset>,
+    pub retention_days: u32,
+}
+
+pub fn list_trash(library: &AssetLibrary) -> Result<TrashListing> {
+    let db = lock(library)?;
+    Ok(TrashListing {
+        projects: db.list_trashed_projects(None)?,
+        assets: db.list_trashed_assets(None)?,
+        retention_days: library.trash_retention_days(),
+    })
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeReport {
+    pub projects: Vec<String>,
+    pub assets: Vec<String>,
+    pub tasks: usize,
+    pub warnings: Vec<String>,
+}
+
+impl PurgeReport {
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty() && self.assets.is_empty() && self.tasks == 0
+    }
+}
+
+/// Permanently delete what was trashed before `before` (RFC 3339), or
+/// everything when `before` is `None`.
+pub fn purge(library: &AssetLibrary, before: Option<&str>) -> Result<PurgeReport> {
+    let (projects, assets, tasks) = {
+        let db = lock(library)?;
+        (
+            db.list_trashed_projects(before)?,
+            db.list_trashed_assets(before)?,
+            db.list_trashed_task_ids(before)?,
+        )
+    };
+    let mut report = PurgeReport::default();
+
+    for project in projects {
+        if let Err(e) = remove_dir(Path::new(&project.trash_dir)) {
+            report.warnings.push(format!("failed to remove {}: {e}", project.trash_dir));
+            continue;
+        }
+        let db = lock(library)?;
+        db.delete_all_project_data(&project.project_id)?;
+        db.delete_trashed_project(&project.project_id)?;
+        report.projects.push(project.project_id);
+    }
+
+    for TrashedAsset { asset, .. } in assets {
+        lock(library)?.delete_asset_rows(std::slice::from_ref(&asset.id))?;
+        report.assets.push(asset.id);
+    }
+
+    {
+        let db = lock(library)?;
+        for id in &tasks {
+            if db.delete_task(id)? {
+                report.tasks += 1;
+            }
+        }
+    }
+
+    // Asset directories whose row is gone — purged above, or with a project
+    let assets_dir = trash_dir(library.projects_dir()).join("assets");
+    for entry in std::fs::read_dir(&assets_dir).into_iter().flatten().flatten() {
+        let id = entry.file_name().to_string_lossy().to_string();
+        if lock(library)?.asset_row_exists(&id)? {
+            continue;
+        }
+        if let Err(e) = remove_dir(&entry.path()) {
+            report.warnings.push(format!("failed to remove {}: {e}", entry.path().display()));
+        }
+    }
+    Ok(report)
+}
+
+/// Purge expired trash every few hours, starting right away. The retention
+/// period is read from the library on each pass.
+pub async fn sweep(library: AssetLibrary) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let days = i64::from(library.trash_retention_days());
+        let cutoff = (chrono::Utc::now() - chrono::Duration::days(days)).to_rfc3339();
+        let lib = library.clone();
+        match tokio::task::spawn_blocking(move || purge(&lib, Some(&cutoff))).await {
+            Ok(Ok(report)) if report.is_empty() => {}
+            Ok(Ok(report)) => info!(
+                projects = report.projects.len(),
+                assets = report.assets.len(),
+                tasks = report.tasks,
+                "purged expired trash"
+            ),
+            Ok(Err(e)) => error!("trash sweep failed: {e:#}"),
+            Err(e) => error!("trash sweep panicked: {e}"),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+fn lock(library: &AssetLibrary) -> Result<std::sync::MutexGuard<'_, crate::db::Db>> {
+    library.db().lock().map_err(|e| anyhow::anyhow!("db lock: {e}"))
+}
+
+/// Rename, falling back to copy and remove across filesystems.
+fn move_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if std::fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(src, dest)?;
+    std::fs::remove_file(src)
+}
+
+fn remove_dir(dir: &Path) -> std::io::Result<()> {
+    match std::fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{add_asset, setup};
+
+    #[test]
+    fn trashed_assets_and_projects_restore_until_purged() {
+        let (root, lib) = setup();
+        std::fs::write(lib.project_dir("p1").join("manifest.json"), r#"{"name":"Poster"}"#).unwrap();
+        let a = add_asset(&lib, "a", "a.png", b"a");
+        let b = add_asset(&lib, "b", "b.png", b"b");
+
+        let deleted = lib.delete_assets(&["a".to_string()], false).unwrap();
+        assert!(deleted.trashed);
+        assert!(!Path::new(&a.file_path).exists());
+        assert!(asset_trash_dir(&lib, "a").join("file-a.png").exists());
+        let listing = list_trash(&lib).unwrap();
+        assert_eq!(listing.assets.len(), 1);
+        assert_eq!(listing.retention_days, DEFAULT_RETENTION_DAYS);
+
+        let restored = restore_assets(&lib, &["a".to_string(), "b".to_string()]).unwrap();
+        assert_eq!(restored.restored, vec!["a".to_string()]);
+        assert_eq!(restored.not_found, vec!["b".to_string()]);
+        assert_eq!(std::fs::read(&a.file_path).unwrap(), b"a");
+        assert!(!asset_trash_dir(&lib, "a").exists());
+
+        // A project trashed with an asset already in the trash brings back
+        // only what it took with it
+        lib.delete_assets(&["b".to_string()], false).unwrap();
+        let project = trash_project(&lib, "p1").unwrap();
+        assert_eq!(project.name.as_deref(), Some("Poster"));
+        assert!(!lib.project_dir("p1").exists());
+        assert!(lock(&lib).unwrap().list_all_assets(Some("p1")).unwrap().is_empty());
+        let listing = list_trash(&lib).unwrap();
+        assert_eq!((listing.projects.len(), listing.assets.len()), (1, 0));
+
+        restore_project(&lib, "p1").unwrap();
+        assert!(Path::new(&a.file_path).exists());
+        let live = lock(&lib).unwrap().list_all_assets(Some("p1")).unwrap();
+        assert_eq!(live.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+
+        // Nothing is old enough for a cutoff in the past; emptying purges all
+        let cutoff = (chrono::Utc::now() - chrono::Duration::days(1)).to_rfc3339();
+        assert!(purge(&lib, Some(&cutoff)).unwrap().is_empty());
+        let report = purge(&lib, None).unwrap();
+        assert_eq!(report.assets, vec!["b".to_string()]);
+        assert!(!asset_trash_dir(&lib, "b").exists());
+        assert!(!lock(&lib).unwrap().asset_row_exists("b").unwrap());
+        assert!(!Path::new(&b.file_path).exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -2,11 +2,32 @@ import { listen } from "@tauri-apps/api/event"
 import { AnimatePresence, motion } from "motion/react"
 import { Loader2 } from "lucide-react"
 import { useEffect, useState } from "react"
+import type { TaskEventPayload, TaskStatus } from "@/lib/commands"
 import { useCanvasStore } from "./store"
 
 interface ActiveTask {
   taskId: string
   type: "image" | "video"
+  status: TaskStatus
+}
+
+/** Insert or update the task carried by a submitted/progress event. */
+function upsertTask(tasks: ActiveTask[], data: TaskEventPayload): ActiveTask[] {
+  const next = { taskId: data.taskId, type: data.type, status: data.status }
+  if (!tasks.some((t) => t.taskId === data.taskId)) return [...tasks, next]
+  return tasks.map((t) => (t.taskId === data.taskId ? next : t))
+}
+
+function phaseLabel(task: ActiveTask): string {
+  const kind = task.type === "video" ? "video" : "image"
+  switch (task.status) {
+    case "queued_remote":
+      return `Waiting for ${kind}`
+    case "downloading":
+      return `Downloading ${kind}`
+    default:
+      return `Generating ${kind}`
+  }
 }
 
 export function GeneratingOverlay() {
@@ -17,25 +38,23 @@ export function GeneratingOverlay() {
     if (!projectId) return
 
     const unlisteners = [
-      listen<{ taskId: string; projectId: string; type: string }>(
-        "task:submitted",
-        (event) => {
-          const data = event.payload
-          if (data.projectId !== projectId) return
-          setActiveTasks((prev) => [
-            ...prev,
-            { taskId: data.taskId, type: data.type as "image" | "video" },
-          ])
-        }
-      ),
-      listen<{ taskId: string; projectId: string; status: string }>(
-        "task:complete",
-        (event) => {
-          const data = event.payload
-          if (data.projectId !== projectId) return
-          setActiveTasks((prev) => prev.filter((t) => t.taskId !== data.taskId))
-        }
-      ),
+      listen<TaskEventPayload>("task:submitted", (event) => {
+        const data = event.payload
+        if (data.projectId !== projectId) return
+        setActiveTasks((prev) => upsertTask(prev, data))
+      }),
+      // Also picks up tasks resumed after a restart, which never emit
+      // `task:submitted` in this session.
+      listen<TaskEventPayload>("task:progress", (event) => {
+        const data = event.payload
+        if (data.projectId !== projectId) return
+        setActiveTasks((prev) => upsertTask(prev, data))
+      }),
+      listen<TaskEventPayload>("task:complete", (event) => {
+        const data = event.payload
+        if (data.projectId !== projectId) return
+        setActiveTasks((prev) => prev.filter((t) => t.taskId !== data.taskId))
+      }),
     ]
 
     return () => {
@@ -47,7 +66,7 @@ export function GeneratingOverlay() {
 
   const generating = activeTasks[0]
 
-  const label = generating?.type === "video" ? "Generating video" : "Generating image"
+  const label = generating ? phaseLabel(generating) : ""
   const estimate = generating?.type === "video" ? "~1-2 min" : "~30s"
 
   return (
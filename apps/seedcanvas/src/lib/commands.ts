@@ -45,6 +45,28 @@ export interface TaskStatusResult {
   updatedAt?: string
 }
 
+export type TaskStatus =
+  | "pending"
+  | "running"
+  | "queued_remote"
+  | "downloading"
+  | "done"
+  | "failed"
+  | "cancelled"
+
+/** Payload of the `task:submitted`, `task:progress` and `task:complete` events. */
+export interface TaskEventPayload {
+  taskId: string
+  projectId: string
+  type: "image" | "video"
+  status: TaskStatus
+  output?: { assetPath: string; width: number; height: number } | null
+  error?: string | null
+  nodeId?: string | null
+  createdAt: string
+  updatedAt: string
+}
+
 // -- Assets --
 
 export interface AssetRow {
@@ -213,6 +235,18 @@ export function deleteProjectData(projectId: string, keepAssets: boolean): Promi
   return invoke("delete_project_data", { projectId, keepAssets })
 }
 
+export interface TrashedProject {
+  projectId: string
+  name: string | null
+  trashDir: string
+  deletedAt: string
+}
+
+/** Move a project directory to the trash and mark its tasks and assets deleted. */
+export function trashProject(projectId: string): Promise<TrashedProject> {
+  return invoke<TrashedProject>("trash_project", { projectId })
+}
+
 // ── MCP ───────────────────────────────────────────────────────────────────
 
 /** Resolve the absolute path to the bundled seedcanvas-mcp binary. */
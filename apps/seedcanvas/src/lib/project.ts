@@ -2,7 +2,7 @@ import { join } from "@tauri-apps/api/path"
 import { remove } from "@tauri-apps/plugin-fs"
 import type { CanvasFile } from "../canvas/types"
 import type { ProjectManifest, RecentProject } from "../project/types"
-import { deleteProjectData } from "./commands"
+import { deleteProjectData, trashProject } from "./commands"
 import { ensureDir, getDataDir, getProjectDir, readJson, writeJson } from "./fs"
 import { generateId } from "./id"
 
@@ -137,28 +137,22 @@ export async function deleteProject(
   id: string,
   options: { keepAssets?: boolean } = {}
 ): Promise<void> {
-  const projectDir = await getProjectDir(id)
-
   if (options.keepAssets) {
     // Delete everything except the assets/ subdirectory
+    const projectDir = await getProjectDir(id)
     const manifestPath = await join(projectDir, "manifest.json")
     const canvasPath = await join(projectDir, "canvas.json")
     const coverPath = await join(projectDir, "cover.png")
     for (const f of [manifestPath, canvasPath, coverPath]) {
       try { await remove(f) } catch { /* may not exist */ }
     }
+    // Preserve asset DB records so the kept files remain trackable
+    deleteProjectData(id, true).catch(() => {})
   } else {
-    try {
-      await remove(projectDir, { recursive: true })
-    } catch {
-      // Directory may already be gone
-    }
+    // The backend moves the directory into the trash with its rows
+    await trashProject(id)
   }
 
-  // Cleanup SQLite data for this project
-  // When keeping assets, preserve asset DB records so files remain trackable
-  deleteProjectData(id, options.keepAssets ?? false).catch(() => {})
-
   const projects = await listRecentProjects()
   const filtered = projects.filter((p) => p.id !== id)
   await saveRecentProjects(filtered)
@@ -8,6 +8,7 @@ import { GeneratingOverlay } from "@/canvas/GeneratingOverlay"
 import { setupMcpBridge } from "@/canvas/mcp-bridge"
 import { useCanvasStore } from "@/canvas/store"
 import { startAutoSave } from "@/lib/auto-save"
+import type { TaskEventPayload } from "@/lib/commands"
 import { assetUrl } from "@/lib/fs"
 import { type AppSettings, loadSettings } from "@/lib/settings"
 import { generateId } from "@/lib/id"
@@ -76,19 +77,13 @@ export function CanvasLayout() {
     return setupMcpBridge()
   }, [storeProjectId])
 
-  // Listen for task:complete events from Rust backend
+  // Place finished results on the canvas. In-flight `task:progress` events
+  // are tracked by <GeneratingOverlay />; failed and cancelled tasks leave
+  // the canvas untouched.
   useEffect(() => {
     if (!storeProjectId) return
 
-    const unlisten = listen<{
-      taskId: string
-      projectId: string
-      type: string
-      status: string
-      output?: { assetPath: string; width: number; height: number }
-      error?: string
-      nodeId?: string
-    }>("task:complete", (event) => {
+    const unlisten = listen<TaskEventPayload>("task:complete", (event) => {
       const data = event.payload
       if (data.projectId !== storeProjectId || data.status !== "done" || !data.output) return
 
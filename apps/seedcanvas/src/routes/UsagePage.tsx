@@ -1,4 +1,4 @@
-import { BarChart3, CheckCircle2, Film, Image, XCircle } from "lucide-react"
+import { Ban, BarChart3, CheckCircle2, Film, Image, XCircle } from "lucide-react"
 import { useEffect, useState } from "react"
 import { Badge } from "@/components/ui/badge"
 import { getUsageStats, type UsageStats } from "@/lib/commands"
@@ -172,8 +172,18 @@ function StatusBadge({ status }: { status: string }) {
           <XCircle size={12} /> Failed
         </span>
       )
+    case "cancelled":
+      return (
+        <span className="inline-flex items-center gap-1 text-xs text-muted-foreground">
+          <Ban size={12} /> Cancelled
+        </span>
+      )
     case "running":
       return <span className="text-xs text-blue-500">Running</span>
+    case "queued_remote":
+      return <span className="text-xs text-blue-500">Queued remotely</span>
+    case "downloading":
+      return <span className="text-xs text-blue-500">Downloading</span>
     default:
       return <span className="text-xs text-muted-foreground capitalize">{status}</span>
   }